@@ -0,0 +1,73 @@
+//! Compares the mutex and the atomic backends under contention: several threads apply
+//! `OP + 1` to the same calculator, and the time each backend takes is printed.
+//!
+//! Run with `cargo bench --bench contention`.
+
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use tpi_calculadora_distribuida::{
+    atomic_calculator::AtomicCalculator, calculator::Calculator, operation::Operation,
+    overflow::Overflow, width::Width,
+};
+
+/// The number of operations applied by each thread.
+const OPS_PER_THREAD: usize = 200_000;
+/// The numbers of threads compared.
+const THREADS: [usize; 4] = [1, 2, 4, 8];
+
+fn main() {
+    let width: Width = match "u64".parse() {
+        Ok(width) => width,
+        Err(_) => return eprintln!("invalid width"),
+    };
+
+    println!("threads  mutex (ms)  atomic (ms)");
+    for threads in THREADS {
+        let calculator = Arc::new(Mutex::new(Calculator::new(width, Overflow::Wrapping)));
+        let mutex = run(threads, move || {
+            if let Ok(mut calculator) = calculator.lock() {
+                let _ = calculator.apply(Operation::Add(1));
+            }
+        });
+
+        let calculator = Arc::new(AtomicCalculator::new(width, Overflow::Wrapping));
+        let atomic = run(threads, move || {
+            let _ = calculator.apply(&Operation::Add(1));
+        });
+
+        println!(
+            "{:>7}  {:>10}  {:>11}",
+            threads,
+            mutex.as_millis(),
+            atomic.as_millis()
+        );
+    }
+}
+
+/// Returns the time the given number of threads take to call `apply` `OPS_PER_THREAD`
+/// times each.
+fn run<F>(threads: usize, apply: F) -> Duration
+where
+    F: Fn() + Clone + Send + 'static,
+{
+    let start = Instant::now();
+    let handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let apply = apply.clone();
+            thread::spawn(move || {
+                for _ in 0..OPS_PER_THREAD {
+                    apply();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        if handle.join().is_err() {
+            eprintln!("thread join failure");
+        }
+    }
+    start.elapsed()
+}
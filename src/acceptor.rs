@@ -0,0 +1,67 @@
+use crate::{
+    errors::CalculatorErrors, log_event::LogEvent, logger::Logger, registry::Registry,
+    worker_pool::WorkerPool,
+};
+use std::{
+    io::ErrorKind,
+    net::{TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+/// The time the acceptor sleeps when no connection is pending, before checking again
+/// whether the server must shut down.
+const ACCEPT_POLL: Duration = Duration::from_millis(50);
+/// The time the open connections have to finish on shutdown before they are closed.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+/// Accepts connections until the server must shut down, handing each of them to the next
+/// free worker of a pool. The listener is polled, since a blocking accept could not notice
+/// the shutdown. If every worker is busy and the queue of the pool is full, the connection
+/// is rejected.
+///
+/// On shutdown, the acceptor closes the listener and gives the open connections a grace
+/// period to finish. Then it closes the reading half of the connections still open, so
+/// each client gets the response to the message in progress, if any, and then its handler
+/// sees the end of the connection. The acceptor returns once every worker has finished.
+///
+/// # Arguments:
+///
+/// * `listener` - The TCP listener used to accept incoming connections.
+/// * `registry` - The registry of the server, which records whether it must shut down.
+/// * `logger` - The logger of the server.
+/// * `pool` - The workers that handle the connections.
+/// * `reject` - Answers a connection that is rejected, before it is closed.
+pub fn serve(
+    listener: TcpListener,
+    registry: &Registry,
+    logger: &Logger,
+    mut pool: WorkerPool,
+    reject: fn(TcpStream),
+) {
+    if listener.set_nonblocking(true).is_err() {
+        return logger.report(CalculatorErrors::SocketFailure);
+    }
+
+    while !registry.is_shutting_down() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if let Err(stream) = pool.submit(stream) {
+                    logger.log(LogEvent::ServerFailure(CalculatorErrors::ServerBusy));
+                    reject(stream);
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL),
+            Err(_) => {
+                logger.report(CalculatorErrors::ListeningFailure);
+                thread::sleep(ACCEPT_POLL);
+            }
+        }
+    }
+
+    // The listener is dropped at the end of the block, so new connections are refused.
+    {
+        let _listener = listener;
+    }
+    pool.drain(SHUTDOWN_GRACE, logger);
+}
@@ -0,0 +1,20 @@
+use crate::response::Response;
+
+/// What the handler of a connection must do after a message of the text protocol is
+/// processed by the session of the client.
+///
+/// Most messages are answered with a response, but some of them change how the connection
+/// is handled, which only its handler can do.
+#[derive(Debug)]
+pub enum Action {
+    /// Sends the response to the client.
+    Respond(Response),
+    /// Adds the connection to the replicas of the server, which answer the handshake
+    /// themselves.
+    Replicate,
+    /// Reads the given number of lines from the client and applies them as a batch, with
+    /// `Session::handle_batch`.
+    ReadBatch(usize),
+    /// Sends the answer to a message of another node of the Raft cluster, as is.
+    Cluster(String),
+}
@@ -0,0 +1,192 @@
+use crate::{
+    calculator::Calculator, errors::CalculatorErrors, operation::Operation, overflow::Overflow,
+    width::Width,
+};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A calculator whose value can be changed by several threads at once without a lock.
+///
+/// The value is kept in an `AtomicU64`, which holds any width, as its two's complement
+/// bits. Every change computes the new value from the one it read, with the same rules as
+/// `Calculator`, and stores it with a compare-and-swap, which fails if another thread
+/// changed the value in between; in that case the change is computed again from the new
+/// value. A failed operation leaves the value unchanged, and no thread ever blocks.
+#[derive(Debug)]
+pub struct AtomicCalculator {
+    bits: AtomicU64,
+    calculator: Calculator,
+}
+
+impl AtomicCalculator {
+    /// Creates a calculator with value zero.
+    ///
+    /// # Arguments:
+    ///
+    /// * `width` - The numeric width of the calculator's value.
+    /// * `overflow` - The policy applied when a result does not fit in the width.
+    pub fn new(width: Width, overflow: Overflow) -> Self {
+        Self {
+            bits: AtomicU64::new(0),
+            calculator: Calculator::new(width, overflow),
+        }
+    }
+
+    /// Returns the current value of the calculator.
+    pub fn value(&self) -> i128 {
+        self.decode(self.bits.load(Ordering::Acquire))
+    }
+
+    /// Applies an operation to the calculator, with the same semantics as
+    /// `Calculator::apply`.
+    ///
+    /// # Arguments:
+    ///
+    /// * `op` - The operation to apply.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::DivisionByZero` if a division by zero is attempted, or
+    /// `CalculatorErrors::Overflow` if the result does not fit and the overflow policy is checked.
+    ///
+    /// # Returns:
+    ///
+    /// The value if the operation is `Get`, or `None` otherwise.
+    pub fn apply(&self, op: &Operation) -> Result<Option<i128>, CalculatorErrors> {
+        if *op == Operation::Get {
+            return Ok(Some(self.value()));
+        }
+
+        self.update(|value| Ok((self.compute(value, op)?, ())))?;
+        Ok(None)
+    }
+
+    /// Applies a sequence of operations atomically, with the same semantics as
+    /// `Calculator::apply_all`: either all of them are applied, or the value is left
+    /// unchanged.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::TransactionAborted` with the (1-based) index of the
+    /// first operation that failed and its error.
+    pub fn apply_all(&self, ops: &[Operation]) -> Result<(), CalculatorErrors> {
+        self.update(|mut value| {
+            for (index, op) in ops.iter().enumerate() {
+                value = match self.compute(value, op) {
+                    Ok(value) => value,
+                    Err(e) => {
+                        return Err(CalculatorErrors::TransactionAborted(index + 1, Box::new(e)));
+                    }
+                };
+            }
+            Ok((value, ()))
+        })?;
+        Ok(())
+    }
+
+    /// Computes the result of applying an operation to the given value, without modifying
+    /// the calculator.
+    ///
+    /// # Errors:
+    ///
+    /// Returns the same errors as `apply`.
+    pub fn compute(&self, value: i128, op: &Operation) -> Result<i128, CalculatorErrors> {
+        self.calculator.with_value(value).compute(op)
+    }
+
+    /// Changes the value with a compare-and-swap loop: computes the new value from the
+    /// current one, and stores it unless another thread changed the value in between, in
+    /// which case the new value is computed again.
+    ///
+    /// # Arguments:
+    ///
+    /// * `change` - Computes the new value from the current one, along with any result
+    ///   the caller needs. It may be called several times.
+    ///
+    /// # Errors:
+    ///
+    /// Returns the error of `change`, in which case the value is left unchanged.
+    ///
+    /// # Returns:
+    ///
+    /// The new value, and the result of `change` that computed it.
+    pub fn update<T, F>(&self, mut change: F) -> Result<(i128, T), CalculatorErrors>
+    where
+        F: FnMut(i128) -> Result<(i128, T), CalculatorErrors>,
+    {
+        let mut bits = self.bits.load(Ordering::Acquire);
+        loop {
+            let (value, result) = change(self.decode(bits))?;
+            match self.bits.compare_exchange_weak(
+                bits,
+                value as u64,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Ok((value, result)),
+                Err(current) => bits = current,
+            }
+        }
+    }
+
+    /// Returns the value held by the given bits, sign extending them if the width is signed.
+    fn decode(&self, bits: u64) -> i128 {
+        if self.calculator.width().is_signed() {
+            bits as i64 as i128
+        } else {
+            bits as i128
+        }
+    }
+}
+
+#[test]
+fn test_atomic_apply() {
+    let calculator = AtomicCalculator::new(Width::default(), Overflow::default());
+    calculator.apply(&Operation::Add(250)).unwrap();
+    calculator.apply(&Operation::Add(10)).unwrap();
+    assert_eq!(calculator.apply(&Operation::Get).unwrap(), Some(4));
+}
+
+#[test]
+fn test_atomic_signed_value() {
+    let calculator = AtomicCalculator::new("i64".parse().unwrap(), Overflow::default());
+    calculator.apply(&Operation::Sub(5)).unwrap();
+    assert_eq!(calculator.value(), -5);
+    calculator.apply(&Operation::Set(i64::MIN as i128)).unwrap();
+    assert_eq!(calculator.value(), i64::MIN as i128);
+}
+
+#[test]
+fn test_atomic_apply_all_is_atomic() {
+    let calculator = AtomicCalculator::new(Width::default(), Overflow::default());
+    calculator.apply(&Operation::Add(3)).unwrap();
+    match calculator.apply_all(&[Operation::Add(1), Operation::Div(0)]) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::TransactionAborted(2, _) => (),
+            _ => panic!("Should throw TransactionAborted error, got: {:?}", e),
+        },
+    };
+    assert_eq!(calculator.value(), 3);
+}
+
+#[test]
+fn test_atomic_concurrent_adds() {
+    let calculator = std::sync::Arc::new(AtomicCalculator::new(
+        "u32".parse().unwrap(),
+        Overflow::default(),
+    ));
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let calculator = std::sync::Arc::clone(&calculator);
+            std::thread::spawn(move || {
+                for _ in 0..1000 {
+                    calculator.apply(&Operation::Add(1)).unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(calculator.value(), 8000);
+}
@@ -0,0 +1,52 @@
+use crate::errors::CalculatorErrors;
+use std::{fmt, str::FromStr};
+
+/// The way the values of the registers are shared between clients.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Backend {
+    /// Each register is behind a mutex, which every change locks.
+    #[default]
+    Mutex,
+    /// Each value is an atomic integer, changed with compare-and-swap loops.
+    Atomic,
+}
+
+impl FromStr for Backend {
+    type Err = CalculatorErrors;
+
+    /// Parses a backend: `mutex` or `atomic`.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::InvalidArgument` if the backend is unknown.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mutex" => Ok(Self::Mutex),
+            "atomic" => Ok(Self::Atomic),
+            _ => Err(CalculatorErrors::InvalidArgument(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    /// Formats the backend as it is given on the command line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mutex => write!(f, "mutex"),
+            Self::Atomic => write!(f, "atomic"),
+        }
+    }
+}
+
+#[test]
+fn test_parse_backend() {
+    assert_eq!("atomic".parse::<Backend>().unwrap(), Backend::Atomic);
+    assert_eq!(Backend::default().to_string(), "mutex");
+    match "rwlock".parse::<Backend>() {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidArgument(_) => (),
+            _ => panic!("Should throw InvalidArgument error, got: {:?}", e),
+        },
+    };
+}
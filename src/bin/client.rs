@@ -1,27 +1,56 @@
-use tpi_calculadora_distribuida::{errors::CalculatorErrors, response::Response};
+use tpi_calculadora_distribuida::{
+    connection::{Connection, DEFAULT_PIPELINE_WINDOW},
+    errors::CalculatorErrors,
+    response::Response,
+};
 
+use std::collections::hash_map::RandomState;
 use std::env;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
-use std::net::TcpStream;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader};
 
 /// The entry point for the client application.
 ///
 /// The client reads operations from a file and sends them to the server.
 /// At the end, it retrieves the final value of the calculator.
+///
+/// The server may be given as a comma-separated list of addresses, in which case the
+/// client fails over to the next one if the connection drops. Every operation carries
+/// an id within a client session, so an operation sent again to the same server is not
+/// applied twice. Sessions are not shared between servers, so an operation sent again to
+/// another server after a failover may be applied twice.
+///
+/// With `--pipeline [window]`, the client sends up to `window` operations (64 by default)
+/// before waiting for their responses, and prefixes each error with the line of the file that produced it.
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() != 3 {
-        return Response::Error(CalculatorErrors::ArgsLenFailure).eprint();
-    }
+    let window = match args[..] {
+        [_, _, _] => None,
+        [_, _, _, ref option] if option == "--pipeline" => Some(DEFAULT_PIPELINE_WINDOW),
+        [_, _, _, ref option, ref window] if option == "--pipeline" => {
+            match window.parse::<usize>() {
+                Ok(window) if window > 0 => Some(window),
+                _ => {
+                    let e = CalculatorErrors::InvalidArgument(window.to_owned());
+                    return Response::Error(e).eprint();
+                }
+            }
+        }
+        _ => return Response::Error(CalculatorErrors::ArgsLenFailure).eprint(),
+    };
 
-    let stream = match TcpStream::connect(&args[1]) {
-        Ok(stream) => stream,
-        Err(_) => return Response::Error(CalculatorErrors::SocketFailure).eprint(),
+    let mut connection = match Connection::open(&args[1]) {
+        Ok(connection) => connection,
+        Err(e) => return Response::Error(e).eprint(),
     };
+    connection.resume(&session_token());
 
-    read_file(&args[2], stream);
+    match window {
+        Some(window) => pipeline_file(&args[2], connection, window),
+        None => read_file(&args[2], connection),
+    }
 }
 
 /// Reads operations from a file and sends them to the server.
@@ -29,8 +58,8 @@ fn main() {
 /// # Arguments:
 ///
 /// * `path` - The path to the file containing the operations.
-/// * `stream` - The TCP stream used to communicate with the server.
-fn read_file(path: &str, mut stream: TcpStream) {
+/// * `connection` - The connection used to communicate with the server.
+fn read_file(path: &str, mut connection: Connection) {
     let file = match File::open(path) {
         Ok(file) => file,
         Err(_) => return Response::Error(CalculatorErrors::FileOpenFailure).eprint(),
@@ -38,7 +67,7 @@ fn read_file(path: &str, mut stream: TcpStream) {
 
     let reader_file = BufReader::new(file);
 
-    for _line in reader_file.lines() {
+    for (id, _line) in reader_file.lines().enumerate() {
         let line = match _line {
             Ok(line) => line,
             Err(_) => {
@@ -47,52 +76,91 @@ fn read_file(path: &str, mut stream: TcpStream) {
             }
         };
 
-        send_request(&mut stream, "OP", line);
-        read_response(&stream);
+        if !send_request(&mut connection, &format!("OP {} ID {}", line, id)) {
+            return;
+        }
     }
 
-    send_request(&mut stream, "GET", "".to_owned());
-    read_response(&stream);
+    send_request(&mut connection, "GET");
 }
 
-/// Sends a request to the server.
+/// Reads operations from a file and sends them to the server without waiting for the
+/// response to each one before sending the next.
 ///
 /// # Arguments:
 ///
-/// * `stream` - A mutable reference to the TCP stream used to communicate with the server.
-/// * `data` - The data to send to the server.
-fn send_request(stream: &mut TcpStream, message_op: &str, data: String) {
-    if stream
-        .write_all(format!("{} {}\n", message_op, data).as_bytes())
-        .is_err()
-    {
-        Response::Error(CalculatorErrors::WritingFailure).eprint();
+/// * `path` - The path to the file containing the operations.
+/// * `connection` - The connection used to communicate with the server.
+/// * `window` - The maximum number of operations sent without response.
+fn pipeline_file(path: &str, mut connection: Connection, window: usize) {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Response::Error(CalculatorErrors::FileOpenFailure).eprint(),
     };
 
-    if stream.flush().is_err() {
-        Response::Error(CalculatorErrors::WritingFailure).eprint();
-    };
+    let requests = BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter_map(|(id, line)| match line {
+            Ok(line) => Some((id, format!("OP {} ID {}", line, id))),
+            Err(_) => {
+                Response::Error(CalculatorErrors::ReadLineFailure).eprint();
+                None
+            }
+        });
+    let result = connection.pipeline(requests, window, |id, response| {
+        if response.starts_with("ERROR") {
+            eprintln!("line {}: {}", id + 1, response);
+        }
+    });
+
+    match result {
+        Ok(()) => {
+            send_request(&mut connection, "GET");
+        }
+        Err(e) => Response::Error(e).eprint(),
+    }
 }
 
-/// Reads a response from the server.
+/// Sends a request to the server, and prints its response.
 ///
 /// # Arguments:
 ///
-/// * `stream` - A mutable reference to the TCP stream used to communicate with the server.
-fn read_response(stream: &TcpStream) {
-    let mut reader = BufReader::new(stream);
-
-    let mut response = String::new();
-
-    if reader.read_line(&mut response).is_err() {
-        return Response::Error(CalculatorErrors::ListeningFailure).eprint();
-    };
+/// * `connection` - The connection used to communicate with the server.
+/// * `request` - The request to send to the server.
+///
+/// # Returns:
+///
+/// Whether the server answered the request.
+fn send_request(connection: &mut Connection, request: &str) -> bool {
+    match connection.request(request) {
+        Ok(response) => {
+            print_response(&response);
+            true
+        }
+        Err(e) => {
+            Response::Error(e).eprint();
+            false
+        }
+    }
+}
 
-    if response.starts_with("VALUE") {
-        let ans: Vec<&str> = response.split(" ").collect();
-        print!("{}", ans[1]);
+/// Prints a response of the server: the value to stdout, and errors to stderr.
+///
+/// # Arguments:
+///
+/// * `response` - The response received from the server.
+fn print_response(response: &str) {
+    if let Some(value) = response.strip_prefix("VALUE ") {
+        println!("{}", value);
     }
     if response.starts_with("ERROR") {
-        eprint!("{}", response);
+        eprintln!("{}", response);
     }
 }
+
+/// Returns a random token for the client session.
+fn session_token() -> String {
+    let random = RandomState::new().build_hasher().finish();
+    format!("{:x}{:x}", std::process::id(), random)
+}
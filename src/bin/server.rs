@@ -1,14 +1,19 @@
 use tpi_calculadora_distribuida::{
-    calculator::Calculator, errors::CalculatorErrors, operation::Operation, response::Response,
+    acceptor, action::Action, binary, errors::CalculatorErrors, follower::Follower, http_server,
+    json, log_event::LogEvent, logger::Logger, raft::Raft, registry::Registry, response::Response,
+    server_config::ServerConfig, server_mode::ServerMode, session::Session, storage::Storage,
+    worker_pool::WorkerPool,
 };
+#[cfg(unix)]
+use tpi_calculadora_distribuida::{event_loop, signal};
 
 use std::{
     env,
-    io::{BufRead, BufReader},
+    io::{self, BufRead, BufReader, Read, Write},
     net::{TcpListener, TcpStream},
-    str::FromStr,
-    sync::{Arc, Mutex},
-    thread,
+    path::Path,
+    sync::Arc,
+    thread::{self, JoinHandle},
 };
 
 /// The entry point for the server application.
@@ -17,20 +22,35 @@ use std::{
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() != 2 {
-        return Response::Error(CalculatorErrors::ArgsLenFailure).eprint();
-    }
+    let config = match ServerConfig::from_args(&args) {
+        Ok(config) => config,
+        Err(e) => return Response::Error(e).eprint(),
+    };
 
-    server(&args[1]);
+    server(config);
 }
 
-/// Starts the server and binds it to the specified address.
+/// Starts the server and binds it to the configured address. The server shuts down in
+/// order when a client sends `SHUTDOWN` with the admin token, or when it receives `SIGTERM` or `SIGINT`.
 ///
 /// # Arguments:
 ///
-/// * `addr` - The address to bind the server to.
-fn server(addr: &str) {
-    let listener = match TcpListener::bind(addr) {
+/// * `config` - The configuration of the server.
+fn server(config: ServerConfig) {
+    let (logger, log_handle) = match logger(&config) {
+        Ok(logger) => logger,
+        Err(e) => return Response::Error(e).eprint(),
+    };
+    let registry = match registry(&config, &logger) {
+        Ok(registry) => registry,
+        Err(e) => {
+            logger.report(e);
+            return stop_logger(logger, log_handle);
+        }
+    };
+    install_signals(&logger);
+
+    let listener = match TcpListener::bind(&config.addr) {
         Ok(listener) => listener,
         Err(_) => {
             Response::Error(CalculatorErrors::SocketFailure).eprint();
@@ -38,52 +58,254 @@ fn server(addr: &str) {
         }
     };
 
-    server_listening(listener);
+    let http = match config.http.as_ref().map(TcpListener::bind).transpose() {
+        Ok(http) => http,
+        Err(_) => return Response::Error(CalculatorErrors::SocketFailure).eprint(),
+    };
+
+    let registry = Arc::new(registry);
+    Raft::spawn(&registry);
+    let http = http.map(|http| {
+        let (registry, logger) = (Arc::clone(&registry), logger.clone());
+        let (workers, queue) = (config.workers, config.accept_queue);
+        thread::spawn(move || http_server::serve(http, registry, logger, workers, queue))
+    });
+    if !config.replica_of.is_empty() {
+        let follower = Follower::new(Arc::clone(&registry), config.replica_of, logger.clone());
+        thread::spawn(move || follower.run());
+    }
+
+    match config.mode {
+        ServerMode::Threads => {
+            let pool = client_pool(config.workers, config.accept_queue, &registry, &logger);
+            server_listening(listener, &registry, &logger, pool);
+        }
+        ServerMode::EventLoop => serve_events(&listener, &registry, &logger),
+    }
+    if let Some(http) = http
+        && http.join().is_err()
+    {
+        logger.report(CalculatorErrors::JoinFailure);
+    }
+    if let Err(e) = registry.snapshot() {
+        logger.report(e);
+    }
+    stop_logger(logger, log_handle);
 }
 
-/// Listens for incoming client connections and spawns a new thread for each connection.
+/// Serves every client connection from the calling thread, with an event loop.
 ///
 /// # Arguments:
 ///
 /// * `listener` - The TCP listener used to accept incoming connections.
-fn server_listening(listener: TcpListener) {
-    let counter = Arc::new(Mutex::new(Calculator::default()));
-    let mut handles = vec![];
-
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                let counter = Arc::clone(&counter);
-                let handle = thread::spawn(move || {
-                    handle_client(stream, counter);
-                });
-                handles.push(handle);
-            }
-            Err(_) => Response::Error(CalculatorErrors::ListeningFailure).eprint(),
+/// * `registry` - The registry of named calculators shared by every client.
+/// * `logger` - The logger of the server.
+#[cfg(unix)]
+fn serve_events(listener: &TcpListener, registry: &Arc<Registry>, logger: &Logger) {
+    event_loop::serve(listener, registry, logger);
+}
+
+/// Reports that the event loop is only available on unix targets, which the configuration
+/// already checks.
+#[cfg(not(unix))]
+fn serve_events(_listener: &TcpListener, _registry: &Arc<Registry>, logger: &Logger) {
+    logger.report(CalculatorErrors::InvalidArgument("--mode".to_owned()));
+}
+
+/// Makes `SIGTERM` and `SIGINT` shut the server down in order.
+///
+/// # Arguments:
+///
+/// * `logger` - The logger of the server, which reports a handler that cannot be installed.
+#[cfg(unix)]
+fn install_signals(logger: &Logger) {
+    if let Err(e) = signal::install() {
+        logger.report(e);
+    }
+}
+
+/// Does nothing, since the termination signals are only handled on unix targets.
+#[cfg(not(unix))]
+fn install_signals(_logger: &Logger) {}
+
+/// Waits for the logger thread to write every event, once the last handle to it is
+/// dropped.
+///
+/// # Arguments:
+///
+/// * `logger` - The last handle to the logger thread.
+/// * `handle` - The logger thread, if there is one.
+fn stop_logger(logger: Logger, handle: Option<JoinHandle<()>>) {
+    // The logger is dropped at the end of the block, which closes the channel.
+    {
+        let _logger = logger;
+    }
+
+    if let Some(handle) = handle
+        && handle.join().is_err()
+    {
+        Response::Error(CalculatorErrors::JoinFailure).eprint();
+    }
+}
+
+/// Spawns the logger thread, if a log file is configured.
+///
+/// # Arguments:
+///
+/// * `config` - The configuration of the server.
+///
+/// # Errors:
+///
+/// Returns `CalculatorErrors::FileOpenFailure` if the log file cannot be opened.
+fn logger(config: &ServerConfig) -> Result<(Logger, Option<JoinHandle<()>>), CalculatorErrors> {
+    match &config.log_file {
+        Some(path) => {
+            let (logger, handle) = Logger::spawn(path, config.log_level, config.log_max_size)?;
+            Ok((logger, Some(handle)))
         }
+        None => Ok((Logger::default(), None)),
     }
+}
 
-    for handle in handles {
-        match handle.join() {
-            Ok(_) => (),
-            Err(_) => {
-                Response::Error(CalculatorErrors::JoinFailure).eprint();
-                return;
-            }
+/// Creates the registry of the server, restoring its state from the data directory if
+/// one is configured, or joining the Raft cluster if it belongs to one, in which case the
+/// data directory keeps the state of the node instead.
+///
+/// # Arguments:
+///
+/// * `config` - The configuration of the server.
+/// * `logger` - The logger of the server, which logs the stored changes that fail.
+///
+/// # Errors:
+///
+/// Returns `CalculatorErrors::StorageFailure` if the data directory cannot be used.
+fn registry(config: &ServerConfig, logger: &Logger) -> Result<Registry, CalculatorErrors> {
+    let mut registry = Registry::new(config.width, config.overflow, config.history, config.undo);
+    registry.set_replica(!config.replica_of.is_empty());
+    registry.set_backend(config.backend);
+    registry.set_admin_token(config.admin_token.to_owned());
+    registry.set_max_registers(config.max_registers);
+    match (config.cluster_index(), &config.data_dir) {
+        (Some(me), dir) => registry.attach_cluster(raft(config, me, dir.as_deref())?)?,
+        (None, Some(dir)) => {
+            registry.attach_storage(Storage::open(dir, config.snapshot_every)?, logger)?
+        }
+        (None, None) => (),
+    }
+    Ok(registry)
+}
+
+/// Creates the node of the Raft cluster, restoring its state from the `raft` directory
+/// inside the data directory if one is configured. The node replaces its applied entries
+/// with a snapshot every `--snapshot-every` entries.
+///
+/// # Arguments:
+///
+/// * `config` - The configuration of the server.
+/// * `me` - The index of the server in the addresses of the cluster.
+/// * `dir` - The data directory, if any.
+///
+/// # Errors:
+///
+/// Returns `CalculatorErrors::StorageFailure` if the data directory cannot be used.
+fn raft(config: &ServerConfig, me: usize, dir: Option<&str>) -> Result<Raft, CalculatorErrors> {
+    let mut raft = Raft::new(config.cluster.to_vec(), me, config.snapshot_every);
+    if let Some(dir) = dir {
+        let dir = Path::new(dir).join("raft");
+        let storage = Storage::open(&dir.to_string_lossy(), config.snapshot_every)?;
+        raft.attach_storage(storage)?;
+    }
+    Ok(raft)
+}
+
+/// Spawns the pool of workers that handle the client connections.
+///
+/// # Arguments:
+///
+/// * `workers` - The number of threads that handle connections.
+/// * `queue` - The number of connections that may wait for a free worker.
+/// * `registry` - The registry of named calculators shared by every client.
+/// * `logger` - The logger of the server.
+fn client_pool(
+    workers: usize,
+    queue: usize,
+    registry: &Arc<Registry>,
+    logger: &Logger,
+) -> WorkerPool {
+    let (registry, logger) = (Arc::clone(registry), logger.clone());
+    WorkerPool::spawn(workers, queue, move |stream| {
+        handle_client(stream, registry, logger)
+    })
+}
+
+/// Listens for incoming client connections and hands each of them to a worker of the
+/// pool, until a client asks the server to shut down and every connection is closed. If
+/// every worker is busy and the queue is full, the client is answered with
+/// `ERROR "server busy"` and disconnected.
+///
+/// # Arguments:
+///
+/// * `listener` - The TCP listener used to accept incoming connections.
+/// * `registry` - The registry of named calculators shared by every client.
+/// * `logger` - The logger of the server.
+/// * `pool` - The workers that handle the client connections.
+fn server_listening(
+    listener: TcpListener,
+    registry: &Arc<Registry>,
+    logger: &Logger,
+    pool: WorkerPool,
+) {
+    acceptor::serve(listener, registry, logger, pool, |stream| {
+        Response::Error(CalculatorErrors::ServerBusy).send_response(&stream)
+    });
+}
+
+/// Handles a single client connection, with the binary protocol if the first byte the
+/// client sends is `binary::MAGIC`, or with the text protocol otherwise.
+///
+/// # Arguments:
+///
+/// * `stream` - The TCP stream used to communicate with the client.
+/// * `registry` - A shared reference to the registry of named calculators.
+/// * `logger` - The logger of the server.
+fn handle_client(stream: TcpStream, registry: Arc<Registry>, logger: Logger) {
+    let session = match Session::new(Arc::clone(&registry)) {
+        Ok(session) => session,
+        Err(e) => return Response::Error(e).send_response(&stream),
+    };
+    let client = session.client();
+    let peer = stream
+        .peer_addr()
+        .map_or("unknown".to_owned(), |addr| addr.to_string());
+    logger.log(LogEvent::ConnectionOpened { client, peer });
+
+    let mut first = [0; 1];
+    match stream.peek(&mut first) {
+        Ok(1) if first[0] == binary::MAGIC => {
+            handle_binary_client(stream, session, &registry, &logger)
         }
+        _ => handle_text_client(stream, session, &registry, &logger),
     }
+    logger.log(LogEvent::ConnectionClosed { client });
 }
 
-/// Handles a single client connection.
+/// Handles a client connection that uses the text protocol.
 ///
 /// # Arguments:
 ///
 /// * `stream` - The TCP stream used to communicate with the client.
-/// * `counter` - A shared reference to the calculator instance.
-fn handle_client(stream: TcpStream, counter: Arc<Mutex<Calculator>>) {
-    let reader = BufReader::new(&stream);
+/// * `session` - The session of the client.
+/// * `registry` - A shared reference to the registry of named calculators.
+/// * `logger` - The logger of the server.
+fn handle_text_client(
+    stream: TcpStream,
+    mut session: Session,
+    registry: &Registry,
+    logger: &Logger,
+) {
+    let mut lines = BufReader::new(&stream).lines();
 
-    for line in reader.lines() {
+    while let Some(line) = lines.next() {
         let line = match line {
             Ok(line) => line,
             Err(_) => {
@@ -92,36 +314,125 @@ fn handle_client(stream: TcpStream, counter: Arc<Mutex<Calculator>>) {
             }
         };
 
-        match handle_op(line, &counter) {
-            Ok(ans) => match ans {
-                Some(value_ans) => Response::Value(value_ans).send_response(&stream),
-                None => Response::Ok.send_response(&stream),
+        if line.starts_with('{') {
+            let response = handle_json_line(&mut session, registry, &line);
+            logger.log_response(session.client(), &line, &response);
+            send_json(&stream, response);
+            continue;
+        }
+
+        let action = session.handle_line(&line);
+        logger.log_action(session.client(), &line, &action);
+        match action {
+            Action::Respond(response) => response.send_response(&stream),
+            Action::Replicate => match registry.add_replica(&stream, logger) {
+                Ok(()) => (),
+                Err(e) => Response::Error(e).send_response(&stream),
             },
-            Err(e) => Response::Error(e).send_response(&stream),
-        };
+            Action::ReadBatch(count) => match read_batch(&mut lines, count) {
+                Ok(batch) => session.handle_batch(&batch).send_response(&stream),
+                Err(e) => Response::Error(e).send_response(&stream),
+            },
+            Action::Cluster(answer) => send_answer(&stream, &answer),
+        }
     }
 }
 
-/// Processes an operation received from the client.
+/// Processes a line of the JSON protocol, which carries an operation.
 ///
 /// # Arguments:
 ///
-/// * `line` - The operation received from the client as a string.
-/// * `counter` - A shared reference to the calculator instance.
+/// * `session` - The session of the client.
+/// * `registry` - A shared reference to the registry of named calculators.
+/// * `line` - The line received from the client, e.g. `{"op": "+", "operand": 3}`.
 ///
 /// # Returns:
 ///
-/// A `Response` indicating the result of the operation.
-fn handle_op(
-    line: String,
-    counter: &Arc<Mutex<Calculator>>,
-) -> Result<Option<u8>, CalculatorErrors> {
-    let op = Operation::from_str(&line)?;
+/// The `Response` that must be sent back to the client.
+fn handle_json_line(session: &mut Session, registry: &Registry, line: &str) -> Response {
+    match json::decode_operation(line, registry.width()) {
+        Ok(op) => session.handle_operation(op),
+        Err(e) => Response::Error(e),
+    }
+}
 
-    let mut calculator = match counter.lock() {
-        Ok(calculator) => calculator,
-        Err(_) => return Err(CalculatorErrors::LockFailure),
-    };
+/// Sends a response to the client as a line of the JSON protocol.
+///
+/// # Arguments:
+///
+/// * `stream` - The TCP stream used to communicate with the client.
+/// * `response` - The response to send.
+fn send_json(mut stream: &TcpStream, response: Response) {
+    let line = format!("{}\n", json::encode_response(&response));
+    if stream.write_all(line.as_bytes()).is_err() || stream.flush().is_err() {
+        Response::Error(CalculatorErrors::WritingFailure).eprint();
+    }
+}
+
+/// Sends the answer to a message of another node of the Raft cluster, as a line.
+///
+/// # Arguments:
+///
+/// * `stream` - The TCP stream used to communicate with the node.
+/// * `answer` - The answer to send.
+fn send_answer(mut stream: &TcpStream, answer: &str) {
+    let line = format!("{}\n", answer);
+    if stream.write_all(line.as_bytes()).is_err() || stream.flush().is_err() {
+        Response::Error(CalculatorErrors::WritingFailure).eprint();
+    }
+}
+
+/// Handles a client connection that uses the binary protocol: after `binary::MAGIC`, every
+/// frame carries an operation, answered with a frame that carries the response.
+///
+/// # Arguments:
+///
+/// * `stream` - The TCP stream used to communicate with the client.
+/// * `session` - The session of the client.
+/// * `registry` - A shared reference to the registry of named calculators.
+/// * `logger` - The logger of the server.
+fn handle_binary_client(
+    stream: TcpStream,
+    mut session: Session,
+    registry: &Registry,
+    logger: &Logger,
+) {
+    let mut reader = BufReader::new(&stream);
+    if reader.read_exact(&mut [0; 1]).is_err() {
+        return;
+    }
+
+    while let Ok(Some(frame)) = binary::read_frame(&mut reader) {
+        let (message, response) = match binary::decode_operation(&frame, registry.width()) {
+            Ok(op) => (op.to_string(), session.handle_operation(op)),
+            Err(e) => ("binary frame".to_owned(), Response::Error(e)),
+        };
+        logger.log_response(session.client(), &message, &response);
+
+        if binary::write_frame(&stream, &binary::encode_response(&response)).is_err() {
+            return logger.report(CalculatorErrors::WritingFailure);
+        }
+    }
+}
 
-    calculator.apply(op)
+/// Reads the lines of a batch sent by a client.
+///
+/// # Arguments:
+///
+/// * `lines` - The lines received from the client.
+/// * `count` - The number of lines in the batch.
+///
+/// # Errors:
+///
+/// Returns `CalculatorErrors::ReadLineFailure` if the connection ends, or a line cannot be
+/// read, before the batch is complete.
+fn read_batch<I>(lines: &mut I, count: usize) -> Result<Vec<String>, CalculatorErrors>
+where
+    I: Iterator<Item = io::Result<String>>,
+{
+    let batch: Vec<String> = lines.take(count).map_while(Result::ok).collect();
+    if batch.len() != count {
+        return Err(CalculatorErrors::ReadLineFailure);
+    }
+    Ok(batch)
 }
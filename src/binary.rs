@@ -0,0 +1,340 @@
+use crate::{errors::CalculatorErrors, operation::Operation, response::Response, width::Width};
+use std::io::{self, Read, Write};
+
+/// The first byte sent by a client of the binary protocol. A client of the text protocol
+/// never sends it, so the server can tell them apart on the same port.
+pub const MAGIC: u8 = 0;
+/// The size of a record: a code followed by a 128 bits big endian integer.
+pub const RECORD_SIZE: usize = 17;
+
+/// The code of `GET`.
+const GET: u8 = 0;
+/// The operators of the binary operations, whose codes start at 1.
+const OPERATORS: [&str; 12] = [
+    "+", "-", "*", "/", "%", "^", "&", "|", "xor", "<<", ">>", "=",
+];
+/// The unary operators, whose codes follow the binary ones.
+const UNARY_OPERATORS: [&str; 3] = ["neg", "abs", "not"];
+/// The code of `RESET`, which follows the unary operators.
+const RESET: u8 = (OPERATORS.len() + UNARY_OPERATORS.len() + 1) as u8;
+
+/// The code of a response with no value.
+const OK: u8 = 0;
+/// The code of a response with the value of the calculator.
+const VALUE: u8 = 1;
+/// The code of an error, followed by the code of its kind instead of a value.
+const ERROR: u8 = 2;
+/// The kinds of errors, as returned by `CalculatorErrors::code`, whose codes start at 1.
+const ERRORS: [&str; 31] = [
+    "division_by_zero",
+    "overflow",
+    "invalid_operation",
+    "invalid_integer",
+    "invalid_expression",
+    "unexpected_message",
+    "compare_failure",
+    "nothing_to_undo",
+    "nothing_to_redo",
+    "no_transaction",
+    "nested_transaction",
+    "read_only_replica",
+    "not_leader",
+    "stale_epoch",
+    "unauthorized",
+    "transaction_aborted",
+    "invalid_frame",
+    "invalid_json",
+    "too_many_registers",
+    "join_failure",
+    "lock_failure",
+    "writing_failure",
+    "listening_failure",
+    "socket_failure",
+    "server_busy",
+    "file_open_failure",
+    "read_line_failure",
+    "storage_failure",
+    "signal_failure",
+    "args_len_failure",
+    "invalid_argument",
+];
+
+/// Reads a frame: a 16 bits big endian length followed by that many bytes.
+///
+/// # Returns:
+///
+/// The bytes of the frame, or `None` if the connection ended before a new frame.
+///
+/// # Errors:
+///
+/// Returns the error produced while reading, if any.
+pub fn read_frame<R: Read>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut length = [0; 2];
+    match reader.read_exact(&mut length) {
+        Ok(()) => (),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let mut frame = vec![0; u16::from_be_bytes(length) as usize];
+    reader.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+/// Removes the first frame from the bytes received so far, if it is complete.
+///
+/// # Arguments:
+///
+/// * `buffer` - The bytes received and not yet processed.
+///
+/// # Returns:
+///
+/// The bytes of the frame, or `None` if the buffer does not hold a complete frame yet.
+pub fn take_frame(buffer: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let length = match buffer[..] {
+        [high, low, ..] => u16::from_be_bytes([high, low]) as usize,
+        _ => return None,
+    };
+    if buffer.len() < 2 + length {
+        return None;
+    }
+    let frame = buffer[2..2 + length].to_vec();
+    buffer.drain(..2 + length);
+    Some(frame)
+}
+
+/// Writes a frame: the length of the bytes as a 16 bits big endian integer, followed by them.
+/// Bytes beyond the maximum length of a frame are not written.
+///
+/// # Errors:
+///
+/// Returns the error produced while writing, if any.
+pub fn write_frame<W: Write>(mut writer: W, bytes: &[u8]) -> io::Result<()> {
+    let bytes = &bytes[..bytes.len().min(u16::MAX as usize)];
+    let mut frame = (bytes.len() as u16).to_be_bytes().to_vec();
+    frame.extend_from_slice(bytes);
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+/// Encodes an operation as a record.
+///
+/// # Errors:
+///
+/// Returns `CalculatorErrors::InvalidOperation` if the operation has no fixed size, as
+/// `EVAL`, `CAS` and conditional operations.
+pub fn encode_operation(op: &Operation) -> Result<[u8; RECORD_SIZE], CalculatorErrors> {
+    let (code, operand) = match op {
+        Operation::Get => (GET, 0),
+        Operation::Reset => (RESET, 0),
+        Operation::Neg => (OPERATORS.len() as u8 + 1, 0),
+        Operation::Abs => (OPERATORS.len() as u8 + 2, 0),
+        Operation::Not => (OPERATORS.len() as u8 + 3, 0),
+        op => match op.get_binary_operator() {
+            Some((operator, operand)) => (code_of(operator), operand),
+            None => return Err(CalculatorErrors::InvalidOperation(op.to_string())),
+        },
+    };
+    Ok(record(code, operand))
+}
+
+/// Decodes an operation from a record.
+///
+/// # Arguments:
+///
+/// * `frame` - The bytes of the frame that carries the record.
+/// * `width` - The width the operand must fit in.
+///
+/// # Errors:
+///
+/// Returns `CalculatorErrors::InvalidFrame` if the frame is not a record,
+/// `CalculatorErrors::InvalidOperation` if the code is not valid, or
+/// `CalculatorErrors::InvalidInteger` if the operand does not fit in the width.
+pub fn decode_operation(frame: &[u8], width: Width) -> Result<Operation, CalculatorErrors> {
+    let (code, operand) = split_record(frame)?;
+    let index = (code as usize).checked_sub(1);
+    let binary = index.and_then(|index| OPERATORS.get(index));
+    let unary = index
+        .and_then(|index| index.checked_sub(OPERATORS.len()))
+        .and_then(|index| UNARY_OPERATORS.get(index));
+
+    match (code, binary, unary) {
+        (GET, _, _) => Ok(Operation::Get),
+        (RESET, _, _) => Ok(Operation::Reset),
+        (_, Some(operator), _) if width.contains(operand) => {
+            Operation::get_binary_op(operator, operand)
+        }
+        (_, Some(_), _) => Err(CalculatorErrors::InvalidInteger(operand.to_string())),
+        (_, None, Some(operator)) => Operation::get_unary_op(operator),
+        _ => Err(CalculatorErrors::InvalidOperation(code.to_string())),
+    }
+}
+
+/// Encodes a response as a record. Errors carry the code of their kind, without details.
+pub fn encode_response(response: &Response) -> [u8; RECORD_SIZE] {
+    match response {
+        Response::Value(value) => record(VALUE, *value),
+        Response::Error(e) => {
+            let index = ERRORS.iter().position(|code| *code == e.code());
+            record(ERROR, index.map_or(0, |index| index as i128 + 1))
+        }
+        _ => record(OK, 0),
+    }
+}
+
+/// Decodes a response, returning the message the text protocol answers for it, except
+/// for errors, which are answered as `ERROR "<kind>"`.
+///
+/// # Errors:
+///
+/// Returns `CalculatorErrors::InvalidFrame` if the frame is not a valid response.
+pub fn decode_response(frame: &[u8]) -> Result<String, CalculatorErrors> {
+    let error = |code: i128| {
+        let index = usize::try_from(code - 1).ok()?;
+        ERRORS.get(index)
+    };
+
+    match split_record(frame)? {
+        (OK, _) => Ok("OK".to_owned()),
+        (VALUE, value) => Ok(format!("VALUE {}", value)),
+        (ERROR, code) => match error(code) {
+            Some(kind) => Ok(format!("ERROR \"{}\"", kind)),
+            None => Err(CalculatorErrors::InvalidFrame),
+        },
+        _ => Err(CalculatorErrors::InvalidFrame),
+    }
+}
+
+/// Returns the code of a binary operator.
+fn code_of(operator: &str) -> u8 {
+    let index = OPERATORS
+        .iter()
+        .position(|candidate| *candidate == operator);
+    index.unwrap_or_default() as u8 + 1
+}
+
+/// Builds a record from a code and an integer.
+fn record(code: u8, value: i128) -> [u8; RECORD_SIZE] {
+    let mut record = [0; RECORD_SIZE];
+    record[0] = code;
+    record[1..].copy_from_slice(&value.to_be_bytes());
+    record
+}
+
+/// Splits a record into its code and its integer.
+fn split_record(frame: &[u8]) -> Result<(u8, i128), CalculatorErrors> {
+    match frame.split_first() {
+        Some((code, value)) if frame.len() == RECORD_SIZE => match value.try_into() {
+            Ok(value) => Ok((*code, i128::from_be_bytes(value))),
+            Err(_) => Err(CalculatorErrors::InvalidFrame),
+        },
+        _ => Err(CalculatorErrors::InvalidFrame),
+    }
+}
+
+#[test]
+fn test_operation_round_trip() {
+    let width = Width::default();
+    for op in [
+        Operation::Add(5),
+        Operation::Xor(3),
+        Operation::Set(255),
+        Operation::Neg,
+        Operation::Abs,
+        Operation::Not,
+        Operation::Reset,
+        Operation::Get,
+    ] {
+        let record = encode_operation(&op).unwrap();
+        assert_eq!(decode_operation(&record, width).unwrap(), op);
+    }
+}
+
+#[test]
+fn test_operation_codes() {
+    assert_eq!(encode_operation(&Operation::Add(1)).unwrap()[0], 1);
+    assert_eq!(encode_operation(&Operation::Set(1)).unwrap()[0], 12);
+    assert_eq!(encode_operation(&Operation::Neg).unwrap()[0], 13);
+    assert_eq!(encode_operation(&Operation::Not).unwrap()[0], 15);
+    assert_eq!(encode_operation(&Operation::Reset).unwrap()[0], 16);
+}
+
+#[test]
+fn test_take_frame() {
+    let mut buffer = vec![0, 2, 7, 8, 0, 3, 9];
+    assert_eq!(take_frame(&mut buffer), Some(vec![7, 8]));
+    assert_eq!(take_frame(&mut buffer), None);
+    assert_eq!(buffer, vec![0, 3, 9]);
+}
+
+#[test]
+fn test_decode_operation_errors() {
+    match decode_operation(&[1, 2, 3], Width::default()) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidFrame => (),
+            _ => panic!("Should throw InvalidFrame error, got: {:?}", e),
+        },
+    };
+    let too_large = encode_operation(&Operation::Add(300)).unwrap();
+    match decode_operation(&too_large, Width::default()) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidInteger(_) => (),
+            _ => panic!("Should throw InvalidInteger error, got: {:?}", e),
+        },
+    };
+    match decode_operation(&record(99, 0), Width::default()) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidOperation(_) => (),
+            _ => panic!("Should throw InvalidOperation error, got: {:?}", e),
+        },
+    };
+}
+
+#[test]
+fn test_response_round_trip() {
+    let value = encode_response(&Response::Value(-7));
+    assert_eq!(value.len(), RECORD_SIZE);
+    assert_eq!(decode_response(&value).unwrap(), "VALUE -7");
+    assert_eq!(
+        decode_response(&encode_response(&Response::Ok)).unwrap(),
+        "OK"
+    );
+    let error = encode_response(&Response::Error(CalculatorErrors::NotLeader(Some(
+        "127.0.0.1:8080".to_owned(),
+    ))));
+    assert_eq!(error.len(), RECORD_SIZE);
+    assert_eq!(decode_response(&error).unwrap(), "ERROR \"not_leader\"");
+}
+
+#[test]
+fn test_every_error_has_a_code() {
+    for e in [
+        CalculatorErrors::DivisionByZero,
+        CalculatorErrors::InvalidArgument("x".to_owned()),
+        CalculatorErrors::StaleEpoch(3),
+    ] {
+        let (_, code) = split_record(&encode_response(&Response::Error(e))).unwrap();
+        assert!(code > 0);
+    }
+    match decode_response(&record(ERROR, ERRORS.len() as i128 + 1)) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidFrame => (),
+            _ => panic!("Should throw InvalidFrame error, got: {:?}", e),
+        },
+    };
+}
+
+#[test]
+fn test_frame_round_trip() {
+    let mut bytes = vec![];
+    write_frame(&mut bytes, &[1, 2, 3]).unwrap();
+    assert_eq!(bytes, vec![0, 3, 1, 2, 3]);
+
+    let mut reader = &bytes[..];
+    assert_eq!(read_frame(&mut reader).unwrap(), Some(vec![1, 2, 3]));
+    assert_eq!(read_frame(&mut reader).unwrap(), None);
+}
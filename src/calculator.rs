@@ -1,24 +1,50 @@
-use crate::{errors::CalculatorErrors, operation::Operation};
+use crate::{
+    errors::CalculatorErrors, expression::Expression, operation::Operation, overflow::Overflow,
+    width::Width,
+};
 
-/// A basic calculator that operates on an 8-bit unsigned integer (u8).
+/// A basic calculator that operates on an integer of a configurable `Width`.
 ///
 /// The calculator supports basic arithmetic operations (addition, subtraction, multiplication, division)
-/// and maintains a single value. Operations that exceed the range of u8 (0-255) will wrap around.
+/// and maintains a single value. Operations that exceed the range of the width are handled according
+/// to the calculator's `Overflow` policy. By default the calculator operates on an 8-bit unsigned
+/// integer (u8) and wraps around.
 #[derive(Default, Debug)]
 pub struct Calculator {
-    value: u8,
+    value: i128,
+    width: Width,
+    overflow: Overflow,
 }
 
 impl Calculator {
+    /// Creates a calculator with value zero.
+    ///
+    /// # Arguments:
+    ///
+    /// * `width` - The numeric width of the calculator's value.
+    /// * `overflow` - The policy applied when a result does not fit in the width.
+    pub fn new(width: Width, overflow: Overflow) -> Self {
+        Self {
+            value: 0,
+            width,
+            overflow,
+        }
+    }
+
     /// Returns the current value of the calculator.
     ///
     /// # Returns:
     ///
-    /// The current value as a `u8`.
-    pub fn value(&self) -> u8 {
+    /// The current value, always within the range of the calculator's width.
+    pub fn value(&self) -> i128 {
         self.value
     }
 
+    /// Returns the numeric width of the calculator.
+    pub fn width(&self) -> Width {
+        self.width
+    }
+
     /// Applies an operation to the calculator.
     ///
     /// # Arguments:
@@ -27,28 +53,241 @@ impl Calculator {
     ///
     /// # Errors:
     ///
-    /// Returns `CalculatorErrors::DivisionByZero` if a division by zero is attempted.
+    /// Returns `CalculatorErrors::DivisionByZero` if a division by zero is attempted, or
+    /// `CalculatorErrors::Overflow` if the result does not fit and the overflow policy is checked.
     ///
     /// # Returns:
     ///
-    /// - `Ok(Some(u8))` if the operation is `Get`.
+    /// - `Ok(Some(i128))` if the operation is `Get`.
     /// - `Ok(None)` for other operations.
     /// - `Err(CalculatorErrors)` if an error occurs.
-    pub fn apply(&mut self, op: Operation) -> Result<Option<u8>, CalculatorErrors> {
-        match op {
-            Operation::Add(operand) => self.value = self.value.wrapping_add(operand),
-            Operation::Sub(operand) => self.value = self.value.wrapping_sub(operand),
-            Operation::Mul(operand) => self.value = self.value.wrapping_mul(operand),
-            Operation::Div(operand) => {
-                if operand == 0 {
-                    return Err(CalculatorErrors::DivisionByZero);
-                }
-                self.value = self.value.wrapping_div(operand)
-            }
-            Operation::Get => return Ok(Some(self.value)),
+    pub fn apply(&mut self, op: Operation) -> Result<Option<i128>, CalculatorErrors> {
+        if op == Operation::Get {
+            return Ok(Some(self.value));
         }
+
+        self.value = self.compute(&op)?;
         Ok(None)
     }
+
+    /// Applies a sequence of operations atomically: either all of them are applied,
+    /// or the calculator is left unchanged.
+    ///
+    /// # Arguments:
+    ///
+    /// * `ops` - The operations to apply, in order.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::TransactionAborted` with the (1-based) index of the
+    /// first operation that failed and its error.
+    ///
+    /// # Returns:
+    ///
+    /// The value of the calculator after each operation.
+    pub fn apply_all(&mut self, ops: Vec<Operation>) -> Result<Vec<i128>, CalculatorErrors> {
+        let previous = self.value;
+        let mut values = Vec::with_capacity(ops.len());
+
+        for (index, op) in ops.into_iter().enumerate() {
+            if let Err(e) = self.apply(op) {
+                self.value = previous;
+                return Err(CalculatorErrors::TransactionAborted(index + 1, Box::new(e)));
+            }
+            values.push(self.value);
+        }
+
+        Ok(values)
+    }
+
+    /// Computes the result of applying an operation, without modifying the calculator.
+    ///
+    /// # Errors:
+    ///
+    /// Returns the same errors as `apply`.
+    pub(crate) fn compute(&self, op: &Operation) -> Result<i128, CalculatorErrors> {
+        match *op {
+            Operation::Add(operand) => self.add(operand),
+            Operation::Sub(operand) => self.sub(operand),
+            Operation::Mul(operand) => self.mul(operand),
+            Operation::Div(operand) => self.div(operand),
+            Operation::Mod(operand) => self.rem(operand),
+            Operation::Pow(operand) => self.pow(operand),
+            Operation::And(operand) => Ok(self.value & operand),
+            Operation::Or(operand) => Ok(self.value | operand),
+            Operation::Xor(operand) => Ok(self.value ^ operand),
+            Operation::Shl(operand) => self.shl(operand),
+            Operation::Shr(operand) => Ok(self.value >> Self::shift(operand)?),
+            Operation::Set(operand) => Ok(operand),
+            Operation::Reset => Ok(0),
+            Operation::Neg => self.neg(),
+            Operation::Abs if self.value < 0 => self.neg(),
+            Operation::Abs => Ok(self.value),
+            Operation::Not => Ok(self.width.wrap(!self.value)),
+            Operation::Eval(ref expression) => self.evaluate(expression),
+            Operation::IfEquals(expected, ref op) if expected == self.value => self.compute(op),
+            Operation::IfEquals(_, _) => Err(CalculatorErrors::CompareFailure(self.value)),
+            Operation::Get => Ok(self.value),
+        }
+    }
+
+    /// Evaluates an expression, replacing its placeholder by the current value.
+    ///
+    /// Every operator is computed as the corresponding `Operation`, so each intermediate
+    /// result follows the width and overflow policy of the calculator.
+    fn evaluate(&self, expression: &Expression) -> Result<i128, CalculatorErrors> {
+        match expression {
+            Expression::Number(number) => Ok(*number),
+            Expression::Current => Ok(self.value),
+            Expression::Unary(operator, operand) => {
+                let operand = self.evaluate(operand)?;
+                self.with_value(operand)
+                    .compute(&Operation::get_unary_op(operator)?)
+            }
+            Expression::Binary(operator, left, right) => {
+                let (left, right) = (self.evaluate(left)?, self.evaluate(right)?);
+                self.with_value(left)
+                    .compute(&Operation::get_binary_op(operator, right)?)
+            }
+        }
+    }
+
+    /// Returns a calculator with the same width and overflow policy holding the given value.
+    pub(crate) fn with_value(&self, value: i128) -> Self {
+        Self {
+            value,
+            width: self.width,
+            overflow: self.overflow,
+        }
+    }
+
+    /// Computes the addition of the operand to the current value.
+    fn add(&self, operand: i128) -> Result<i128, CalculatorErrors> {
+        let value = self.value;
+        self.resolve(
+            value.checked_add(operand),
+            value.saturating_add(operand),
+            value.wrapping_add(operand),
+        )
+    }
+
+    /// Computes the subtraction of the operand from the current value.
+    fn sub(&self, operand: i128) -> Result<i128, CalculatorErrors> {
+        let value = self.value;
+        self.resolve(
+            value.checked_sub(operand),
+            value.saturating_sub(operand),
+            value.wrapping_sub(operand),
+        )
+    }
+
+    /// Computes the negation of the current value.
+    fn neg(&self) -> Result<i128, CalculatorErrors> {
+        let value = self.value;
+        self.resolve(
+            value.checked_neg(),
+            value.saturating_neg(),
+            value.wrapping_neg(),
+        )
+    }
+
+    /// Computes the multiplication of the current value by the operand.
+    fn mul(&self, operand: i128) -> Result<i128, CalculatorErrors> {
+        let value = self.value;
+        self.resolve(
+            value.checked_mul(operand),
+            value.saturating_mul(operand),
+            value.wrapping_mul(operand),
+        )
+    }
+
+    /// Computes the division of the current value by the operand.
+    fn div(&self, operand: i128) -> Result<i128, CalculatorErrors> {
+        if operand == 0 {
+            return Err(CalculatorErrors::DivisionByZero);
+        }
+
+        let value = self.value;
+        self.resolve(
+            value.checked_div(operand),
+            value.saturating_div(operand),
+            value.wrapping_div(operand),
+        )
+    }
+
+    /// Computes the remainder of the division of the current value by the operand.
+    ///
+    /// The remainder has the sign of the current value, and never overflows.
+    fn rem(&self, operand: i128) -> Result<i128, CalculatorErrors> {
+        match self.value.checked_rem(operand) {
+            Some(remainder) => Ok(remainder),
+            None => Err(CalculatorErrors::DivisionByZero),
+        }
+    }
+
+    /// Computes the current value raised to the power of the operand.
+    fn pow(&self, operand: i128) -> Result<i128, CalculatorErrors> {
+        if operand < 0 {
+            return Err(CalculatorErrors::InvalidInteger(operand.to_string()));
+        }
+
+        // Exponents beyond u32 only matter for their parity when checked or saturating,
+        // since any base other than 0, 1 or -1 overflows long before.
+        let parity = (operand % 2) as u32;
+        let exponent = u32::try_from(operand).unwrap_or(u32::MAX - 1 + parity);
+
+        let value = self.value;
+        self.resolve(
+            value.checked_pow(exponent),
+            value.saturating_pow(exponent),
+            wrapping_pow(value, operand as u128),
+        )
+    }
+
+    /// Computes the current value shifted to the left by the operand, that is, multiplied
+    /// by 2 to the power of the operand.
+    fn shl(&self, operand: i128) -> Result<i128, CalculatorErrors> {
+        // Shifting any width by 126 bits already leaves no bit of the value in it.
+        let factor: i128 = 1 << Self::shift(operand)?.min(126);
+        self.mul(factor)
+    }
+
+    /// Converts an operand into a shift amount, which must not be negative.
+    ///
+    /// Shift amounts beyond the bits of an `i128` are clamped, since they produce the
+    /// same result as shifting by 127 bits.
+    fn shift(operand: i128) -> Result<u32, CalculatorErrors> {
+        if operand < 0 {
+            return Err(CalculatorErrors::InvalidInteger(operand.to_string()));
+        }
+        Ok(operand.min(127) as u32)
+    }
+
+    /// Brings the result of an operation into the width according to the overflow policy.
+    fn resolve(
+        &self,
+        checked: Option<i128>,
+        saturating: i128,
+        wrapping: i128,
+    ) -> Result<i128, CalculatorErrors> {
+        self.overflow
+            .resolve(self.width, checked, saturating, wrapping)
+    }
+}
+
+/// Raises a base to the power of an exponent, wrapping around the range of an `i128`.
+///
+/// Unlike `i128::wrapping_pow`, the exponent may be larger than a `u32`.
+fn wrapping_pow(mut base: i128, mut exponent: u128) -> i128 {
+    let mut result: i128 = 1;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exponent >>= 1;
+    }
+    result
 }
 
 #[test]
@@ -171,3 +410,442 @@ fn test_combined_operations() {
     calculator.apply(Operation::Div(4)).unwrap();
     assert_eq!(calculator.value(), 6)
 }
+
+#[test]
+fn test_apply_add_u16_no_overflow() {
+    let mut calculator = Calculator::new("u16".parse().unwrap(), Overflow::Wrapping);
+    calculator.apply(Operation::Add(255)).unwrap();
+    calculator.apply(Operation::Add(1)).unwrap();
+    assert_eq!(calculator.value(), 256);
+}
+
+#[test]
+fn test_apply_sub_i64_negative() {
+    let mut calculator = Calculator::new("i64".parse().unwrap(), Overflow::Wrapping);
+    calculator.apply(Operation::Sub(10)).unwrap();
+    assert_eq!(calculator.value(), -10);
+}
+
+#[test]
+fn test_apply_add_u64_overflow() {
+    let mut calculator = Calculator::new("u64".parse().unwrap(), Overflow::Wrapping);
+    calculator.apply(Operation::Add(u64::MAX as i128)).unwrap();
+    calculator.apply(Operation::Add(1)).unwrap();
+    assert_eq!(calculator.value(), 0);
+}
+
+#[test]
+fn test_apply_mul_u64_overflow() {
+    let mut calculator = Calculator::new("u64".parse().unwrap(), Overflow::Wrapping);
+    calculator.apply(Operation::Add(u64::MAX as i128)).unwrap();
+    calculator.apply(Operation::Mul(u64::MAX as i128)).unwrap();
+    assert_eq!(calculator.value(), 1);
+}
+
+#[test]
+fn test_apply_div_i8_min_by_minus_one() {
+    let mut calculator = Calculator::new("i8".parse().unwrap(), Overflow::Wrapping);
+    calculator.apply(Operation::Sub(127)).unwrap();
+    calculator.apply(Operation::Sub(1)).unwrap();
+    calculator.apply(Operation::Div(-1)).unwrap();
+    assert_eq!(calculator.value(), -128);
+}
+
+#[test]
+fn test_apply_add_saturating() {
+    let mut calculator = Calculator::new(Width::default(), Overflow::Saturating);
+    calculator.apply(Operation::Add(250)).unwrap();
+    calculator.apply(Operation::Add(10)).unwrap();
+    assert_eq!(calculator.value(), 255);
+}
+
+#[test]
+fn test_apply_sub_saturating() {
+    let mut calculator = Calculator::new(Width::default(), Overflow::Saturating);
+    calculator.apply(Operation::Add(5)).unwrap();
+    calculator.apply(Operation::Sub(10)).unwrap();
+    assert_eq!(calculator.value(), 0);
+}
+
+#[test]
+fn test_apply_mul_u64_saturating() {
+    let mut calculator = Calculator::new("u64".parse().unwrap(), Overflow::Saturating);
+    calculator.apply(Operation::Add(u64::MAX as i128)).unwrap();
+    calculator.apply(Operation::Mul(u64::MAX as i128)).unwrap();
+    assert_eq!(calculator.value(), u64::MAX as i128);
+}
+
+#[test]
+fn test_apply_add_checked_overflow() {
+    let mut calculator = Calculator::new(Width::default(), Overflow::Checked);
+    calculator.apply(Operation::Add(250)).unwrap();
+    match calculator.apply(Operation::Add(10)) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::Overflow => (),
+            _ => panic!("Should throw Overflow error"),
+        },
+    }
+    assert_eq!(calculator.value(), 250);
+}
+
+#[test]
+fn test_apply_sub_checked_underflow() {
+    let mut calculator = Calculator::new(Width::default(), Overflow::Checked);
+    match calculator.apply(Operation::Sub(1)) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::Overflow => (),
+            _ => panic!("Should throw Overflow error"),
+        },
+    }
+    assert_eq!(calculator.value(), 0);
+}
+
+#[test]
+fn test_apply_checked_in_range() {
+    let mut calculator = Calculator::new(Width::default(), Overflow::Checked);
+    calculator.apply(Operation::Add(100)).unwrap();
+    calculator.apply(Operation::Mul(2)).unwrap();
+    assert_eq!(calculator.value(), 200);
+}
+
+#[test]
+fn test_apply_mod() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(17)).unwrap();
+    calculator.apply(Operation::Mod(5)).unwrap();
+    assert_eq!(calculator.value(), 2);
+}
+
+#[test]
+fn test_apply_mod_division_by_zero() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(17)).unwrap();
+    match calculator.apply(Operation::Mod(0)) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::DivisionByZero => (),
+            _ => panic!("Should throw DivisionByZero error"),
+        },
+    }
+    assert_eq!(calculator.value(), 17);
+}
+
+#[test]
+fn test_apply_mod_signed_negative() {
+    let mut calculator = Calculator::new("i8".parse().unwrap(), Overflow::Wrapping);
+    calculator.apply(Operation::Sub(7)).unwrap();
+    calculator.apply(Operation::Mod(3)).unwrap();
+    assert_eq!(calculator.value(), -1);
+}
+
+#[test]
+fn test_apply_pow() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(3)).unwrap();
+    calculator.apply(Operation::Pow(4)).unwrap();
+    assert_eq!(calculator.value(), 81);
+}
+
+#[test]
+fn test_apply_pow_0() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Pow(0)).unwrap();
+    assert_eq!(calculator.value(), 1);
+}
+
+#[test]
+fn test_apply_pow_wrapping() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(3)).unwrap();
+    calculator.apply(Operation::Pow(6)).unwrap();
+    assert_eq!(calculator.value(), 729 % 256);
+}
+
+#[test]
+fn test_apply_pow_huge_exponent_wrapping() {
+    // The multiplicative order of 3 modulo 2^64 is 2^62.
+    let mut calculator = Calculator::new("u64".parse().unwrap(), Overflow::Wrapping);
+    calculator.apply(Operation::Add(3)).unwrap();
+    calculator.apply(Operation::Pow(1 << 62)).unwrap();
+    assert_eq!(calculator.value(), 1);
+}
+
+#[test]
+fn test_apply_pow_huge_exponent_keeps_parity() {
+    let mut calculator = Calculator::new("i64".parse().unwrap(), Overflow::Checked);
+    calculator.apply(Operation::Sub(1)).unwrap();
+    calculator.apply(Operation::Pow(i64::MAX as i128)).unwrap();
+    assert_eq!(calculator.value(), -1);
+    calculator
+        .apply(Operation::Pow(i64::MAX as i128 - 1))
+        .unwrap();
+    assert_eq!(calculator.value(), 1);
+}
+
+#[test]
+fn test_apply_pow_checked_overflow() {
+    let mut calculator = Calculator::new(Width::default(), Overflow::Checked);
+    calculator.apply(Operation::Add(2)).unwrap();
+    match calculator.apply(Operation::Pow(8)) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::Overflow => (),
+            _ => panic!("Should throw Overflow error"),
+        },
+    }
+    assert_eq!(calculator.value(), 2);
+}
+
+#[test]
+fn test_apply_pow_negative_exponent() {
+    let mut calculator = Calculator::new("i8".parse().unwrap(), Overflow::Wrapping);
+    match calculator.apply(Operation::Pow(-1)) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidInteger(_) => (),
+            _ => panic!("Should throw InvalidInteger error"),
+        },
+    }
+}
+
+#[test]
+fn test_apply_bitwise() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(0b1100)).unwrap();
+    calculator.apply(Operation::And(0b1010)).unwrap();
+    assert_eq!(calculator.value(), 0b1000);
+    calculator.apply(Operation::Or(0b0011)).unwrap();
+    assert_eq!(calculator.value(), 0b1011);
+    calculator.apply(Operation::Xor(0b1111)).unwrap();
+    assert_eq!(calculator.value(), 0b0100);
+}
+
+#[test]
+fn test_apply_bitwise_signed() {
+    let mut calculator = Calculator::new("i8".parse().unwrap(), Overflow::Checked);
+    calculator.apply(Operation::Sub(1)).unwrap();
+    calculator.apply(Operation::Xor(127)).unwrap();
+    assert_eq!(calculator.value(), -128);
+}
+
+#[test]
+fn test_apply_shl() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(3)).unwrap();
+    calculator.apply(Operation::Shl(2)).unwrap();
+    assert_eq!(calculator.value(), 12);
+}
+
+#[test]
+fn test_apply_shl_wrapping_drops_bits() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(0b1100_0001)).unwrap();
+    calculator.apply(Operation::Shl(1)).unwrap();
+    assert_eq!(calculator.value(), 0b1000_0010);
+    calculator.apply(Operation::Shl(200)).unwrap();
+    assert_eq!(calculator.value(), 0);
+}
+
+#[test]
+fn test_apply_shl_checked_overflow() {
+    let mut calculator = Calculator::new(Width::default(), Overflow::Checked);
+    calculator.apply(Operation::Add(0b1000_0000)).unwrap();
+    assert!(calculator.apply(Operation::Shl(1)).is_err());
+    assert_eq!(calculator.value(), 0b1000_0000);
+}
+
+#[test]
+fn test_apply_shr() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(200)).unwrap();
+    calculator.apply(Operation::Shr(3)).unwrap();
+    assert_eq!(calculator.value(), 25);
+    calculator.apply(Operation::Shr(255)).unwrap();
+    assert_eq!(calculator.value(), 0);
+}
+
+#[test]
+fn test_apply_shr_signed_is_arithmetic() {
+    let mut calculator = Calculator::new("i8".parse().unwrap(), Overflow::Wrapping);
+    calculator.apply(Operation::Sub(16)).unwrap();
+    calculator.apply(Operation::Shr(2)).unwrap();
+    assert_eq!(calculator.value(), -4);
+}
+
+#[test]
+fn test_apply_set() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(10)).unwrap();
+    calculator.apply(Operation::Set(42)).unwrap();
+    assert_eq!(calculator.value(), 42);
+}
+
+#[test]
+fn test_apply_reset() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(10)).unwrap();
+    calculator.apply(Operation::Reset).unwrap();
+    assert_eq!(calculator.value(), 0);
+}
+
+#[test]
+fn test_apply_neg_unsigned_wrapping() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(1)).unwrap();
+    calculator.apply(Operation::Neg).unwrap();
+    assert_eq!(calculator.value(), 255);
+}
+
+#[test]
+fn test_apply_neg_unsigned_checked() {
+    let mut calculator = Calculator::new(Width::default(), Overflow::Checked);
+    calculator.apply(Operation::Neg).unwrap();
+    assert_eq!(calculator.value(), 0);
+    calculator.apply(Operation::Add(1)).unwrap();
+    assert!(calculator.apply(Operation::Neg).is_err());
+    assert_eq!(calculator.value(), 1);
+}
+
+#[test]
+fn test_apply_neg_signed() {
+    let mut calculator = Calculator::new("i8".parse().unwrap(), Overflow::Checked);
+    calculator.apply(Operation::Add(5)).unwrap();
+    calculator.apply(Operation::Neg).unwrap();
+    assert_eq!(calculator.value(), -5);
+}
+
+#[test]
+fn test_apply_abs_signed() {
+    let mut calculator = Calculator::new("i8".parse().unwrap(), Overflow::Saturating);
+    calculator.apply(Operation::Sub(5)).unwrap();
+    calculator.apply(Operation::Abs).unwrap();
+    assert_eq!(calculator.value(), 5);
+    calculator.apply(Operation::Set(-128)).unwrap();
+    calculator.apply(Operation::Abs).unwrap();
+    assert_eq!(calculator.value(), 127);
+}
+
+#[test]
+fn test_apply_abs_unsigned() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(200)).unwrap();
+    calculator.apply(Operation::Abs).unwrap();
+    assert_eq!(calculator.value(), 200);
+}
+
+#[test]
+fn test_apply_not() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(0b1010_0000)).unwrap();
+    calculator.apply(Operation::Not).unwrap();
+    assert_eq!(calculator.value(), 0b0101_1111);
+}
+
+#[test]
+fn test_apply_not_signed() {
+    let mut calculator = Calculator::new("i8".parse().unwrap(), Overflow::Checked);
+    calculator.apply(Operation::Not).unwrap();
+    assert_eq!(calculator.value(), -1);
+}
+
+#[test]
+fn test_apply_all() {
+    let mut calculator = Calculator::default();
+    calculator
+        .apply_all(vec![
+            Operation::Add(10),
+            Operation::Mul(3),
+            Operation::Sub(5),
+        ])
+        .unwrap();
+    assert_eq!(calculator.value(), 25);
+}
+
+#[test]
+fn test_apply_all_rolls_back_on_error() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(7)).unwrap();
+    match calculator.apply_all(vec![
+        Operation::Add(10),
+        Operation::Div(0),
+        Operation::Sub(5),
+    ]) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::TransactionAborted(2, e) => match *e {
+                CalculatorErrors::DivisionByZero => (),
+                _ => panic!("Should abort with DivisionByZero error"),
+            },
+            _ => panic!("Should throw TransactionAborted error at operation 2"),
+        },
+    }
+    assert_eq!(calculator.value(), 7);
+}
+
+#[test]
+fn test_apply_if_equals_matches() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(5)).unwrap();
+    calculator
+        .apply(Operation::IfEquals(5, Box::new(Operation::Set(9))))
+        .unwrap();
+    assert_eq!(calculator.value(), 9);
+}
+
+#[test]
+fn test_apply_if_equals_mismatch() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(5)).unwrap();
+    match calculator.apply(Operation::IfEquals(4, Box::new(Operation::Add(1)))) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::CompareFailure(5) => (),
+            _ => panic!("Should throw CompareFailure error with the current value"),
+        },
+    }
+    assert_eq!(calculator.value(), 5);
+}
+
+#[test]
+fn test_apply_eval() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(4)).unwrap();
+    let expression = Expression::parse("(x + 3) * 2 - x ^ 2 / 8", Width::default()).unwrap();
+    calculator.apply(Operation::Eval(expression)).unwrap();
+    assert_eq!(calculator.value(), 12);
+}
+
+#[test]
+fn test_apply_eval_intermediate_overflow_checked() {
+    let mut calculator = Calculator::new(Width::default(), Overflow::Checked);
+    calculator.apply(Operation::Add(200)).unwrap();
+    let expression = Expression::parse("x * 2 / 4", Width::default()).unwrap();
+    match calculator.apply(Operation::Eval(expression)) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::Overflow => (),
+            _ => panic!("Should throw Overflow error"),
+        },
+    }
+    assert_eq!(calculator.value(), 200);
+}
+
+#[test]
+fn test_apply_eval_division_by_zero() {
+    let mut calculator = Calculator::default();
+    calculator.apply(Operation::Add(9)).unwrap();
+    let expression = Expression::parse("x / (x - 9)", Width::default()).unwrap();
+    assert!(calculator.apply(Operation::Eval(expression)).is_err());
+    assert_eq!(calculator.value(), 9);
+}
+
+#[test]
+fn test_apply_eval_signed_unary() {
+    let mut calculator = Calculator::new("i32".parse().unwrap(), Overflow::Checked);
+    calculator.apply(Operation::Add(7)).unwrap();
+    let expression = Expression::parse("abs(-x * 3) + -2", "i32".parse().unwrap()).unwrap();
+    calculator.apply(Operation::Eval(expression)).unwrap();
+    assert_eq!(calculator.value(), 19);
+}
@@ -0,0 +1,393 @@
+use crate::errors::CalculatorErrors;
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+    thread,
+    time::Duration,
+};
+
+/// The number of times every address is tried before giving up on reconnecting.
+const MAX_ROUNDS: u32 = 4;
+/// The time waited after failing to connect to every address, doubled after each round.
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+/// The number of times a request is retried before giving up.
+const MAX_ATTEMPTS: usize = 10;
+/// The number of requests sent without waiting for their responses unless configured otherwise.
+pub const DEFAULT_PIPELINE_WINDOW: usize = 64;
+/// The prefix of the error sent by a node of a cluster that is not the leader.
+const NOT_LEADER: &str = "ERROR \"not leader";
+/// The error sent by a replica to a change.
+const READ_ONLY: &str = "ERROR \"read only replica\"";
+
+/// A connection of a client to one of several servers, with failover.
+///
+/// The connection is opened to the first reachable address of the list. If it drops
+/// while waiting for a response, the client reconnects, trying every address in order
+/// with an exponential backoff, and sends the request again. A request rejected by a
+/// replica, or by a node of a cluster that is not the leader, is sent again to the
+/// leader, if known, or to the next address.
+///
+/// A request sent again may be applied twice, if the server applied it before the
+/// connection dropped, unless it carries an id and the connection resumes a client
+/// session: the session is resumed on every new connection, so the server answers a
+/// request it already applied with its original response.
+#[derive(Debug)]
+pub struct Connection {
+    addrs: Vec<String>,
+    current: usize,
+    reader: BufReader<TcpStream>,
+    session: Option<String>,
+    resumed: bool,
+}
+
+impl Connection {
+    /// Opens a connection to the first reachable server of the list.
+    ///
+    /// # Arguments:
+    ///
+    /// * `addrs` - The comma-separated addresses of the servers.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::SocketFailure` if no server is reachable.
+    pub fn open(addrs: &str) -> Result<Self, CalculatorErrors> {
+        let addrs: Vec<String> = addrs.split(',').map(|addr| addr.to_owned()).collect();
+        let (current, stream) = connect_any(&addrs, 0)?;
+        Ok(Self {
+            addrs,
+            current,
+            reader: BufReader::new(stream),
+            session: None,
+            resumed: false,
+        })
+    }
+
+    /// Resumes the client session with the given token on this connection, and on every
+    /// connection opened after a failover. The session is resumed before the next request.
+    ///
+    /// # Arguments:
+    ///
+    /// * `token` - The token of the session, unique to the client.
+    pub fn resume(&mut self, token: &str) {
+        self.session = Some(token.to_owned());
+        self.resumed = false;
+    }
+
+    /// Sends a request and waits for its response, failing over to another server if
+    /// needed.
+    ///
+    /// # Arguments:
+    ///
+    /// * `line` - The request, without its terminator.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::SocketFailure` if no server answers the request.
+    ///
+    /// # Returns:
+    ///
+    /// The first line of the response, without its terminator.
+    pub fn request(&mut self, line: &str) -> Result<String, CalculatorErrors> {
+        for _ in 0..MAX_ATTEMPTS {
+            match self.try_request(line) {
+                Ok(response) => match redirect(&response) {
+                    Some(target) => self.reconnect(target)?,
+                    None => return Ok(response),
+                },
+                Err(_) => self.reconnect(None)?,
+            }
+        }
+        Err(CalculatorErrors::SocketFailure)
+    }
+
+    /// Sends requests without waiting for the response to each one before sending the
+    /// next, keeping at most `window` requests without response. The server answers them
+    /// in order. If the connection drops, or the server redirects a request, every request
+    /// without response is sent again to the next server.
+    ///
+    /// Every request must be answered with a single line.
+    ///
+    /// # Arguments:
+    ///
+    /// * `requests` - The requests, each with an index that identifies it for the caller.
+    /// * `window` - The maximum number of requests without response.
+    /// * `on_response` - Called with the index of each request and its response, in order.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::SocketFailure` if no server answers the requests.
+    pub fn pipeline<I, F>(
+        &mut self,
+        mut requests: I,
+        window: usize,
+        mut on_response: F,
+    ) -> Result<(), CalculatorErrors>
+    where
+        I: Iterator<Item = (usize, String)>,
+        F: FnMut(usize, &str),
+    {
+        let mut pending = VecDeque::new();
+        let mut failures = 0;
+        loop {
+            while pending.len() < window.max(1)
+                && let Some((index, request)) = requests.next()
+            {
+                let sent = self.send(&request).is_ok();
+                pending.push_back((index, request));
+                if !sent {
+                    self.recover(&pending, None, &mut failures)?;
+                }
+            }
+
+            let index = match pending.front() {
+                Some((index, _)) => *index,
+                None => return Ok(()),
+            };
+            match self.read_line() {
+                Ok(response) => match redirect(&response) {
+                    Some(target) => self.recover(&pending, target, &mut failures)?,
+                    None => {
+                        failures = 0;
+                        pending.pop_front();
+                        on_response(index, &response);
+                    }
+                },
+                Err(_) => self.recover(&pending, None, &mut failures)?,
+            }
+        }
+    }
+
+    /// Reads the next line sent by the server, such as the entries of a `HISTORY`.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::ListeningFailure` if the connection drops.
+    pub fn read_line(&mut self) -> Result<String, CalculatorErrors> {
+        let mut response = String::new();
+        match self.reader.read_line(&mut response) {
+            Ok(0) | Err(_) => Err(CalculatorErrors::ListeningFailure),
+            Ok(_) => Ok(response.trim_end().to_owned()),
+        }
+    }
+
+    /// Sends a request on the current connection, and reads its response.
+    fn try_request(&mut self, line: &str) -> Result<String, CalculatorErrors> {
+        self.send(line)?;
+        self.read_line()
+    }
+
+    /// Sends a request on the current connection without reading its response, resuming
+    /// the client session first if needed.
+    fn send(&mut self, line: &str) -> Result<(), CalculatorErrors> {
+        if !self.resumed
+            && let Some(token) = &self.session
+        {
+            let message = format!("SESSION {}", token);
+            self.write(&message)?;
+            self.read_line()?;
+            self.resumed = true;
+        }
+        self.write(line)
+    }
+
+    /// Writes a line on the current connection.
+    fn write(&mut self, line: &str) -> Result<(), CalculatorErrors> {
+        let stream = self.reader.get_mut();
+        let message = format!("{}\n", line);
+        if stream.write_all(message.as_bytes()).is_err() || stream.flush().is_err() {
+            return Err(CalculatorErrors::WritingFailure);
+        }
+        Ok(())
+    }
+
+    /// Reconnects, to the given address if any, and sends again every request without
+    /// response, until they are all sent.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::SocketFailure` after too many consecutive failures.
+    fn recover(
+        &mut self,
+        pending: &VecDeque<(usize, String)>,
+        target: Option<&str>,
+        failures: &mut usize,
+    ) -> Result<(), CalculatorErrors> {
+        loop {
+            *failures += 1;
+            if *failures > MAX_ATTEMPTS {
+                return Err(CalculatorErrors::SocketFailure);
+            }
+
+            self.reconnect(target)?;
+            if pending
+                .iter()
+                .all(|(_, request)| self.send(request).is_ok())
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Replaces the current connection with one to the given address, if it is reachable,
+    /// or to the next reachable address of the list.
+    fn reconnect(&mut self, target: Option<&str>) -> Result<(), CalculatorErrors> {
+        if let Some(target) = target
+            && let Ok(stream) = TcpStream::connect(target)
+        {
+            self.reader = BufReader::new(stream);
+            self.resumed = false;
+            return Ok(());
+        }
+
+        let start = (self.current + 1) % self.addrs.len();
+        let (current, stream) = connect_any(&self.addrs, start)?;
+        self.current = current;
+        self.reader = BufReader::new(stream);
+        self.resumed = false;
+        Ok(())
+    }
+}
+
+/// Connects to the first reachable address, starting at the given one, retrying with an
+/// exponential backoff.
+///
+/// # Returns:
+///
+/// The index of the address, and the connection.
+fn connect_any(addrs: &[String], start: usize) -> Result<(usize, TcpStream), CalculatorErrors> {
+    let mut backoff = MIN_BACKOFF;
+    for round in 0..MAX_ROUNDS {
+        for offset in 0..addrs.len() {
+            let index = (start + offset) % addrs.len();
+            if let Ok(stream) = TcpStream::connect(&addrs[index]) {
+                return Ok((index, stream));
+            }
+        }
+
+        if round + 1 < MAX_ROUNDS {
+            thread::sleep(backoff);
+            backoff *= 2;
+        }
+    }
+    Err(CalculatorErrors::SocketFailure)
+}
+
+/// Returns whether a response asks the client to send the request to another server and,
+/// if so, the address of that server, if known.
+fn redirect(response: &str) -> Option<Option<&str>> {
+    if response.starts_with(NOT_LEADER) {
+        return Some(leader(response));
+    }
+    if response == READ_ONLY {
+        return Some(None);
+    }
+    None
+}
+
+/// Returns the address of the leader carried by a `not leader` error, if any.
+fn leader(response: &str) -> Option<&str> {
+    response
+        .strip_prefix(NOT_LEADER)
+        .and_then(|rest| rest.strip_prefix(": "))
+        .and_then(|rest| rest.strip_suffix('"'))
+}
+
+#[cfg(test)]
+fn fake_server(responses: Vec<Option<String>>) -> String {
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        for response in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(response) = response {
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+        }
+    });
+    addr
+}
+
+#[test]
+fn test_request_resent_after_connection_drop() {
+    let addr = fake_server(vec![None, Some("VALUE 5\n".to_owned())]);
+    let mut connection = Connection::open(&addr).unwrap();
+    assert_eq!(connection.request("GET").unwrap(), "VALUE 5");
+}
+
+#[test]
+fn test_request_fails_over_to_next_address() {
+    let first = fake_server(vec![None]);
+    let second = fake_server(vec![Some("OK\n".to_owned())]);
+    let mut connection = Connection::open(&format!("{},{}", first, second)).unwrap();
+    assert_eq!(connection.request("OP + 1").unwrap(), "OK");
+}
+
+#[test]
+fn test_request_follows_leader() {
+    let leader_addr = fake_server(vec![Some("OK\n".to_owned())]);
+    let redirect = format!("ERROR \"not leader: {}\"\n", leader_addr);
+    let follower = fake_server(vec![Some(redirect)]);
+    let mut connection = Connection::open(&follower).unwrap();
+    assert_eq!(connection.request("OP + 1").unwrap(), "OK");
+}
+
+#[test]
+fn test_pipeline_responses_in_order() {
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        for _ in 0..3 {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let response = format!("ECHO {}", line);
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+        }
+    });
+
+    let mut connection = Connection::open(&addr).unwrap();
+    let requests = (1..=3).map(|index| (index, format!("OP + {}", index)));
+    let mut responses = vec![];
+    connection
+        .pipeline(requests, 2, |index, response| {
+            responses.push((index, response.to_owned()))
+        })
+        .unwrap();
+    assert_eq!(
+        responses,
+        vec![
+            (1, "ECHO OP + 1".to_owned()),
+            (2, "ECHO OP + 2".to_owned()),
+            (3, "ECHO OP + 3".to_owned())
+        ]
+    );
+}
+
+#[test]
+fn test_open_unreachable_addresses() {
+    match Connection::open("127.0.0.1:1") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::SocketFailure => (),
+            _ => panic!("Should throw SocketFailure error, got: {:?}", e),
+        },
+    }
+}
+
+#[test]
+fn test_leader_from_error() {
+    assert_eq!(
+        leader("ERROR \"not leader: 127.0.0.1:8080\""),
+        Some("127.0.0.1:8080")
+    );
+    assert_eq!(leader("ERROR \"not leader\""), None);
+}
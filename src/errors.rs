@@ -2,13 +2,17 @@
 ///
 /// Each variant corresponds to a specific error scenario that may arise during
 /// the operation of the calculator or its associated components.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum CalculatorErrors {
     // Client errors:
     /// A division by zero was attempted
     ///
     /// client: OP / 0
     DivisionByZero,
+    /// The result does not fit in the calculator's width and the overflow policy is checked
+    ///
+    /// client: OP + 1 (with value 255 in u8)
+    Overflow,
     /// The operation is not valid
     ///
     /// client: OP multiplicar 0
@@ -17,10 +21,69 @@ pub enum CalculatorErrors {
     ///
     /// client: OP + cinco
     InvalidInteger(String),
+    /// An expression is not valid
+    ///
+    /// client: EVAL (x + 3
+    InvalidExpression(String),
     /// An unexpected message was received
     ///
     /// client: OK
     UnexpectedMessage(String),
+    /// The value of the calculator differs from the expected one, which is carried by the error
+    ///
+    /// client: CAS 5 10 (with value 3)
+    CompareFailure(i128),
+    /// There is no previous value to restore
+    ///
+    /// client: UNDO (before any operation)
+    NothingToUndo,
+    /// There is no undone value to restore
+    ///
+    /// client: REDO (before any UNDO)
+    NothingToRedo,
+    /// A transaction was committed or rolled back without being started
+    ///
+    /// client: COMMIT
+    NoTransaction,
+    /// A transaction was started while another one was in progress
+    ///
+    /// client: BEGIN (after BEGIN)
+    NestedTransaction,
+    /// A client tried to change a register of a replica, which only follows its primary
+    ///
+    /// client: OP + 1 (to a replica)
+    ReadOnlyReplica,
+    /// A client sent a change to a node of a cluster that is not the leader, whose address
+    /// is carried by the error if it is known
+    ///
+    /// client: OP + 1 (to a follower)
+    NotLeader(Option<String>),
+    /// A primary, or a message that fences one, carries an epoch older than the epoch of the
+    /// server, which is carried by the error
+    ///
+    /// server: FENCE 1 secret (to a primary of epoch 2)
+    StaleEpoch(u64),
+    /// A client sent an administrative message without the admin token of the server
+    ///
+    /// client: SHUTDOWN wrong-token
+    Unauthorized,
+    /// An operation of a transaction failed, so none of them was applied
+    ///
+    /// client: BEGIN, OP + 1, OP / 0, COMMIT
+    TransactionAborted(usize, Box<CalculatorErrors>),
+    /// A frame of the binary protocol is not valid
+    ///
+    /// client: a frame of 3 bytes
+    InvalidFrame,
+    /// A message of the JSON protocol is not a valid JSON object
+    ///
+    /// client: {"op": "+"
+    InvalidJson,
+    /// A client selected a new register while the server holds as many registers as it
+    /// allows, which is carried by the error
+    ///
+    /// client: USE b (to a server started with --max-registers 1)
+    TooManyRegisters(usize),
 
     // Server errors:
     /// A thread failed to join
@@ -33,35 +96,109 @@ pub enum CalculatorErrors {
     ListeningFailure,
     /// A failure in open the socket
     SocketFailure,
+    /// Every worker of the server is busy and its queue of connections is full
+    ServerBusy,
     /// A failure in open the file
     FileOpenFailure,
     /// A failure in read the line in the file
     ReadLineFailure,
+    /// A failure in reading or writing the data directory
+    StorageFailure,
+    /// A failure in installing the handler of the termination signals
+    SignalFailure,
     /// A failure in lenght of arguments
     ArgsLenFailure,
+    /// A command line argument is not valid
+    ///
+    /// server: 127.0.0.1:8080 --width f32
+    InvalidArgument(String),
 }
 
 impl CalculatorErrors {
     /// Returns the error message according to the protocol specification
     pub fn get_message(&self) -> String {
+        format!("ERROR \"{}\"", self.reason())
+    }
+
+    /// Returns the reason of the error, as it appears between quotes in the message
+    pub fn reason(&self) -> String {
         match self {
-            Self::DivisionByZero => "ERROR \"division by zero\"".to_owned(),
+            Self::DivisionByZero => "division by zero".to_owned(),
+            Self::Overflow => "overflow".to_owned(),
             Self::InvalidOperation(message) => {
-                format!("ERROR \"parsing error: unknown operation: {}\"", message)
+                format!("parsing error: unknown operation: {}", message)
             }
-            Self::InvalidInteger(message) => {
-                format!("ERROR \"parsing error: invalid integer: {}\"", message)
+            Self::InvalidInteger(message) => format!("parsing error: invalid integer: {}", message),
+            Self::InvalidExpression(token) => {
+                format!("parsing error: invalid expression: {}", token)
             }
-            Self::UnexpectedMessage(target) => format!("ERROR \"unexpected message: {}\"", target),
+            Self::UnexpectedMessage(target) => format!("unexpected message: {}", target),
+            Self::CompareFailure(value) => format!("compare failed: current value {}", value),
+            Self::NothingToUndo => "nothing to undo".to_owned(),
+            Self::NothingToRedo => "nothing to redo".to_owned(),
+            Self::NoTransaction => "no transaction in progress".to_owned(),
+            Self::NestedTransaction => "transaction already in progress".to_owned(),
+            Self::ReadOnlyReplica => "read only replica".to_owned(),
+            Self::NotLeader(Some(leader)) => format!("not leader: {}", leader),
+            Self::NotLeader(None) => "not leader".to_owned(),
+            Self::StaleEpoch(epoch) => format!("stale epoch: {}", epoch),
+            Self::Unauthorized => "unauthorized".to_owned(),
+            Self::TransactionAborted(index, e) => {
+                format!("transaction aborted at operation {}: {}", index, e.reason())
+            }
+            Self::InvalidFrame => "invalid frame".to_owned(),
+            Self::InvalidJson => "invalid json".to_owned(),
+            Self::TooManyRegisters(max) => format!("too many registers: {}", max),
 
-            Self::JoinFailure => "ERROR \"thread join failure\"".to_owned(),
-            Self::LockFailure => "ERROR \"mutex lock failure\"".to_owned(),
-            Self::WritingFailure => "ERROR \"writing failure\"".to_owned(),
-            Self::ListeningFailure => "ERROR \"reading failure\"".to_owned(),
-            Self::SocketFailure => "ERROR \"socket failure\"".to_owned(),
-            Self::FileOpenFailure => "ERROR \"file open failure\"".to_owned(),
-            Self::ReadLineFailure => "ERROR \"line reading failure\"".to_owned(),
-            Self::ArgsLenFailure => "ERROR \"invalid number of arguments\"".to_owned(),
+            Self::JoinFailure => "thread join failure".to_owned(),
+            Self::LockFailure => "mutex lock failure".to_owned(),
+            Self::WritingFailure => "writing failure".to_owned(),
+            Self::ListeningFailure => "reading failure".to_owned(),
+            Self::SocketFailure => "socket failure".to_owned(),
+            Self::ServerBusy => "server busy".to_owned(),
+            Self::FileOpenFailure => "file open failure".to_owned(),
+            Self::ReadLineFailure => "line reading failure".to_owned(),
+            Self::StorageFailure => "storage failure".to_owned(),
+            Self::SignalFailure => "signal handler failure".to_owned(),
+            Self::ArgsLenFailure => "invalid number of arguments".to_owned(),
+            Self::InvalidArgument(argument) => format!("invalid argument: {}", argument),
+        }
+    }
+
+    /// Returns an identifier of the kind of error, which does not depend on its details
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::DivisionByZero => "division_by_zero",
+            Self::Overflow => "overflow",
+            Self::InvalidOperation(_) => "invalid_operation",
+            Self::InvalidInteger(_) => "invalid_integer",
+            Self::InvalidExpression(_) => "invalid_expression",
+            Self::UnexpectedMessage(_) => "unexpected_message",
+            Self::CompareFailure(_) => "compare_failure",
+            Self::NothingToUndo => "nothing_to_undo",
+            Self::NothingToRedo => "nothing_to_redo",
+            Self::NoTransaction => "no_transaction",
+            Self::NestedTransaction => "nested_transaction",
+            Self::ReadOnlyReplica => "read_only_replica",
+            Self::NotLeader(_) => "not_leader",
+            Self::StaleEpoch(_) => "stale_epoch",
+            Self::Unauthorized => "unauthorized",
+            Self::TransactionAborted(..) => "transaction_aborted",
+            Self::InvalidFrame => "invalid_frame",
+            Self::InvalidJson => "invalid_json",
+            Self::TooManyRegisters(_) => "too_many_registers",
+            Self::JoinFailure => "join_failure",
+            Self::LockFailure => "lock_failure",
+            Self::WritingFailure => "writing_failure",
+            Self::ListeningFailure => "listening_failure",
+            Self::SocketFailure => "socket_failure",
+            Self::ServerBusy => "server_busy",
+            Self::FileOpenFailure => "file_open_failure",
+            Self::ReadLineFailure => "read_line_failure",
+            Self::StorageFailure => "storage_failure",
+            Self::SignalFailure => "signal_failure",
+            Self::ArgsLenFailure => "args_len_failure",
+            Self::InvalidArgument(_) => "invalid_argument",
         }
     }
 }
@@ -0,0 +1,306 @@
+use crate::{
+    action::Action, binary, errors::CalculatorErrors, json, log_event::LogEvent, logger::Logger,
+    poll_fd::PollFd, registry::Registry, response::Response, session::Session,
+};
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::TcpStream,
+    sync::Arc,
+    time::Duration,
+};
+
+/// The number of bytes read from a connection at once.
+const READ_CHUNK: usize = 4096;
+/// The maximum number of bytes a client may send without ending a line. It is larger than
+/// any frame of the binary protocol.
+const MAX_PENDING_INPUT: usize = 128 * 1024;
+/// The number of bytes of responses not yet written above which the messages of a client
+/// are not processed, until it reads them.
+const MAX_PENDING_OUTPUT: usize = 64 * 1024;
+/// The time a blocking write to a replica may take before the connection is dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A client connection handled by the event loop, with the bytes received and not yet
+/// processed, and the bytes of the responses not yet written.
+pub struct EventClient {
+    /// The connection, in non-blocking mode.
+    stream: TcpStream,
+    /// The session of the client.
+    session: Session,
+    /// Whether the client uses the binary protocol, once its first byte is received.
+    binary: Option<bool>,
+    /// The bytes received and not yet processed.
+    input: Vec<u8>,
+    /// The bytes of the responses not yet written.
+    output: Vec<u8>,
+    /// The number of lines of the batch being received, and the lines received so far.
+    batch: Option<(usize, Vec<String>)>,
+    /// Whether the client may still send messages.
+    reading: bool,
+    /// Whether the connection must be dropped, because it failed or was handed over to
+    /// the replicas of the server.
+    closed: bool,
+}
+
+impl EventClient {
+    /// Creates the state of a new connection, switching it to non-blocking mode.
+    ///
+    /// # Arguments:
+    ///
+    /// * `stream` - The connection.
+    /// * `registry` - The registry of named calculators shared by every client.
+    /// * `logger` - The logger of the server.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::SocketFailure` if the connection cannot be switched to
+    /// non-blocking mode, or the error produced while creating the session.
+    pub fn new(
+        stream: TcpStream,
+        registry: &Arc<Registry>,
+        logger: &Logger,
+    ) -> Result<Self, CalculatorErrors> {
+        if stream.set_nonblocking(true).is_err() {
+            return Err(CalculatorErrors::SocketFailure);
+        }
+        let session = Session::new(Arc::clone(registry))?;
+        let peer = stream
+            .peer_addr()
+            .map_or("unknown".to_owned(), |addr| addr.to_string());
+        let client = session.client();
+        logger.log(LogEvent::ConnectionOpened { client, peer });
+
+        Ok(Self {
+            stream,
+            session,
+            binary: None,
+            input: vec![],
+            output: vec![],
+            batch: None,
+            reading: true,
+            closed: false,
+        })
+    }
+
+    /// Reads what the client sent, processes every complete message, and writes as much
+    /// of the responses as the connection accepts, without blocking. Once the connection
+    /// accepts no more, the messages left are processed when it is writable again.
+    ///
+    /// # Arguments:
+    ///
+    /// * `registry` - The registry of named calculators shared by every client.
+    /// * `logger` - The logger of the server.
+    pub fn poll(&mut self, registry: &Registry, logger: &Logger) {
+        self.read();
+        loop {
+            self.process(registry, logger);
+            if !self.write() {
+                return;
+            }
+        }
+    }
+
+    /// Returns the descriptor of the connection, waited for until the client sends more
+    /// messages, if they would be processed, or until the responses not yet written can be.
+    pub fn poll_fd(&self) -> PollFd {
+        let read = self.reading
+            && self.input.len() <= MAX_PENDING_INPUT
+            && self.output.len() <= MAX_PENDING_OUTPUT;
+        PollFd::new(&self.stream, read, !self.output.is_empty())
+    }
+
+    /// Returns whether the connection is over: either it failed, or the client closed it
+    /// and every response was written.
+    pub fn is_finished(&self) -> bool {
+        self.closed || (!self.reading && self.output.is_empty())
+    }
+
+    /// Stops reading from the client, discarding the messages not yet processed, so the
+    /// connection is finished once the responses not yet written are.
+    pub fn stop_reading(&mut self) {
+        self.reading = false;
+        self.input.clear();
+        self.batch = None;
+    }
+
+    /// Writes as much of the responses not yet written as the connection accepts, without
+    /// blocking.
+    pub fn flush(&mut self) {
+        self.write();
+    }
+
+    /// Returns the id of the client.
+    pub fn client(&self) -> u64 {
+        self.session.client()
+    }
+
+    /// Switches the connection to blocking mode, with a timeout on writes so that a client
+    /// that does not read cannot stall the server.
+    fn block(&self) -> Result<(), CalculatorErrors> {
+        let blocking = self
+            .stream
+            .set_nonblocking(false)
+            .and_then(|()| self.stream.set_write_timeout(Some(WRITE_TIMEOUT)));
+        match blocking {
+            Ok(()) => Ok(()),
+            Err(_) => Err(CalculatorErrors::SocketFailure),
+        }
+    }
+
+    /// Reads the bytes available on the connection.
+    fn read(&mut self) {
+        let mut chunk = [0; READ_CHUNK];
+        while self.reading && !self.closed && self.input.len() <= MAX_PENDING_INPUT {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => self.reading = false,
+                Ok(read) => self.input.extend_from_slice(&chunk[..read]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => self.closed = true,
+            }
+        }
+    }
+
+    /// Writes as much of the responses as the connection accepts.
+    ///
+    /// # Returns:
+    ///
+    /// Whether any byte was written.
+    fn write(&mut self) -> bool {
+        let mut written = 0;
+        while written < self.output.len() && !self.closed {
+            match self.stream.write(&self.output[written..]) {
+                Ok(0) => self.closed = true,
+                Ok(count) => written += count,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => self.closed = true,
+            }
+        }
+        self.output.drain(..written);
+        written > 0
+    }
+
+    /// Processes every complete message received, with the protocol chosen by the first
+    /// byte the client sent, until too many responses are waiting to be written.
+    fn process(&mut self, registry: &Registry, logger: &Logger) {
+        if self.binary.is_none()
+            && let Some(&first) = self.input.first()
+        {
+            self.binary = Some(first == binary::MAGIC);
+            if first == binary::MAGIC {
+                self.input.drain(..1);
+            }
+        }
+
+        match self.binary {
+            Some(true) => self.process_frames(registry, logger),
+            _ => self.process_lines(registry, logger),
+        }
+    }
+
+    /// Processes every complete frame of the binary protocol.
+    fn process_frames(&mut self, registry: &Registry, logger: &Logger) {
+        while !self.closed
+            && self.output.len() <= MAX_PENDING_OUTPUT
+            && let Some(frame) = binary::take_frame(&mut self.input)
+        {
+            let (message, response) = match binary::decode_operation(&frame, registry.width()) {
+                Ok(op) => (op.to_string(), self.session.handle_operation(op)),
+                Err(e) => ("binary frame".to_owned(), Response::Error(e)),
+            };
+            logger.log_response(self.client(), &message, &response);
+            let _ = binary::write_frame(&mut self.output, &binary::encode_response(&response));
+        }
+    }
+
+    /// Processes every complete line of the text protocol, and the last line if the
+    /// client closed the connection without ending it.
+    fn process_lines(&mut self, registry: &Registry, logger: &Logger) {
+        while !self.closed && self.output.len() <= MAX_PENDING_OUTPUT {
+            let end = match self.input.iter().position(|&byte| byte == b'\n') {
+                Some(end) => end + 1,
+                None if !self.reading && !self.input.is_empty() => self.input.len(),
+                None if self.input.len() > MAX_PENDING_INPUT => return self.reject_line(),
+                None => return,
+            };
+            let bytes: Vec<u8> = self.input.drain(..end).collect();
+            let line = String::from_utf8_lossy(&bytes);
+            self.handle_line(line.trim_end_matches(['\r', '\n']), registry, logger);
+        }
+    }
+
+    /// Answers a line too long to be a message, and stops reading from the client.
+    fn reject_line(&mut self) {
+        let e = CalculatorErrors::UnexpectedMessage("line too long".to_owned());
+        self.respond(&Response::Error(e));
+        self.input.clear();
+        self.reading = false;
+    }
+
+    /// Processes a line of the text or the JSON protocol.
+    fn handle_line(&mut self, line: &str, registry: &Registry, logger: &Logger) {
+        if let Some((_, lines)) = &mut self.batch {
+            lines.push(line.to_owned());
+            return self.complete_batch();
+        }
+        if line.starts_with('{') {
+            let response = match json::decode_operation(line, registry.width()) {
+                Ok(op) => self.session.handle_operation(op),
+                Err(e) => Response::Error(e),
+            };
+            logger.log_response(self.client(), line, &response);
+            let line = format!("{}\n", json::encode_response(&response));
+            return self.output.extend_from_slice(line.as_bytes());
+        }
+
+        let action = self.session.handle_line(line);
+        logger.log_action(self.client(), line, &action);
+        match action {
+            Action::Respond(response) => self.respond(&response),
+            Action::Replicate => self.replicate(registry, logger),
+            Action::ReadBatch(count) => {
+                self.batch = Some((count, vec![]));
+                self.complete_batch();
+            }
+            Action::Cluster(answer) => {
+                self.output
+                    .extend_from_slice(format!("{}\n", answer).as_bytes());
+            }
+        }
+    }
+
+    /// Applies the batch being received, once every line of it is.
+    fn complete_batch(&mut self) {
+        if let Some((count, lines)) = &self.batch
+            && lines.len() == *count
+        {
+            let response = self.session.handle_batch(lines);
+            self.batch = None;
+            self.respond(&response);
+        }
+    }
+
+    /// Hands the connection over to the replicas of the server, which write to it with
+    /// blocking writes bounded by a timeout, so the event loop stops handling it. The
+    /// responses not yet written are written first, the same way.
+    fn replicate(&mut self, registry: &Registry, logger: &Logger) {
+        let result = self
+            .block()
+            .and_then(|()| match self.stream.write_all(&self.output) {
+                Ok(()) => registry.add_replica(&self.stream, logger),
+                Err(_) => Err(CalculatorErrors::WritingFailure),
+            });
+        self.output.clear();
+        if let Err(e) = result {
+            Response::Error(e).send_response(&self.stream);
+        }
+        self.closed = true;
+    }
+
+    /// Queues a response of the text protocol.
+    fn respond(&mut self, response: &Response) {
+        let line = format!("{}\n", response.get_message());
+        self.output.extend_from_slice(line.as_bytes());
+    }
+}
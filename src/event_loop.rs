@@ -0,0 +1,139 @@
+use crate::{
+    errors::CalculatorErrors, event_client::EventClient, log_event::LogEvent, logger::Logger,
+    poll_fd::PollFd, registry::Registry,
+};
+use std::{
+    io::ErrorKind,
+    net::TcpListener,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The longest time the loop waits for a ready connection before checking again whether
+/// the server must shut down.
+const SHUTDOWN_POLL: Duration = Duration::from_millis(50);
+/// The time the loop keeps writing the pending responses of every client on shutdown.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Serves every client connection from the calling thread, until the server must shut
+/// down, with non-blocking sockets: each round of the loop waits with `poll(2)` until the
+/// listener or some connections are ready, accepts the pending connections, and reads,
+/// processes and writes whatever each ready connection has. Idle clients cost no thread,
+/// and are not touched until they send something.
+///
+/// On shutdown, the loop stops reading from the clients, and keeps waiting with `poll(2)`
+/// for their connections to accept the responses still pending, until every response is
+/// written or `FLUSH_TIMEOUT` expires. Then it closes every connection.
+///
+/// # Arguments:
+///
+/// * `listener` - The TCP listener used to accept incoming connections.
+/// * `registry` - The registry of named calculators shared by every client.
+/// * `logger` - The logger of the server.
+pub fn serve(listener: &TcpListener, registry: &Arc<Registry>, logger: &Logger) {
+    if listener.set_nonblocking(true).is_err() {
+        return logger.report(CalculatorErrors::SocketFailure);
+    }
+
+    let mut clients = vec![];
+    while !registry.is_shutting_down() {
+        if let Err(e) = serve_ready(listener, registry, logger, &mut clients) {
+            logger.report(e);
+            break;
+        }
+    }
+
+    flush(clients, logger);
+}
+
+/// Writes the responses pending for every client, waiting for the connections to accept
+/// them up to a single deadline, and closes every connection.
+fn flush(mut clients: Vec<EventClient>, logger: &Logger) {
+    let deadline = Instant::now() + FLUSH_TIMEOUT;
+    clients.iter_mut().for_each(EventClient::stop_reading);
+    clients.retain(|client| !close_finished(client, logger));
+
+    while !clients.is_empty()
+        && let Some(timeout) = deadline.checked_duration_since(Instant::now())
+    {
+        let mut fds: Vec<PollFd> = clients.iter().map(EventClient::poll_fd).collect();
+        if let Err(e) = PollFd::wait(&mut fds, timeout) {
+            logger.report(e);
+            break;
+        }
+        for (client, fd) in clients.iter_mut().zip(&fds) {
+            if fd.is_ready() {
+                client.flush();
+            }
+        }
+        clients.retain(|client| !close_finished(client, logger));
+    }
+
+    for client in clients {
+        logger.log(LogEvent::ConnectionClosed {
+            client: client.client(),
+        });
+    }
+}
+
+/// Waits until the listener or some connections are ready, and serves them. Connections
+/// accepted in this round are waited for from the next one.
+///
+/// # Errors:
+///
+/// Returns `CalculatorErrors::SocketFailure` if the sockets cannot be waited for.
+fn serve_ready(
+    listener: &TcpListener,
+    registry: &Arc<Registry>,
+    logger: &Logger,
+    clients: &mut Vec<EventClient>,
+) -> Result<(), CalculatorErrors> {
+    let mut fds = vec![PollFd::new(listener, true, false)];
+    fds.extend(clients.iter().map(EventClient::poll_fd));
+    PollFd::wait(&mut fds, SHUTDOWN_POLL)?;
+
+    for (client, fd) in clients.iter_mut().zip(&fds[1..]) {
+        if fd.is_ready() {
+            client.poll(registry, logger);
+        }
+    }
+    if fds[0].is_ready() {
+        accept(listener, registry, logger, clients);
+    }
+    clients.retain(|client| !close_finished(client, logger));
+    Ok(())
+}
+
+/// Accepts every pending connection.
+fn accept(
+    listener: &TcpListener,
+    registry: &Arc<Registry>,
+    logger: &Logger,
+    clients: &mut Vec<EventClient>,
+) {
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => match EventClient::new(stream, registry, logger) {
+                Ok(client) => clients.push(client),
+                Err(e) => logger.report(e),
+            },
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+            Err(_) => return logger.report(CalculatorErrors::ListeningFailure),
+        }
+    }
+}
+
+/// Logs that a connection is closed, if it is finished.
+///
+/// # Returns:
+///
+/// Whether the connection is finished.
+fn close_finished(client: &EventClient, logger: &Logger) -> bool {
+    if !client.is_finished() {
+        return false;
+    }
+    logger.log(LogEvent::ConnectionClosed {
+        client: client.client(),
+    });
+    true
+}
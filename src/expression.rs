@@ -0,0 +1,342 @@
+use crate::{errors::CalculatorErrors, width::Width};
+use std::{fmt, iter::Peekable, vec::IntoIter};
+
+/// The binary operators of an expression, grouped by precedence from lowest to highest.
+const PRECEDENCE: [&[&str]; 6] = [
+    &["|"],
+    &["xor"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+/// The placeholder for the calculator's current value in an expression.
+const CURRENT_VALUE: &str = "x";
+
+/// The maximum nesting of an expression: parentheses, unary operators, exponents and
+/// chained binary operators each add a level. It bounds the recursion of parsing and
+/// evaluating an expression, which would otherwise overflow the stack of the thread of a
+/// client: each parenthesis nests a call for every precedence level.
+const MAX_DEPTH: usize = 64;
+
+/// The tokens of an expression being parsed.
+type Tokens = Peekable<IntoIter<String>>;
+
+/// An infix expression, parsed into an abstract syntax tree.
+///
+/// Expressions support parentheses, the binary operators of `Operation` with the usual
+/// precedence (`^` binds tightest and is right associative), the prefix operators `-`,
+/// `neg`, `abs` and `not`, and `x` as a placeholder for the current value,
+/// e.g. `(x + 3) * 2`.
+#[derive(PartialEq, Eq, Debug)]
+pub enum Expression {
+    /// A literal number.
+    Number(i128),
+    /// The calculator's current value.
+    Current,
+    /// A unary operator (`neg`, `abs` or `not`) applied to an expression.
+    Unary(String, Box<Expression>),
+    /// A binary operator applied to two expressions.
+    Binary(String, Box<Expression>, Box<Expression>),
+}
+
+impl fmt::Display for Expression {
+    /// Formats the expression fully parenthesized, so that it can be parsed back.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(number) if *number < 0 => write!(f, "(-{})", number.unsigned_abs()),
+            Self::Number(number) => write!(f, "{}", number),
+            Self::Current => write!(f, "{}", CURRENT_VALUE),
+            Self::Unary(operator, operand) => write!(f, "{}({})", operator, operand),
+            Self::Binary(operator, left, right) => write!(f, "({} {} {})", left, operator, right),
+        }
+    }
+}
+
+impl Expression {
+    /// Parses an infix expression.
+    ///
+    /// # Arguments:
+    ///
+    /// * `s` - The expression, e.g. `(x + 3) * 2`.
+    /// * `width` - The width every literal number must fit in.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::InvalidExpression` if the expression is malformed or nested
+    /// too deeply, or `CalculatorErrors::InvalidInteger` if a number does not fit in the width.
+    pub fn parse(s: &str, width: Width) -> Result<Self, CalculatorErrors> {
+        let mut tokens = tokenize(s)?.into_iter().peekable();
+        let expression = Self::parse_level(&mut tokens, 0, 0, width)?;
+
+        match tokens.next() {
+            Some(token) => Err(CalculatorErrors::InvalidExpression(token)),
+            None => Ok(expression),
+        }
+    }
+
+    /// Parses a sequence of left associative binary operations of the given precedence level,
+    /// at the given nesting depth.
+    fn parse_level(
+        tokens: &mut Tokens,
+        level: usize,
+        depth: usize,
+        width: Width,
+    ) -> Result<Self, CalculatorErrors> {
+        let operators = match PRECEDENCE.get(level) {
+            Some(operators) => operators,
+            None => return Self::parse_unary(tokens, depth, width),
+        };
+
+        let mut depth = depth;
+        let mut expression = Self::parse_level(tokens, level + 1, depth, width)?;
+        while let Some(operator) = tokens.next_if(|token| operators.contains(&token.as_str())) {
+            depth = Self::nest(depth)?;
+            let right = Self::parse_level(tokens, level + 1, depth, width)?;
+            expression = Self::Binary(operator, Box::new(expression), Box::new(right));
+        }
+
+        Ok(expression)
+    }
+
+    /// Parses an expression optionally preceded by a unary operator.
+    fn parse_unary(
+        tokens: &mut Tokens,
+        depth: usize,
+        width: Width,
+    ) -> Result<Self, CalculatorErrors> {
+        let operator = match tokens.peek().map(|token| token.as_str()) {
+            Some("-") | Some("neg") => "neg",
+            Some("abs") => "abs",
+            Some("not") => "not",
+            _ => return Self::parse_power(tokens, depth, width),
+        };
+
+        tokens.next();
+        let operand = Self::parse_unary(tokens, Self::nest(depth)?, width)?;
+        Ok(Self::Unary(operator.to_owned(), Box::new(operand)))
+    }
+
+    /// Parses a power, which is right associative and binds tighter than unary operators.
+    fn parse_power(
+        tokens: &mut Tokens,
+        depth: usize,
+        width: Width,
+    ) -> Result<Self, CalculatorErrors> {
+        let base = Self::parse_primary(tokens, depth, width)?;
+
+        match tokens.next_if(|token| token == "^") {
+            Some(operator) => {
+                let exponent = Self::parse_unary(tokens, Self::nest(depth)?, width)?;
+                Ok(Self::Binary(operator, Box::new(base), Box::new(exponent)))
+            }
+            None => Ok(base),
+        }
+    }
+
+    /// Parses a number, the current value placeholder or a parenthesized expression.
+    fn parse_primary(
+        tokens: &mut Tokens,
+        depth: usize,
+        width: Width,
+    ) -> Result<Self, CalculatorErrors> {
+        let token = match tokens.next() {
+            Some(token) => token,
+            None => {
+                return Err(CalculatorErrors::InvalidExpression(
+                    "end of line".to_owned(),
+                ));
+            }
+        };
+
+        if token == "(" {
+            let expression = Self::parse_level(tokens, 0, Self::nest(depth)?, width)?;
+            return match tokens.next() {
+                Some(closing) if closing == ")" => Ok(expression),
+                _ => Err(CalculatorErrors::InvalidExpression("missing )".to_owned())),
+            };
+        }
+
+        match token.as_str() {
+            CURRENT_VALUE => Ok(Self::Current),
+            number if number.starts_with(|c: char| c.is_ascii_digit()) => {
+                Ok(Self::Number(width.parse(number)?))
+            }
+            _ => Err(CalculatorErrors::InvalidExpression(token)),
+        }
+    }
+
+    /// Returns the depth of an expression nested in one at the given depth.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::InvalidExpression` if it exceeds `MAX_DEPTH`.
+    fn nest(depth: usize) -> Result<usize, CalculatorErrors> {
+        match depth + 1 {
+            depth if depth > MAX_DEPTH => Err(CalculatorErrors::InvalidExpression(
+                "nested too deeply".to_owned(),
+            )),
+            depth => Ok(depth),
+        }
+    }
+}
+
+/// Splits an expression into numbers, words, operators and parentheses.
+///
+/// # Errors:
+///
+/// Returns `CalculatorErrors::InvalidExpression` if a character is not valid.
+fn tokenize(s: &str) -> Result<Vec<String>, CalculatorErrors> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        let mut token = c.to_string();
+        if c.is_ascii_alphanumeric() {
+            while let Some(next) = chars.next_if(|next| next.is_ascii_alphanumeric()) {
+                token.push(next);
+            }
+        } else if (c == '<' || c == '>') && chars.next_if_eq(&c).is_some() {
+            token.push(c);
+        } else if !"+-*/%^&|()".contains(c) {
+            return Err(CalculatorErrors::InvalidExpression(token));
+        }
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+fn number(value: i128) -> Box<Expression> {
+    Box::new(Expression::Number(value))
+}
+
+#[test]
+fn test_parse_number() {
+    let expression = Expression::parse("42", Width::default()).unwrap();
+    assert_eq!(expression, Expression::Number(42));
+}
+
+#[test]
+fn test_parse_current_value() {
+    let expression = Expression::parse("x", Width::default()).unwrap();
+    assert_eq!(expression, Expression::Current);
+}
+
+#[test]
+fn test_parse_precedence() {
+    let expression = Expression::parse("1 + 2 * 3", Width::default()).unwrap();
+    let product = Expression::Binary("*".to_owned(), number(2), number(3));
+    assert_eq!(
+        expression,
+        Expression::Binary("+".to_owned(), number(1), Box::new(product))
+    );
+}
+
+#[test]
+fn test_parse_left_associative() {
+    let expression = Expression::parse("8 - 2 - 1", Width::default()).unwrap();
+    let difference = Expression::Binary("-".to_owned(), number(8), number(2));
+    assert_eq!(
+        expression,
+        Expression::Binary("-".to_owned(), Box::new(difference), number(1))
+    );
+}
+
+#[test]
+fn test_parse_power_right_associative() {
+    let expression = Expression::parse("2 ^ 3 ^ 2", Width::default()).unwrap();
+    let power = Expression::Binary("^".to_owned(), number(3), number(2));
+    assert_eq!(
+        expression,
+        Expression::Binary("^".to_owned(), number(2), Box::new(power))
+    );
+}
+
+#[test]
+fn test_parse_parentheses() {
+    let expression = Expression::parse("(x + 3) * 2", Width::default()).unwrap();
+    let sum = Expression::Binary("+".to_owned(), Box::new(Expression::Current), number(3));
+    assert_eq!(
+        expression,
+        Expression::Binary("*".to_owned(), Box::new(sum), number(2))
+    );
+}
+
+#[test]
+fn test_parse_unary() {
+    let expression = Expression::parse("-abs x", Width::default()).unwrap();
+    let abs = Expression::Unary("abs".to_owned(), Box::new(Expression::Current));
+    assert_eq!(
+        expression,
+        Expression::Unary("neg".to_owned(), Box::new(abs))
+    );
+}
+
+#[test]
+fn test_parse_shift_and_xor() {
+    let expression = Expression::parse("x<<2 xor 1", Width::default()).unwrap();
+    let shift = Expression::Binary("<<".to_owned(), Box::new(Expression::Current), number(2));
+    assert_eq!(
+        expression,
+        Expression::Binary("xor".to_owned(), Box::new(shift), number(1))
+    );
+}
+
+#[test]
+fn test_parse_invalid_expressions() {
+    for s in ["", "1 +", "(1 + 2", "1 2", "y + 1", "1 $ 2", "< 1"] {
+        match Expression::parse(s, Width::default()) {
+            Ok(_) => panic!("Should throw an error for {:?}", s),
+            Err(e) => match e {
+                CalculatorErrors::InvalidExpression(_) => (),
+                _ => panic!("Should throw InvalidExpression error, got: {:?}", e),
+            },
+        }
+    }
+}
+
+#[test]
+fn test_parse_deeply_nested_expressions() {
+    let parentheses = format!("{}1{}", "(".repeat(3000), ")".repeat(3000));
+    let unary = format!("{}1", "- ".repeat(3000));
+    let chain = vec!["1"; 3000].join(" + ");
+    let powers = vec!["1"; 3000].join(" ^ ");
+    for s in [parentheses, unary, chain, powers] {
+        match Expression::parse(&s, Width::default()) {
+            Ok(_) => panic!("Should throw an error"),
+            Err(e) => match e {
+                CalculatorErrors::InvalidExpression(_) => (),
+                _ => panic!("Should throw InvalidExpression error, got: {:?}", e),
+            },
+        }
+    }
+    let nested = format!("{}1{}", "(".repeat(60), ")".repeat(60));
+    assert_eq!(
+        Expression::parse(&nested, Width::default()).unwrap(),
+        Expression::Number(1)
+    );
+}
+
+#[test]
+fn test_parse_number_out_of_width() {
+    match Expression::parse("x + 256", Width::default()) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidInteger(_) => (),
+            _ => panic!("Should throw InvalidInteger error, got: {:?}", e),
+        },
+    }
+}
+
+#[test]
+fn test_display_expression() {
+    let expression = Expression::parse("-(x + 3) * 2 ^ 3", Width::default()).unwrap();
+    assert_eq!(expression.to_string(), "(neg((x + 3)) * (2 ^ 3))");
+}
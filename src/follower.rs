@@ -0,0 +1,180 @@
+use crate::{errors::CalculatorErrors, log_event::LogEvent, logger::Logger, registry::Registry};
+use std::{
+    cmp,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::TcpStream,
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+/// The time a read from the primary may block before checking if the replica was promoted.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// The time waited after failing to follow every primary, doubled after each failure.
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+/// The maximum time waited between two attempts to follow the primaries.
+const MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+/// The replication loop of a replica server.
+///
+/// The follower connects to the first reachable primary of its list and applies every
+/// change it forwards. If the connection drops, it tries the addresses again, in order,
+/// with an exponential backoff; a server of the list that is itself a replica rejects
+/// the connection, and a primary whose epoch is older than the replica's is skipped, since
+/// a replica was promoted after it.
+///
+/// The changes that fail to be applied are logged. The follower stops once the replica is
+/// promoted. Then it fences every server of its list with the new epoch, so the primary it
+/// replaces stops accepting changes.
+pub struct Follower {
+    registry: Arc<Registry>,
+    primaries: Vec<String>,
+    logger: Logger,
+}
+
+impl Follower {
+    /// Creates a follower of the given primaries.
+    ///
+    /// # Arguments:
+    ///
+    /// * `registry` - The registry of the replica.
+    /// * `primaries` - The addresses of the servers that may be the primary.
+    /// * `logger` - The logger of the server.
+    pub fn new(registry: Arc<Registry>, primaries: Vec<String>, logger: Logger) -> Self {
+        Self {
+            registry,
+            primaries,
+            logger,
+        }
+    }
+
+    /// Follows the primaries until the replica is promoted, and then fences them.
+    pub fn run(&self) {
+        let mut backoff = MIN_BACKOFF;
+        while self.registry.is_replica() {
+            for primary in &self.primaries {
+                if self.registry.is_replica() && self.follow(primary).is_ok() {
+                    backoff = MIN_BACKOFF;
+                }
+            }
+
+            if self.registry.is_replica() {
+                thread::sleep(backoff);
+                backoff = cmp::min(backoff * 2, MAX_BACKOFF);
+            }
+        }
+
+        if let Some(token) = self.registry.admin_token() {
+            for primary in &self.primaries {
+                let _ = self.fence(primary, token);
+            }
+        }
+    }
+
+    /// Sends the epoch of the promoted replica to a server that may be the primary, so it
+    /// stops accepting changes if it still takes itself as the primary.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::SocketFailure` if the server cannot be reached.
+    fn fence(&self, primary: &str, token: &str) -> Result<(), CalculatorErrors> {
+        let mut stream = match TcpStream::connect(primary) {
+            Ok(stream) => stream,
+            Err(_) => return Err(CalculatorErrors::SocketFailure),
+        };
+
+        let message = format!("FENCE {} {}\n", self.registry.epoch(), token);
+        let sent = stream.set_read_timeout(Some(POLL_INTERVAL)).is_ok()
+            && stream.write_all(message.as_bytes()).is_ok();
+        if !sent {
+            return Err(CalculatorErrors::SocketFailure);
+        }
+        // The answer is awaited, so the server processes the message before it is closed.
+        let _ = BufReader::new(stream).read_line(&mut String::new());
+        Ok(())
+    }
+
+    /// Follows a primary until the connection drops or the replica is promoted.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::SocketFailure` if the primary cannot be reached,
+    /// `CalculatorErrors::ReadOnlyReplica` if it rejects the replica, or
+    /// `CalculatorErrors::StaleEpoch` if its epoch is older than the replica's.
+    fn follow(&self, primary: &str) -> Result<(), CalculatorErrors> {
+        let mut stream = match TcpStream::connect(primary) {
+            Ok(stream) => stream,
+            Err(_) => return Err(CalculatorErrors::SocketFailure),
+        };
+
+        let connected = stream.set_read_timeout(Some(POLL_INTERVAL)).is_ok()
+            && stream.write_all(b"REPLICATE\n").is_ok();
+        if !connected {
+            return Err(CalculatorErrors::SocketFailure);
+        }
+
+        let mut reader = BufReader::new(stream);
+        let epoch = self.read_line(&mut reader)?.and_then(|line| {
+            let epoch = line.strip_prefix("EPOCH ")?;
+            epoch.parse().ok()
+        });
+        match epoch {
+            Some(epoch) => {
+                self.registry.follow_epoch(epoch)?;
+                self.receive(&mut reader)
+            }
+            None => Err(CalculatorErrors::ReadOnlyReplica),
+        }
+    }
+
+    /// Applies the changes received from the primary, grouping each `BEGIN` ... `COMMIT`
+    /// block so that transactions are applied as a unit.
+    fn receive(&self, reader: &mut BufReader<TcpStream>) -> Result<(), CalculatorErrors> {
+        let mut block = vec![];
+        while let Some(line) = self.read_line(reader)? {
+            let (name, message) = match line.split_once(' ') {
+                Some((name, message)) => (name.to_owned(), message.to_owned()),
+                None => return Err(CalculatorErrors::UnexpectedMessage(line)),
+            };
+
+            let in_transaction = !block.is_empty() || message == "BEGIN";
+            let complete = !in_transaction || message == "COMMIT";
+            block.push(message);
+
+            if complete {
+                if let Err(error) = self.registry.replicate(&name, &block) {
+                    let register = name;
+                    self.logger
+                        .log(LogEvent::ReplicationFailed { register, error });
+                }
+                block.clear();
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a line from the primary, waiting until one is complete.
+    ///
+    /// # Returns:
+    ///
+    /// The line without its terminator, or `None` if the replica was promoted.
+    fn read_line(
+        &self,
+        reader: &mut BufReader<TcpStream>,
+    ) -> Result<Option<String>, CalculatorErrors> {
+        let mut line = vec![];
+        while self.registry.is_replica() {
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => return Err(CalculatorErrors::ListeningFailure),
+                Ok(_) if line.ends_with(b"\n") => {
+                    let line = String::from_utf8_lossy(&line);
+                    return Ok(Some(line.trim_end().to_owned()));
+                }
+                Ok(_) => return Err(CalculatorErrors::ListeningFailure),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => (),
+                Err(_) => return Err(CalculatorErrors::ListeningFailure),
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,37 @@
+use std::fmt;
+
+/// A record of an operation applied to a register.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct HistoryEntry {
+    /// The position of the operation among those applied to the register, starting at 1.
+    pub sequence: u64,
+    /// The id of the client that sent the operation.
+    pub client: u64,
+    /// The operation, formatted as the message that produces it.
+    pub operation: String,
+    /// The value of the register after applying the operation.
+    pub value: i128,
+}
+
+impl fmt::Display for HistoryEntry {
+    /// Formats the entry as sent in the response to `HISTORY`:
+    /// `ENTRY <sequence> <client> <value> <operation>`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ENTRY {} {} {} {}",
+            self.sequence, self.client, self.value, self.operation
+        )
+    }
+}
+
+#[test]
+fn test_display_entry() {
+    let entry = HistoryEntry {
+        sequence: 3,
+        client: 1,
+        operation: "OP + 5".to_owned(),
+        value: 12,
+    };
+    assert_eq!(entry.to_string(), "ENTRY 3 1 12 OP + 5");
+}
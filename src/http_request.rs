@@ -0,0 +1,183 @@
+use crate::errors::CalculatorErrors;
+use std::io::BufRead;
+
+/// The maximum size of the body of a request, in bytes.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+/// The maximum number of header lines of a request.
+const MAX_HEADERS: usize = 100;
+
+/// A request received by the HTTP front-end of the server.
+#[derive(PartialEq, Eq, Debug)]
+pub struct HttpRequest {
+    /// The method, e.g. `GET`.
+    pub method: String,
+    /// The path, without the query string, e.g. `/value`.
+    pub path: String,
+    /// The parameters of the query string, decoded.
+    pub query: Vec<(String, String)>,
+    /// Whether the connection stays open after the response.
+    pub keep_alive: bool,
+    /// The body of the request.
+    pub body: String,
+}
+
+impl HttpRequest {
+    /// Reads a request from a connection.
+    ///
+    /// # Arguments:
+    ///
+    /// * `reader` - The reader of the connection.
+    ///
+    /// # Returns:
+    ///
+    /// The request, or `None` if the connection ended before a new request.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::UnexpectedMessage` if the request is not valid, or
+    /// `CalculatorErrors::ReadLineFailure` if it cannot be read.
+    pub fn read<R: BufRead>(reader: &mut R) -> Result<Option<Self>, CalculatorErrors> {
+        let line = match read_line(reader)? {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+
+        let (method, target, version) = match line.split(' ').collect::<Vec<_>>()[..] {
+            [method, target, version] if version.starts_with("HTTP/1.") => {
+                (method, target, version)
+            }
+            _ => return Err(CalculatorErrors::UnexpectedMessage(line)),
+        };
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let mut request = Self {
+            method: method.to_owned(),
+            path: path.to_owned(),
+            query: parse_query(query),
+            keep_alive: version == "HTTP/1.1",
+            body: String::new(),
+        };
+
+        let length = request.read_headers(reader)?;
+        let mut body = vec![0; length];
+        if reader.read_exact(&mut body).is_err() {
+            return Err(CalculatorErrors::ReadLineFailure);
+        }
+        request.body = String::from_utf8_lossy(&body).into_owned();
+        Ok(Some(request))
+    }
+
+    /// Returns the value of the parameter of the query string with the given name, if any.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        let mut params = self.query.iter();
+        params
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Reads the headers of the request, up to the empty line that ends them.
+    ///
+    /// # Returns:
+    ///
+    /// The length of the body.
+    fn read_headers<R: BufRead>(&mut self, reader: &mut R) -> Result<usize, CalculatorErrors> {
+        let mut length = 0;
+        for _ in 0..MAX_HEADERS {
+            let line = match read_line(reader)? {
+                Some(line) if line.is_empty() => return Ok(length),
+                Some(line) => line,
+                None => return Err(CalculatorErrors::ReadLineFailure),
+            };
+
+            let (name, value) = line.split_once(':').unwrap_or((&line, ""));
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                length = match value.parse() {
+                    Ok(length) if length <= MAX_BODY_SIZE => length,
+                    _ => return Err(CalculatorErrors::UnexpectedMessage(line)),
+                };
+            } else if name.eq_ignore_ascii_case("connection") {
+                self.keep_alive = !value.eq_ignore_ascii_case("close");
+            }
+        }
+        Err(CalculatorErrors::UnexpectedMessage("headers".to_owned()))
+    }
+}
+
+/// Reads a line ended by `\r\n` or `\n`, without the line ending, or `None` at the end of
+/// the connection.
+fn read_line<R: BufRead>(reader: &mut R) -> Result<Option<String>, CalculatorErrors> {
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) => Ok(None),
+        Ok(_) => Ok(Some(line.trim_end_matches(['\r', '\n']).to_owned())),
+        Err(_) => Err(CalculatorErrors::ReadLineFailure),
+    }
+}
+
+/// Parses a query string, e.g. `register=team_a&count=3`.
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    let params = query.split('&').filter(|param| !param.is_empty());
+    params
+        .map(|param| {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+/// Decodes a component of a query string, in which `+` is a space and `%XX` is the byte
+/// with the hexadecimal value XX.
+fn decode(component: &str) -> String {
+    let mut bytes = vec![];
+    let mut rest = component.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = tail.get(..2).and_then(|hex| {
+            let hex = std::str::from_utf8(hex).ok()?;
+            u8::from_str_radix(hex, 16).ok()
+        });
+        match (byte, escaped) {
+            (b'%', Some(escaped)) => {
+                bytes.push(escaped);
+                rest = &tail[2..];
+                continue;
+            }
+            (b'+', _) => bytes.push(b' '),
+            (byte, _) => bytes.push(byte),
+        }
+        rest = tail;
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[test]
+fn test_read_request() {
+    let message = "POST /op?register=team%20a&count=3 HTTP/1.1\r\n\
+                   Host: localhost\r\nContent-Length: 6\r\n\r\nOP + 5";
+    let request = HttpRequest::read(&mut message.as_bytes()).unwrap().unwrap();
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.path, "/op");
+    assert_eq!(request.param("register"), Some("team a"));
+    assert_eq!(request.param("count"), Some("3"));
+    assert!(request.keep_alive);
+    assert_eq!(request.body, "OP + 5");
+}
+
+#[test]
+fn test_read_request_connection_close() {
+    let message = "GET /value HTTP/1.1\r\nConnection: close\r\n\r\n";
+    let request = HttpRequest::read(&mut message.as_bytes()).unwrap().unwrap();
+    assert!(!request.keep_alive);
+    assert!(request.body.is_empty());
+    assert_eq!(HttpRequest::read(&mut "".as_bytes()).unwrap(), None);
+}
+
+#[test]
+fn test_read_invalid_request() {
+    match HttpRequest::read(&mut "OP + 5\n".as_bytes()) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::UnexpectedMessage(_) => (),
+            _ => panic!("Should throw UnexpectedMessage error, got: {:?}", e),
+        },
+    };
+}
@@ -0,0 +1,185 @@
+use crate::{
+    acceptor, action::Action, errors::CalculatorErrors, http_request::HttpRequest, json,
+    logger::Logger, operation::Operation, registry::Registry, response::Response, session::Session,
+    worker_pool::WorkerPool,
+};
+use std::{
+    io::{BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+};
+
+/// Serves the HTTP front-end of the server, handling the connections with a pool of
+/// workers, until the server shuts down. If every worker is busy, a connection is answered
+/// with a `503` status and closed.
+///
+/// Every request is processed by a new session, on the register given by the `register`
+/// parameter of the query string (`default` if none), and answered with the JSON protocol:
+///
+/// * `POST /op` - Applies the operation in the body, either as a JSON object or as text.
+/// * `GET /value` - Returns the value of the register.
+/// * `GET /history` - Returns the last `count` entries of the history of the register.
+///
+/// # Arguments:
+///
+/// * `listener` - The TCP listener used to accept incoming connections.
+/// * `registry` - The registry of named calculators shared by every client.
+/// * `logger` - The logger of the server.
+/// * `workers` - The number of threads that handle connections.
+/// * `queue` - The number of connections that may wait for a free worker.
+pub fn serve(
+    listener: TcpListener,
+    registry: Arc<Registry>,
+    logger: Logger,
+    workers: usize,
+    queue: usize,
+) {
+    let (client_registry, client_logger) = (Arc::clone(&registry), logger.clone());
+    let pool = WorkerPool::spawn(workers, queue, move |stream| {
+        handle_connection(stream, client_registry, client_logger)
+    });
+    acceptor::serve(listener, &registry, &logger, pool, reject);
+}
+
+/// Answers a connection that is rejected because every worker is busy.
+fn reject(stream: TcpStream) {
+    let (status, body) = answer(Response::Error(CalculatorErrors::ServerBusy));
+    let _ = write_response(&stream, status, &body, false);
+}
+
+/// Answers the requests received on a connection, until the client closes it or asks to.
+fn handle_connection(stream: TcpStream, registry: Arc<Registry>, logger: Logger) {
+    let mut reader = BufReader::new(&stream);
+    loop {
+        let (request, (status, body)) = match HttpRequest::read(&mut reader) {
+            Ok(Some(request)) => {
+                let answer = route(&request, &registry, &logger);
+                (Some(request), answer)
+            }
+            Ok(None) => return,
+            Err(e) => (None, answer(Response::Error(e))),
+        };
+
+        let keep_alive = request.is_some_and(|request| request.keep_alive);
+        if write_response(&stream, status, &body, keep_alive).is_err() || !keep_alive {
+            return;
+        }
+    }
+}
+
+/// Processes a request, returning the status and the body of the answer.
+fn route(request: &HttpRequest, registry: &Arc<Registry>, logger: &Logger) -> (u16, String) {
+    let path = request.path.as_str();
+    if !matches!(path, "/op" | "/value" | "/history") {
+        return fail(404, CalculatorErrors::UnexpectedMessage(path.to_owned()));
+    }
+    if !matches!(
+        (request.method.as_str(), path),
+        ("POST", "/op") | ("GET", "/value") | ("GET", "/history")
+    ) {
+        return fail(
+            405,
+            CalculatorErrors::UnexpectedMessage(request.method.to_owned()),
+        );
+    }
+
+    let mut session = match Session::new(Arc::clone(registry)) {
+        Ok(session) => session,
+        Err(e) => return answer(Response::Error(e)),
+    };
+    if let Some(name) = request.param("register")
+        && let Action::Respond(Response::Error(e)) = session.handle_line(&format!("USE {}", name))
+    {
+        return answer(Response::Error(e));
+    }
+    let response = dispatch(request, &mut session, registry);
+    let message = format!(
+        "{} {} {}",
+        request.method,
+        request.path,
+        request.body.trim()
+    );
+    logger.log_response(session.client(), message.trim_end(), &response);
+    answer(response)
+}
+
+/// Processes a request whose method and path are valid with the given session.
+fn dispatch(request: &HttpRequest, session: &mut Session, registry: &Registry) -> Response {
+    match request.path.as_str() {
+        "/op" => match parse_operation(&request.body, registry) {
+            Ok(op) => session.handle_operation(op),
+            Err(e) => Response::Error(e),
+        },
+        "/history" => {
+            let count = request.param("count").unwrap_or_default();
+            match session.handle_line(&format!("HISTORY {}", count)) {
+                Action::Respond(response) => response,
+                _ => Response::Error(CalculatorErrors::UnexpectedMessage(count.to_owned())),
+            }
+        }
+        _ => session.handle_operation(Operation::Get),
+    }
+}
+
+/// Parses the operation in the body of a request, as a JSON object if it starts with `{`,
+/// or as a text message otherwise.
+fn parse_operation(body: &str, registry: &Registry) -> Result<Operation, CalculatorErrors> {
+    let body = body.trim();
+    if body.starts_with('{') {
+        return json::decode_operation(body, registry.width());
+    }
+    Operation::parse(body, registry.width())
+}
+
+/// Returns the status and the body that answer a response.
+fn answer(response: Response) -> (u16, String) {
+    let status = match &response {
+        Response::Error(
+            CalculatorErrors::NotLeader(_)
+            | CalculatorErrors::ReadOnlyReplica
+            | CalculatorErrors::ServerBusy,
+        ) => 503,
+        Response::Error(
+            CalculatorErrors::LockFailure
+            | CalculatorErrors::StorageFailure
+            | CalculatorErrors::WritingFailure,
+        ) => 500,
+        Response::Error(CalculatorErrors::Unauthorized) => 403,
+        Response::Error(_) => 400,
+        _ => 200,
+    };
+    (status, json::encode_response(&response))
+}
+
+/// Returns the status and the body that answer an error with the given status.
+fn fail(status: u16, e: CalculatorErrors) -> (u16, String) {
+    (status, json::encode_response(&Response::Error(e)))
+}
+
+/// Writes an answer with a JSON body.
+fn write_response(
+    mut stream: &TcpStream,
+    status: u16,
+    body: &str,
+    keep_alive: bool,
+) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    let connection = if keep_alive { "keep-alive" } else { "close" };
+    let message = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: {}\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        connection,
+        body
+    );
+    stream.write_all(message.as_bytes())?;
+    stream.flush()
+}
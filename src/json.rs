@@ -0,0 +1,148 @@
+use crate::{
+    errors::CalculatorErrors, history_entry::HistoryEntry, json_value::JsonValue,
+    operation::Operation, response::Response, width::Width,
+};
+
+/// Decodes an operation from a line of the JSON protocol: an object with an `op` field,
+/// which is an operator, `GET` or `RESET`, and an `operand` field for binary operators.
+///
+/// # Arguments:
+///
+/// * `line` - The line received, e.g. `{"op": "+", "operand": 3}`.
+/// * `width` - The width the operand must fit in.
+///
+/// # Errors:
+///
+/// Returns `CalculatorErrors::InvalidJson` if the line is not a JSON object,
+/// `CalculatorErrors::ArgsLenFailure` if a field is missing or not expected, e.g. the operand
+/// of a binary operator,
+/// `CalculatorErrors::InvalidOperation` if the operator is not valid, or
+/// `CalculatorErrors::InvalidInteger` if the operand does not fit in the width.
+pub fn decode_operation(line: &str, width: Width) -> Result<Operation, CalculatorErrors> {
+    let (mut op, mut operand) = (None, None);
+    for (key, value) in JsonValue::parse_object(line)? {
+        match (key.as_str(), value) {
+            ("op", JsonValue::String(value)) => op = Some(value),
+            ("operand", JsonValue::Integer(value)) => operand = Some(value),
+            (key, _) => return Err(CalculatorErrors::UnexpectedMessage(key.to_owned())),
+        }
+    }
+
+    match (op.as_deref(), operand) {
+        (Some("GET"), None) => Ok(Operation::Get),
+        (Some("RESET"), None) => Ok(Operation::Reset),
+        (Some(op), None) if Operation::get_binary_op(op, 0).is_ok() => {
+            Err(CalculatorErrors::ArgsLenFailure)
+        }
+        (Some(op), None) => Operation::get_unary_op(op),
+        (Some(op), Some(operand)) if width.contains(operand) => {
+            Operation::get_binary_op(op, operand)
+        }
+        (Some(_), Some(operand)) => Err(CalculatorErrors::InvalidInteger(operand.to_string())),
+        (None, _) => Err(CalculatorErrors::ArgsLenFailure),
+    }
+}
+
+/// Encodes a response as a line of the JSON protocol, with a `status` field, and either a
+/// `value` field, an `entries` field with the history, or the `code` and `message` of the
+/// error.
+pub fn encode_response(response: &Response) -> String {
+    match response {
+        Response::Value(value) => format!("{{\"status\":\"ok\",\"value\":{}}}", value),
+        Response::History(entries) => {
+            let entries: Vec<String> = entries.iter().map(encode_entry).collect();
+            format!("{{\"status\":\"ok\",\"entries\":[{}]}}", entries.join(","))
+        }
+        Response::Error(e) => format!(
+            "{{\"status\":\"error\",\"code\":\"{}\",\"message\":{}}}",
+            e.code(),
+            JsonValue::String(e.reason())
+        ),
+        _ => "{\"status\":\"ok\"}".to_owned(),
+    }
+}
+
+/// Encodes an entry of the history as a JSON object.
+fn encode_entry(entry: &HistoryEntry) -> String {
+    format!(
+        "{{\"sequence\":{},\"client\":{},\"value\":{},\"operation\":{}}}",
+        entry.sequence,
+        entry.client,
+        entry.value,
+        JsonValue::String(entry.operation.to_owned())
+    )
+}
+
+#[test]
+fn test_decode_operation() {
+    let width = Width::default();
+    let op = decode_operation(r#"{"op": "+", "operand": 3}"#, width).unwrap();
+    assert_eq!(op, Operation::Add(3));
+    assert_eq!(
+        decode_operation(r#"{"op": "neg"}"#, width).unwrap(),
+        Operation::Neg
+    );
+    assert_eq!(
+        decode_operation(r#"{"op": "GET"}"#, width).unwrap(),
+        Operation::Get
+    );
+}
+
+#[test]
+fn test_decode_operation_errors() {
+    match decode_operation(r#"{"op": "+", "operand": 300}"#, Width::default()) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidInteger(_) => (),
+            _ => panic!("Should throw InvalidInteger error, got: {:?}", e),
+        },
+    };
+    match decode_operation(r#"{"operand": 3}"#, Width::default()) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::ArgsLenFailure => (),
+            _ => panic!("Should throw an args length error, got: {:?}", e),
+        },
+    };
+    match decode_operation(r#"{"op": "+"}"#, Width::default()) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::ArgsLenFailure => (),
+            _ => panic!("Should throw an args length error, got: {:?}", e),
+        },
+    };
+    match decode_operation(r#"{"op": "$", "operand": 3}"#, Width::default()) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidOperation(_) => (),
+            _ => panic!("Should throw InvalidOperation error, got: {:?}", e),
+        },
+    };
+}
+
+#[test]
+fn test_encode_response() {
+    assert_eq!(encode_response(&Response::Ok), r#"{"status":"ok"}"#);
+    assert_eq!(
+        encode_response(&Response::Value(7)),
+        r#"{"status":"ok","value":7}"#
+    );
+    assert_eq!(
+        encode_response(&Response::Error(CalculatorErrors::DivisionByZero)),
+        r#"{"status":"error","code":"division_by_zero","message":"division by zero"}"#
+    );
+}
+
+#[test]
+fn test_encode_history() {
+    let entry = HistoryEntry {
+        sequence: 1,
+        client: 2,
+        operation: "OP + 5".to_owned(),
+        value: 5,
+    };
+    assert_eq!(
+        encode_response(&Response::History(vec![entry])),
+        r#"{"status":"ok","entries":[{"sequence":1,"client":2,"value":5,"operation":"OP + 5"}]}"#
+    );
+}
@@ -0,0 +1,189 @@
+use crate::errors::CalculatorErrors;
+use std::{fmt, iter::Peekable, str::Chars};
+
+/// A value of a field of a JSON object, as used by the JSON protocol: only strings and
+/// integers are supported.
+#[derive(PartialEq, Eq, Debug)]
+pub enum JsonValue {
+    /// A string, unescaped.
+    String(String),
+    /// An integer.
+    Integer(i128),
+}
+
+impl fmt::Display for JsonValue {
+    /// Formats the value as JSON, escaping strings.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Integer(value) => write!(f, "{}", value),
+            Self::String(value) => {
+                write!(f, "\"")?;
+                for c in value.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+        }
+    }
+}
+
+impl JsonValue {
+    /// Parses a JSON object whose values are strings or integers.
+    ///
+    /// # Arguments:
+    ///
+    /// * `s` - The object, e.g. `{"op": "+", "operand": 3}`.
+    ///
+    /// # Returns:
+    ///
+    /// The fields of the object, in order.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::InvalidJson` if the string is not such an object, or
+    /// `CalculatorErrors::InvalidInteger` if an integer does not fit in 128 bits.
+    pub fn parse_object(s: &str) -> Result<Vec<(String, Self)>, CalculatorErrors> {
+        let mut chars = s.trim().chars().peekable();
+        expect(&mut chars, '{')?;
+        let mut fields = vec![];
+        if skip_whitespace(&mut chars) == Some('}') {
+            chars.next();
+        } else {
+            loop {
+                skip_whitespace(&mut chars);
+                let key = parse_string(&mut chars)?;
+                skip_whitespace(&mut chars);
+                expect(&mut chars, ':')?;
+                skip_whitespace(&mut chars);
+                fields.push((key, parse_value(&mut chars)?));
+                skip_whitespace(&mut chars);
+                match chars.next() {
+                    Some(',') => (),
+                    Some('}') => break,
+                    _ => return Err(CalculatorErrors::InvalidJson),
+                }
+            }
+        }
+
+        match chars.next() {
+            Some(_) => Err(CalculatorErrors::InvalidJson),
+            None => Ok(fields),
+        }
+    }
+}
+
+/// Skips whitespace, returning the next character, if any.
+fn skip_whitespace(chars: &mut Peekable<Chars>) -> Option<char> {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    chars.peek().copied()
+}
+
+/// Consumes the given character.
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> Result<(), CalculatorErrors> {
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        _ => Err(CalculatorErrors::InvalidJson),
+    }
+}
+
+/// Parses a string or an integer.
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<JsonValue, CalculatorErrors> {
+    if chars.peek() == Some(&'"') {
+        return Ok(JsonValue::String(parse_string(chars)?));
+    }
+
+    let mut number = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || *c == '-') {
+        number.push(c);
+    }
+    match number.parse() {
+        Ok(number) => Ok(JsonValue::Integer(number)),
+        Err(_) if number.is_empty() => Err(CalculatorErrors::InvalidJson),
+        Err(_) => Err(CalculatorErrors::InvalidInteger(number)),
+    }
+}
+
+/// Parses a string, unescaping it.
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, CalculatorErrors> {
+    expect(chars, '"')?;
+    let mut string = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(string),
+            Some('\\') => string.push(parse_escape(chars)?),
+            Some(c) if !c.is_control() => string.push(c),
+            _ => return Err(CalculatorErrors::InvalidJson),
+        }
+    }
+}
+
+/// Parses the character escaped after a backslash.
+fn parse_escape(chars: &mut Peekable<Chars>) -> Result<char, CalculatorErrors> {
+    match chars.next() {
+        Some(c @ ('"' | '\\' | '/')) => Ok(c),
+        Some('n') => Ok('\n'),
+        Some('t') => Ok('\t'),
+        Some('r') => Ok('\r'),
+        Some('b') => Ok('\u{8}'),
+        Some('f') => Ok('\u{c}'),
+        Some('u') => {
+            let code: String = chars.take(4).collect();
+            match u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                Some(c) if code.len() == 4 => Ok(c),
+                _ => Err(CalculatorErrors::InvalidJson),
+            }
+        }
+        _ => Err(CalculatorErrors::InvalidJson),
+    }
+}
+
+#[test]
+fn test_parse_object() {
+    let fields = JsonValue::parse_object(r#" { "op" : "+", "operand": -3 } "#).unwrap();
+    assert_eq!(
+        fields,
+        vec![
+            ("op".to_owned(), JsonValue::String("+".to_owned())),
+            ("operand".to_owned(), JsonValue::Integer(-3))
+        ]
+    );
+    assert_eq!(JsonValue::parse_object("{}").unwrap(), vec![]);
+}
+
+#[test]
+fn test_parse_object_escapes() {
+    let fields = JsonValue::parse_object(r#"{"op":"a\"b\\c\u0041"}"#).unwrap();
+    assert_eq!(fields[0].1, JsonValue::String("a\"b\\cA".to_owned()));
+}
+
+#[test]
+fn test_parse_object_errors() {
+    for invalid in [
+        r#"{"op": "+""#,
+        r#"{"op" "+"}"#,
+        r#"{"op": true}"#,
+        "{} x",
+        "[]",
+    ] {
+        match JsonValue::parse_object(invalid) {
+            Ok(_) => panic!("Should throw an error"),
+            Err(e) => match e {
+                CalculatorErrors::InvalidJson => (),
+                _ => panic!("Should throw InvalidJson error, got: {:?}", e),
+            },
+        };
+    }
+}
+
+#[test]
+fn test_display_escapes_strings() {
+    let value = JsonValue::String("say \"hi\"\n".to_owned());
+    assert_eq!(value.to_string(), r#""say \"hi\"\n""#);
+    assert_eq!(JsonValue::Integer(-5).to_string(), "-5");
+}
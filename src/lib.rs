@@ -1,4 +1,46 @@
+pub mod acceptor;
+pub mod action;
+pub mod atomic_calculator;
+pub mod backend;
+pub mod binary;
 pub mod calculator;
+pub mod connection;
 pub mod errors;
+#[cfg(unix)]
+pub mod event_client;
+#[cfg(unix)]
+pub mod event_loop;
+pub mod expression;
+pub mod follower;
+pub mod history_entry;
+pub mod http_request;
+pub mod http_server;
+pub mod json;
+pub mod json_value;
+pub mod log_entry;
+pub mod log_event;
+pub mod log_level;
+pub mod logger;
 pub mod operation;
+pub mod overflow;
+#[cfg(unix)]
+pub mod poll_fd;
+pub mod raft;
+pub mod raft_peer;
+pub mod raft_role;
+pub mod raft_state;
+pub mod register;
+pub mod registry;
+pub mod replica_set;
+pub mod request;
+pub mod request_cache;
 pub mod response;
+pub mod server_config;
+pub mod server_mode;
+pub mod session;
+pub mod session_table;
+#[cfg(unix)]
+pub mod signal;
+pub mod storage;
+pub mod width;
+pub mod worker_pool;
@@ -0,0 +1,112 @@
+use crate::errors::CalculatorErrors;
+use std::{fmt, str::FromStr};
+
+/// The separator of the messages of an entry.
+const MESSAGE_SEPARATOR: char = ';';
+
+/// An entry of the replicated log of a Raft cluster.
+///
+/// Each entry carries the protocol messages of a change to a register: a single message,
+/// a complete `BEGIN` ... `COMMIT` block, or a `BATCH <count>` message followed by the
+/// operations of a batch. An entry without messages is a no-op, added
+/// by every new leader to commit the entries of the previous terms.
+///
+/// An entry is sent as `<term> <client> <register> <message>[;<message>]...`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LogEntry {
+    /// The term of the leader that created the entry.
+    pub term: u64,
+    /// The id of the client that sent the change.
+    pub client: u64,
+    /// The name of the register the change is applied to.
+    pub register: String,
+    /// The protocol messages that reproduce the change.
+    pub messages: Vec<String>,
+}
+
+impl LogEntry {
+    /// Creates a no-op entry for the given term.
+    pub fn noop(term: u64) -> Self {
+        Self {
+            term,
+            client: 0,
+            register: "-".to_owned(),
+            messages: vec![],
+        }
+    }
+
+    /// Returns whether the entry is a no-op.
+    pub fn is_noop(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let messages = self.messages.join(&MESSAGE_SEPARATOR.to_string());
+        write!(
+            f,
+            "{} {} {} {}",
+            self.term, self.client, self.register, messages
+        )
+    }
+}
+
+impl FromStr for LogEntry {
+    type Err = CalculatorErrors;
+
+    /// Parses an entry received from the leader.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::UnexpectedMessage` if the entry is malformed.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.splitn(4, ' ').collect();
+        match fields[..] {
+            [term, client, register, messages] => match (term.parse(), client.parse()) {
+                (Ok(term), Ok(client)) => Ok(Self {
+                    term,
+                    client,
+                    register: register.to_owned(),
+                    messages: messages
+                        .split(MESSAGE_SEPARATOR)
+                        .filter(|message| !message.is_empty())
+                        .map(|message| message.to_owned())
+                        .collect(),
+                }),
+                _ => Err(CalculatorErrors::UnexpectedMessage(s.to_owned())),
+            },
+            _ => Err(CalculatorErrors::UnexpectedMessage(s.to_owned())),
+        }
+    }
+}
+
+#[test]
+fn test_entry_round_trip() {
+    let entry = LogEntry {
+        term: 3,
+        client: 7,
+        register: "a".to_owned(),
+        messages: vec!["BEGIN".to_owned(), "OP + 1".to_owned(), "COMMIT".to_owned()],
+    };
+    assert_eq!(entry.to_string(), "3 7 a BEGIN;OP + 1;COMMIT");
+    assert_eq!(entry.to_string().parse::<LogEntry>().unwrap(), entry);
+}
+
+#[test]
+fn test_noop_round_trip() {
+    let entry: LogEntry = LogEntry::noop(2).to_string().parse().unwrap();
+    assert!(entry.is_noop());
+    assert_eq!(entry.term, 2);
+}
+
+#[test]
+fn test_parse_malformed_entry() {
+    match "3 a".parse::<LogEntry>() {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::UnexpectedMessage(_) => (),
+            _ => panic!("Should throw UnexpectedMessage error, got: {:?}", e),
+        },
+    }
+}
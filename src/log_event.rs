@@ -0,0 +1,122 @@
+use crate::{errors::CalculatorErrors, log_level::LogLevel};
+use std::fmt;
+
+/// An event of the server, sent to the logger thread.
+#[derive(Debug)]
+pub enum LogEvent {
+    /// A client connected from the given address.
+    ConnectionOpened { client: u64, peer: String },
+    /// A client closed its connection.
+    ConnectionClosed { client: u64 },
+    /// A message of a client was processed successfully.
+    MessageApplied { client: u64, message: String },
+    /// A message of a client was answered with an error.
+    ErrorReturned {
+        client: u64,
+        message: String,
+        error: CalculatorErrors,
+    },
+    /// The server failed, regardless of any client.
+    ServerFailure(CalculatorErrors),
+    /// A record recovered from the storage could not be applied.
+    RecordFailed {
+        register: String,
+        message: String,
+        error: CalculatorErrors,
+    },
+    /// A change received from the primary could not be applied or persisted by the replica.
+    ReplicationFailed {
+        register: String,
+        error: CalculatorErrors,
+    },
+    /// A replica was dropped, since the changes could not be sent to it.
+    ReplicaDropped(CalculatorErrors),
+}
+
+impl fmt::Display for LogEvent {
+    /// Formats the event as it appears in the log file.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ConnectionOpened { client, peer } => {
+                write!(f, "client {} connected from {}", client, peer)
+            }
+            Self::ConnectionClosed { client } => write!(f, "client {} disconnected", client),
+            Self::MessageApplied { client, message } => write!(f, "client {}: {}", client, message),
+            Self::ErrorReturned {
+                client,
+                message,
+                error,
+            } => write!(
+                f,
+                "client {}: {} -> {}",
+                client,
+                message,
+                error.get_message()
+            ),
+            Self::ServerFailure(error) => write!(f, "{}", error.get_message()),
+            Self::RecordFailed {
+                register,
+                message,
+                error,
+            } => write!(
+                f,
+                "record {} {} -> {}",
+                register,
+                message,
+                error.get_message()
+            ),
+            Self::ReplicationFailed { register, error } => {
+                write!(
+                    f,
+                    "replicated change to {} -> {}",
+                    register,
+                    error.get_message()
+                )
+            }
+            Self::ReplicaDropped(error) => write!(f, "replica dropped: {}", error.get_message()),
+        }
+    }
+}
+
+impl LogEvent {
+    /// Returns the level of the event.
+    pub fn level(&self) -> LogLevel {
+        match self {
+            Self::ConnectionOpened { .. } | Self::ConnectionClosed { .. } => LogLevel::Info,
+            Self::MessageApplied { .. } => LogLevel::Debug,
+            Self::ErrorReturned { .. }
+            | Self::ServerFailure(_)
+            | Self::RecordFailed { .. }
+            | Self::ReplicationFailed { .. }
+            | Self::ReplicaDropped(_) => LogLevel::Error,
+        }
+    }
+}
+
+#[test]
+fn test_display_event() {
+    let event = LogEvent::ErrorReturned {
+        client: 3,
+        message: "OP / 0".to_owned(),
+        error: CalculatorErrors::DivisionByZero,
+    };
+    assert_eq!(
+        event.to_string(),
+        "client 3: OP / 0 -> ERROR \"division by zero\""
+    );
+    assert_eq!(event.level(), LogLevel::Error);
+}
+
+#[test]
+fn test_display_record_failed() {
+    let event = LogEvent::RecordFailed {
+        register: "a".to_owned(),
+        message: "OP / 0".to_owned(),
+        error: CalculatorErrors::DivisionByZero,
+    };
+    assert_eq!(
+        event.to_string(),
+        "record a OP / 0 -> ERROR \"division by zero\""
+    );
+    assert_eq!(event.level(), LogLevel::Error);
+}
@@ -0,0 +1,57 @@
+use crate::errors::CalculatorErrors;
+use std::{fmt, str::FromStr};
+
+/// The severity of a log event. A logger configured with a level writes the events of that
+/// level and of the more severe ones.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum LogLevel {
+    /// Errors returned to clients, and failures of the server.
+    Error,
+    /// Connections opened and closed.
+    #[default]
+    Info,
+    /// Every message applied.
+    Debug,
+}
+
+impl FromStr for LogLevel {
+    type Err = CalculatorErrors;
+
+    /// Parses a level: `error`, `info` or `debug`.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::InvalidArgument` if the level is unknown.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "info" => Ok(Self::Info),
+            "debug" => Ok(Self::Debug),
+            _ => Err(CalculatorErrors::InvalidArgument(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    /// Formats the level as it appears in the log file.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "ERROR"),
+            Self::Info => write!(f, "INFO"),
+            Self::Debug => write!(f, "DEBUG"),
+        }
+    }
+}
+
+#[test]
+fn test_parse_level() {
+    assert_eq!("debug".parse::<LogLevel>().unwrap(), LogLevel::Debug);
+    assert!(LogLevel::Error < LogLevel::Info);
+    match "verbose".parse::<LogLevel>() {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidArgument(_) => (),
+            _ => panic!("Should throw InvalidArgument error, got: {:?}", e),
+        },
+    };
+}
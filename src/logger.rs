@@ -0,0 +1,232 @@
+use crate::{
+    action::Action, errors::CalculatorErrors, log_event::LogEvent, log_level::LogLevel,
+    response::Response,
+};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The size a log file grows to before it is rotated, unless configured otherwise.
+pub const DEFAULT_LOG_MAX_SIZE: usize = 1024 * 1024;
+/// The number of rotated log files kept, as `<path>.1` (the newest) to `<path>.3`.
+const ROTATIONS: usize = 3;
+
+/// A handle to the logger thread, which receives the events of the server over a channel
+/// and writes them to the log file, one per line, with their time and level. Every thread
+/// of the server logs through its own copy of the handle, so logging never blocks on the
+/// file.
+///
+/// The default logger is disabled, and discards every event.
+#[derive(Clone, Debug, Default)]
+pub struct Logger {
+    sender: Option<Sender<(SystemTime, LogEvent)>>,
+    level: LogLevel,
+}
+
+impl Logger {
+    /// Spawns the logger thread, which ends once every copy of the handle is dropped.
+    ///
+    /// # Arguments:
+    ///
+    /// * `path` - The path of the log file, which is appended to if it exists.
+    /// * `level` - The least severe level of the events written.
+    /// * `max_size` - The size in bytes the log file grows to before it is rotated.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::FileOpenFailure` if the log file cannot be opened.
+    pub fn spawn(
+        path: &str,
+        level: LogLevel,
+        max_size: usize,
+    ) -> Result<(Self, JoinHandle<()>), CalculatorErrors> {
+        let file = open(path)?;
+        let (sender, receiver) = mpsc::channel();
+        let path = path.to_owned();
+        let handle = thread::spawn(move || write_events(receiver, &path, file, max_size));
+        let logger = Self {
+            sender: Some(sender),
+            level,
+        };
+        Ok((logger, handle))
+    }
+
+    /// Returns whether the events of the given level are written.
+    pub fn enabled(&self, level: LogLevel) -> bool {
+        self.sender.is_some() && level <= self.level
+    }
+
+    /// Sends an event to the logger thread, if its level is written.
+    pub fn log(&self, event: LogEvent) {
+        if event.level() <= self.level
+            && let Some(sender) = &self.sender
+        {
+            let _ = sender.send((SystemTime::now(), event));
+        }
+    }
+
+    /// Reports a failure of the server, printing it and logging it.
+    pub fn report(&self, e: CalculatorErrors) {
+        Response::Error(e.clone()).eprint();
+        self.log(LogEvent::ServerFailure(e));
+    }
+
+    /// Logs the processing of a message of a client: as an error if it failed, or as a
+    /// message applied otherwise.
+    ///
+    /// # Arguments:
+    ///
+    /// * `client` - The id of the client.
+    /// * `message` - The message received from the client.
+    /// * `response` - The response to the message.
+    pub fn log_response(&self, client: u64, message: &str, response: &Response) {
+        match response {
+            Response::Error(e) => self.log_error(client, message, e),
+            _ => self.log_applied(client, message),
+        }
+    }
+
+    /// Logs the processing of a message of the text protocol: as its response if it is
+    /// answered with one, or as a message applied otherwise.
+    ///
+    /// # Arguments:
+    ///
+    /// * `client` - The id of the client.
+    /// * `message` - The message received from the client.
+    /// * `action` - The action the message results in.
+    pub fn log_action(&self, client: u64, message: &str, action: &Action) {
+        match action {
+            Action::Respond(response) => self.log_response(client, message, response),
+            _ => self.log_applied(client, message),
+        }
+    }
+
+    /// Logs an error returned to a client.
+    fn log_error(&self, client: u64, message: &str, e: &CalculatorErrors) {
+        if self.enabled(LogLevel::Error) {
+            self.log(LogEvent::ErrorReturned {
+                client,
+                message: message.to_owned(),
+                error: e.clone(),
+            });
+        }
+    }
+
+    /// Logs a message of a client applied without errors.
+    fn log_applied(&self, client: u64, message: &str) {
+        if self.enabled(LogLevel::Debug) {
+            self.log(LogEvent::MessageApplied {
+                client,
+                message: message.to_owned(),
+            });
+        }
+    }
+}
+
+/// Writes the events received until the channel is closed, rotating the log file when it
+/// reaches its maximum size.
+fn write_events(
+    receiver: Receiver<(SystemTime, LogEvent)>,
+    path: &str,
+    mut file: File,
+    max_size: usize,
+) {
+    let mut size = file
+        .metadata()
+        .map_or(0, |metadata| metadata.len() as usize);
+    for (time, event) in receiver {
+        let line = format!("{} {} {}\n", timestamp(time), event.level(), event);
+        if file.write_all(line.as_bytes()).is_err() {
+            Response::Error(CalculatorErrors::WritingFailure).eprint();
+        }
+
+        size += line.len();
+        if size >= max_size {
+            match rotate(path) {
+                Ok(rotated) => (file, size) = (rotated, 0),
+                Err(e) => Response::Error(e).eprint(),
+            }
+        }
+    }
+}
+
+/// Renames the log file to `<path>.1`, shifting the older rotated files, and opens a new
+/// log file. The oldest rotated file is overwritten.
+fn rotate(path: &str) -> Result<File, CalculatorErrors> {
+    for index in (1..ROTATIONS).rev() {
+        let _ = fs::rename(
+            format!("{}.{}", path, index),
+            format!("{}.{}", path, index + 1),
+        );
+    }
+    if fs::rename(path, format!("{}.1", path)).is_err() {
+        return Err(CalculatorErrors::WritingFailure);
+    }
+    open(path)
+}
+
+/// Opens a log file for appending, creating it if it does not exist.
+fn open(path: &str) -> Result<File, CalculatorErrors> {
+    match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(file) => Ok(file),
+        Err(_) => Err(CalculatorErrors::FileOpenFailure),
+    }
+}
+
+/// Formats a time as the seconds since the Unix epoch, with milliseconds.
+fn timestamp(time: SystemTime) -> String {
+    let elapsed = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("{}.{:03}", elapsed.as_secs(), elapsed.subsec_millis())
+}
+
+#[cfg(test)]
+fn test_path(name: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("tpi_logger_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let _ = fs::create_dir_all(&dir);
+    dir.join("server.log").to_string_lossy().into_owned()
+}
+
+#[test]
+fn test_logger_filters_by_level() {
+    let path = test_path("level");
+    let (logger, handle) = Logger::spawn(&path, LogLevel::Info, DEFAULT_LOG_MAX_SIZE).unwrap();
+    logger.log(LogEvent::ConnectionOpened {
+        client: 1,
+        peer: "127.0.0.1:5000".to_owned(),
+    });
+    logger.log(LogEvent::MessageApplied {
+        client: 1,
+        message: "OP + 1".to_owned(),
+    });
+    assert!(!logger.enabled(LogLevel::Debug));
+    {
+        let _closed = logger;
+    }
+    handle.join().unwrap();
+
+    let log = fs::read_to_string(&path).unwrap();
+    assert_eq!(log.lines().count(), 1);
+    assert!(log.contains(" INFO client 1 connected from 127.0.0.1:5000"));
+}
+
+#[test]
+fn test_logger_rotates_file() {
+    let path = test_path("rotate");
+    let (logger, handle) = Logger::spawn(&path, LogLevel::Debug, 40).unwrap();
+    for client in 0..6 {
+        logger.log(LogEvent::ConnectionClosed { client });
+    }
+    {
+        let _closed = logger;
+    }
+    handle.join().unwrap();
+
+    assert!(fs::metadata(format!("{}.1", path)).is_ok());
+    assert!(fs::metadata(format!("{}.2", path)).is_ok());
+    assert!(fs::metadata(format!("{}.4", path)).is_err());
+}
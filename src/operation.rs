@@ -1,19 +1,48 @@
-use crate::errors::CalculatorErrors;
-use std::str::FromStr;
+use crate::{errors::CalculatorErrors, expression::Expression, width::Width};
+use std::{fmt, str::FromStr};
 
 /// An enum representing the possible operations that can be applied to the calculator.
 ///
 /// Each variant corresponds to a specific arithmetic operation or a request to retrieve the current value.
+/// Operands are stored in an `i128`, and are checked against the calculator's `Width` when parsed.
 #[derive(PartialEq, Eq, Debug)]
 pub enum Operation {
     /// Adds the given value to the calculator's value.
-    Add(u8),
+    Add(i128),
     /// Subtracts the given value from the calculator's value.
-    Sub(u8),
+    Sub(i128),
     /// Multiplies the calculator's value by the given value.
-    Mul(u8),
+    Mul(i128),
     /// Divides the calculator's value by the given value.
-    Div(u8),
+    Div(i128),
+    /// Replaces the calculator's value by the remainder of its division by the given value.
+    Mod(i128),
+    /// Raises the calculator's value to the power of the given value.
+    Pow(i128),
+    /// Applies a bitwise AND between the calculator's value and the given value.
+    And(i128),
+    /// Applies a bitwise OR between the calculator's value and the given value.
+    Or(i128),
+    /// Applies a bitwise XOR between the calculator's value and the given value.
+    Xor(i128),
+    /// Shifts the calculator's value to the left by the given number of bits.
+    Shl(i128),
+    /// Shifts the calculator's value to the right by the given number of bits.
+    Shr(i128),
+    /// Replaces the calculator's value by the given value.
+    Set(i128),
+    /// Replaces the calculator's value by zero.
+    Reset,
+    /// Negates the calculator's value.
+    Neg,
+    /// Replaces the calculator's value by its absolute value.
+    Abs,
+    /// Inverts every bit of the calculator's value.
+    Not,
+    /// Replaces the calculator's value by the result of evaluating the given expression.
+    Eval(Expression),
+    /// Applies the given operation only if the calculator's value equals the expected one.
+    IfEquals(i128, Box<Operation>),
     /// Retrieves the calculator's current value.
     Get,
 }
@@ -21,52 +50,153 @@ pub enum Operation {
 impl FromStr for Operation {
     type Err = CalculatorErrors;
 
+    /// Parses a string into an `Operation`, with operands of the default width (u8).
+    ///
+    /// See `Operation::parse`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, Width::default())
+    }
+}
+
+impl fmt::Display for Operation {
+    /// Formats the operation as the message that produces it, so that it can be parsed back.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Get => write!(f, "GET"),
+            Self::Reset => write!(f, "RESET"),
+            Self::Neg => write!(f, "OP neg"),
+            Self::Abs => write!(f, "OP abs"),
+            Self::Not => write!(f, "OP not"),
+            Self::Eval(expression) => write!(f, "EVAL {}", expression),
+            Self::IfEquals(expected, op) => write!(f, "{} IF {}", op, expected),
+            op => match op.get_binary_operator() {
+                Some((operator, operand)) => write!(f, "OP {} {}", operator, operand),
+                None => write!(f, "{:?}", op),
+            },
+        }
+    }
+}
+
+impl Operation {
     /// Parses a string into an `Operation`.
     ///
     /// # Arguments:
     ///
-    /// * `s` - A string slice representing the operation, in the format `<operator> <operand>`.
+    /// * `s` - A string slice representing the operation, in the format `OP <operator> <operand>`,
+    ///   `OP <unary operator>` (both optionally followed by `IF <expected>`), `CAS <expected> <new>`,
+    ///   `EVAL <expression>`, `RESET` or `GET`.
+    /// * `width` - The width the operand must fit in.
     ///
     /// # Errors:
     ///
-    /// Returns `CalculatorErrors::ParseFailure` if the string is not in the correct format,
-    /// or `CalculatorErrors::InvalidOperation` if the operator is not valid.
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+    /// Returns `CalculatorErrors::ArgsLenFailure` if the string is not in the correct format,
+    /// `CalculatorErrors::InvalidOperation` if the operator is not valid, or
+    /// `CalculatorErrors::InvalidInteger` if the operand does not fit in the width.
+    pub fn parse(s: &str, width: Width) -> Result<Self, CalculatorErrors> {
         // Split the string into tokens separated by whitespace.
         let tokens: Vec<&str> = s.split_whitespace().collect();
 
-        match tokens[0] {
-            "GET" => {
-                if tokens.len() != 1 {
-                    return Err(CalculatorErrors::ArgsLenFailure);
-                }
-                Ok(Self::Get)
-            }
-            "OP" => {
-                if tokens.len() != 3 {
-                    return Err(CalculatorErrors::ArgsLenFailure);
+        match tokens.first().copied().unwrap_or_default() {
+            "GET" => Self::get_without_args(&tokens, Self::Get),
+            "RESET" => Self::get_without_args(&tokens, Self::Reset),
+            "OP" => Self::get_op_args(&tokens[1..], width),
+            "CAS" => match tokens[1..] {
+                [expected, new] => {
+                    Self::get_conditional(expected, Self::get_op("=", new, width)?, width)
                 }
+                _ => Err(CalculatorErrors::ArgsLenFailure),
+            },
+            "EVAL" => Self::get_eval(s, width),
+            message => Err(CalculatorErrors::UnexpectedMessage(message.to_owned())),
+        }
+    }
+
+    /// Parses the expression following `EVAL`.
+    fn get_eval(s: &str, width: Width) -> Result<Self, CalculatorErrors> {
+        match s.trim_start().strip_prefix("EVAL") {
+            Some(expression) => Ok(Self::Eval(Expression::parse(expression, width)?)),
+            None => Err(CalculatorErrors::UnexpectedMessage(s.to_owned())),
+        }
+    }
 
-                Ok(Self::get_op(tokens[1], tokens[2])?)
+    /// Returns the given operation if the message has no arguments.
+    fn get_without_args(tokens: &[&str], op: Self) -> Result<Self, CalculatorErrors> {
+        if tokens.len() != 1 {
+            return Err(CalculatorErrors::ArgsLenFailure);
+        }
+        Ok(op)
+    }
+
+    /// Parses the arguments of an `OP` message.
+    fn get_op_args(args: &[&str], width: Width) -> Result<Self, CalculatorErrors> {
+        match *args {
+            [operation] => Self::get_unary_op(operation),
+            [operation, operand] => Self::get_op(operation, operand, width),
+            [operation, "IF", expected] => {
+                Self::get_conditional(expected, Self::get_unary_op(operation)?, width)
             }
-            message => Err(CalculatorErrors::UnexpectedMessage(message.to_owned())),
+            [operation, operand, "IF", expected] => {
+                Self::get_conditional(expected, Self::get_op(operation, operand, width)?, width)
+            }
+            _ => Err(CalculatorErrors::ArgsLenFailure),
         }
     }
-}
 
-impl Operation {
-    fn get_op(operation: &str, operand: &str) -> Result<Self, CalculatorErrors> {
-        // Parse the operand into an u8.
-        let operand: u8 = match operand.parse() {
-            Ok(operand) => operand,
-            Err(_) => return Err(CalculatorErrors::InvalidInteger(operand.to_owned())),
-        };
+    /// Wraps an operation so that it is only applied if the value equals the expected one.
+    fn get_conditional(expected: &str, op: Self, width: Width) -> Result<Self, CalculatorErrors> {
+        Ok(Self::IfEquals(width.parse(expected)?, Box::new(op)))
+    }
+
+    /// Returns the unary operation for the given operator: `neg`, `abs` or `not`.
+    pub(crate) fn get_unary_op(operation: &str) -> Result<Self, CalculatorErrors> {
+        match operation {
+            "neg" => Ok(Self::Neg),
+            "abs" => Ok(Self::Abs),
+            "not" => Ok(Self::Not),
+            _ => Err(CalculatorErrors::InvalidOperation(operation.to_owned())),
+        }
+    }
+
+    fn get_op(operation: &str, operand: &str, width: Width) -> Result<Self, CalculatorErrors> {
+        // Parse the operand into an integer of the given width.
+        let operand = width.parse(operand)?;
+        Self::get_binary_op(operation, operand)
+    }
+
+    /// Returns the operator and operand of a binary operation, or `None` for other operations.
+    pub(crate) fn get_binary_operator(&self) -> Option<(&'static str, i128)> {
+        match *self {
+            Self::Add(operand) => Some(("+", operand)),
+            Self::Sub(operand) => Some(("-", operand)),
+            Self::Mul(operand) => Some(("*", operand)),
+            Self::Div(operand) => Some(("/", operand)),
+            Self::Mod(operand) => Some(("%", operand)),
+            Self::Pow(operand) => Some(("^", operand)),
+            Self::And(operand) => Some(("&", operand)),
+            Self::Or(operand) => Some(("|", operand)),
+            Self::Xor(operand) => Some(("xor", operand)),
+            Self::Shl(operand) => Some(("<<", operand)),
+            Self::Shr(operand) => Some((">>", operand)),
+            Self::Set(operand) => Some(("=", operand)),
+            _ => None,
+        }
+    }
 
+    /// Returns the operation for the given binary operator and operand.
+    pub(crate) fn get_binary_op(operation: &str, operand: i128) -> Result<Self, CalculatorErrors> {
         match operation {
             "+" => Ok(Self::Add(operand)),
             "-" => Ok(Self::Sub(operand)),
             "*" => Ok(Self::Mul(operand)),
             "/" => Ok(Self::Div(operand)),
+            "%" => Ok(Self::Mod(operand)),
+            "^" => Ok(Self::Pow(operand)),
+            "&" => Ok(Self::And(operand)),
+            "|" => Ok(Self::Or(operand)),
+            "xor" => Ok(Self::Xor(operand)),
+            "<<" => Ok(Self::Shl(operand)),
+            ">>" => Ok(Self::Shr(operand)),
+            "=" => Ok(Self::Set(operand)),
             _ => Err(CalculatorErrors::InvalidOperation(operation.to_owned())),
         }
     }
@@ -74,28 +204,200 @@ impl Operation {
 
 #[test]
 fn test_parse_add() {
-    let op = Operation::get_op("+", "10").unwrap();
+    let op = Operation::get_op("+", "10", Width::default()).unwrap();
     assert_eq!(op, Operation::Add(10));
 }
 
 #[test]
 fn test_parse_sub() {
-    let op = Operation::get_op("-", "10").unwrap();
+    let op = Operation::get_op("-", "10", Width::default()).unwrap();
     assert_eq!(op, Operation::Sub(10));
 }
 
 #[test]
 fn test_parse_mul() {
-    let op = Operation::get_op("*", "10").unwrap();
+    let op = Operation::get_op("*", "10", Width::default()).unwrap();
     assert_eq!(op, Operation::Mul(10));
 }
 
 #[test]
 fn test_parse_div() {
-    let op = Operation::get_op("/", "10").unwrap();
+    let op = Operation::get_op("/", "10", Width::default()).unwrap();
     assert_eq!(op, Operation::Div(10));
 }
 
+#[test]
+fn test_parse_mod() {
+    let op = Operation::get_op("%", "10", Width::default()).unwrap();
+    assert_eq!(op, Operation::Mod(10));
+}
+
+#[test]
+fn test_parse_pow() {
+    let op = Operation::get_op("^", "10", Width::default()).unwrap();
+    assert_eq!(op, Operation::Pow(10));
+}
+
+#[test]
+fn test_parse_and() {
+    let op = Operation::get_op("&", "10", Width::default()).unwrap();
+    assert_eq!(op, Operation::And(10));
+}
+
+#[test]
+fn test_parse_or() {
+    let op = Operation::get_op("|", "10", Width::default()).unwrap();
+    assert_eq!(op, Operation::Or(10));
+}
+
+#[test]
+fn test_parse_xor() {
+    let op = Operation::get_op("xor", "10", Width::default()).unwrap();
+    assert_eq!(op, Operation::Xor(10));
+}
+
+#[test]
+fn test_parse_shl() {
+    let op = Operation::get_op("<<", "10", Width::default()).unwrap();
+    assert_eq!(op, Operation::Shl(10));
+}
+
+#[test]
+fn test_parse_shr() {
+    let op = Operation::get_op(">>", "10", Width::default()).unwrap();
+    assert_eq!(op, Operation::Shr(10));
+}
+
+#[test]
+fn test_parse_set() {
+    let op = Operation::get_op("=", "10", Width::default()).unwrap();
+    assert_eq!(op, Operation::Set(10));
+}
+
+#[test]
+fn test_parse_unary() {
+    assert_eq!(Operation::get_unary_op("neg").unwrap(), Operation::Neg);
+    assert_eq!(Operation::get_unary_op("abs").unwrap(), Operation::Abs);
+    assert_eq!(Operation::get_unary_op("not").unwrap(), Operation::Not);
+}
+
+#[test]
+fn test_parse_invalid_unary() {
+    match Operation::from_str("OP +") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidOperation(_) => (),
+            _ => panic!("Should throw InvalidOperation error, got: {:?}", e),
+        },
+    }
+}
+
+#[test]
+fn test_parse_op_unary() {
+    let op = Operation::from_str("OP neg").unwrap();
+    assert_eq!(op, Operation::Neg);
+}
+
+#[test]
+fn test_parse_reset() {
+    let op = Operation::from_str("RESET").unwrap();
+    assert_eq!(op, Operation::Reset);
+}
+
+#[test]
+fn test_parse_reset_error_lenght() {
+    match Operation::from_str("RESET 0") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::ArgsLenFailure => (),
+            _ => panic!("Should throw an args length error, got: {:?}", e),
+        },
+    };
+}
+
+#[test]
+fn test_parse_cas() {
+    let op = Operation::from_str("CAS 5 10").unwrap();
+    assert_eq!(op, Operation::IfEquals(5, Box::new(Operation::Set(10))));
+}
+
+#[test]
+fn test_parse_cas_error_lenght() {
+    match Operation::from_str("CAS 5") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::ArgsLenFailure => (),
+            _ => panic!("Should throw an args length error, got: {:?}", e),
+        },
+    };
+}
+
+#[test]
+fn test_parse_op_if() {
+    let op = Operation::from_str("OP + 3 IF 7").unwrap();
+    assert_eq!(op, Operation::IfEquals(7, Box::new(Operation::Add(3))));
+}
+
+#[test]
+fn test_parse_op_unary_if() {
+    let op = Operation::from_str("OP not IF 0").unwrap();
+    assert_eq!(op, Operation::IfEquals(0, Box::new(Operation::Not)));
+}
+
+#[test]
+fn test_parse_op_if_invalid_expected() {
+    match Operation::from_str("OP + 3 IF 256") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidInteger(_) => (),
+            _ => panic!("Should throw InvalidInteger error, got: {:?}", e),
+        },
+    }
+}
+
+#[test]
+fn test_parse_eval() {
+    let op = Operation::from_str("EVAL (x + 3) * 2").unwrap();
+    let expression = Expression::parse("(x + 3) * 2", Width::default()).unwrap();
+    assert_eq!(op, Operation::Eval(expression));
+}
+
+#[test]
+fn test_parse_eval_invalid() {
+    match Operation::from_str("EVAL (x + 3") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidExpression(_) => (),
+            _ => panic!("Should throw InvalidExpression error, got: {:?}", e),
+        },
+    }
+}
+
+#[test]
+fn test_display_round_trip() {
+    let width: Width = "i64".parse().unwrap();
+    for line in [
+        "GET",
+        "RESET",
+        "OP + 5",
+        "OP xor -3",
+        "OP = 7",
+        "OP abs",
+        "OP << 2 IF -1",
+        "OP neg IF 0",
+        "EVAL (x + 3) * -abs x",
+    ] {
+        let op = Operation::parse(line, width).unwrap();
+        let reparsed = Operation::parse(&op.to_string(), width).unwrap();
+        assert_eq!(op, reparsed, "{} was formatted as {}", line, op);
+    }
+}
+
+#[test]
+fn test_display_binary() {
+    assert_eq!(Operation::Shr(3).to_string(), "OP >> 3");
+}
+
 #[test]
 fn test_parse_op_add() {
     let op = Operation::from_str("OP + 10").unwrap();
@@ -150,19 +452,19 @@ fn test_parse_get_error_lenght() {
 
 #[test]
 fn test_parse_add_border_case_0() {
-    let op = Operation::get_op("+", "0").unwrap();
+    let op = Operation::get_op("+", "0", Width::default()).unwrap();
     assert_eq!(op, Operation::Add(0));
 }
 
 #[test]
 fn test_parse_add_border_case_255() {
-    let op = Operation::get_op("+", "255").unwrap();
+    let op = Operation::get_op("+", "255", Width::default()).unwrap();
     assert_eq!(op, Operation::Add(255));
 }
 
 #[test]
 fn test_parse_invalid_operation() {
-    match Operation::get_op("%", "10") {
+    match Operation::get_op("$", "10", Width::default()) {
         Ok(_) => panic!("Should throw an error"),
         Err(e) => match e {
             CalculatorErrors::InvalidOperation(_) => (),
@@ -173,7 +475,7 @@ fn test_parse_invalid_operation() {
 
 #[test]
 fn test_parse_invalid_line() {
-    match Operation::get_op("+", "- 10") {
+    match Operation::get_op("+", "- 10", Width::default()) {
         Ok(_) => panic!("Should throw an error"),
         Err(e) => match e {
             CalculatorErrors::InvalidInteger(_) => (),
@@ -184,7 +486,7 @@ fn test_parse_invalid_line() {
 
 #[test]
 fn test_parse_invalid_negative_operand_1() {
-    match Operation::get_op("+", "-1") {
+    match Operation::get_op("+", "-1", Width::default()) {
         Ok(_) => panic!("Should throw an error"),
         Err(e) => match e {
             CalculatorErrors::InvalidInteger(_) => (),
@@ -195,7 +497,30 @@ fn test_parse_invalid_negative_operand_1() {
 
 #[test]
 fn test_parse_invalid_operand_256() {
-    match Operation::get_op("+", "256") {
+    match Operation::get_op("+", "256", Width::default()) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidInteger(_) => (),
+            _ => panic!("Should throw InvalidInteger error, got: {:?}", e),
+        },
+    }
+}
+
+#[test]
+fn test_parse_op_u16_operand() {
+    let op = Operation::parse("OP + 1000", "u16".parse().unwrap()).unwrap();
+    assert_eq!(op, Operation::Add(1000));
+}
+
+#[test]
+fn test_parse_op_i64_negative_operand() {
+    let op = Operation::parse("OP * -3", "i64".parse().unwrap()).unwrap();
+    assert_eq!(op, Operation::Mul(-3));
+}
+
+#[test]
+fn test_parse_op_u16_operand_out_of_range() {
+    match Operation::parse("OP + 65536", "u16".parse().unwrap()) {
         Ok(_) => panic!("Should throw an error"),
         Err(e) => match e {
             CalculatorErrors::InvalidInteger(_) => (),
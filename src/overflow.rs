@@ -0,0 +1,131 @@
+use crate::{errors::CalculatorErrors, width::Width};
+use std::str::FromStr;
+
+/// The policy applied when the result of an operation does not fit in the
+/// calculator's width.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Overflow {
+    /// The result wraps around the boundaries of the width (e.g. 255 + 1 = 0 in u8).
+    #[default]
+    Wrapping,
+    /// The result is clamped to the boundaries of the width (e.g. 255 + 1 = 255 in u8).
+    Saturating,
+    /// The operation is rejected and the value is left unchanged.
+    Checked,
+}
+
+impl FromStr for Overflow {
+    type Err = CalculatorErrors;
+
+    /// Parses a policy: `wrapping`, `saturating` or `checked`.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::InvalidArgument` if the policy is unknown.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wrapping" => Ok(Self::Wrapping),
+            "saturating" => Ok(Self::Saturating),
+            "checked" => Ok(Self::Checked),
+            _ => Err(CalculatorErrors::InvalidArgument(s.to_owned())),
+        }
+    }
+}
+
+impl Overflow {
+    /// Brings the result of an operation into the range of the width according to the policy.
+    ///
+    /// The operation must be computed in `i128` in its three flavours, since the exact
+    /// result may not even fit in an `i128`.
+    ///
+    /// # Arguments:
+    ///
+    /// * `width` - The width of the calculator.
+    /// * `checked` - The exact result, or `None` if it overflows an `i128`.
+    /// * `saturating` - The result clamped to the range of an `i128`.
+    /// * `wrapping` - The result wrapped around the range of an `i128`.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::Overflow` if the policy is `Checked` and the exact
+    /// result does not fit in the width.
+    pub fn resolve(
+        &self,
+        width: Width,
+        checked: Option<i128>,
+        saturating: i128,
+        wrapping: i128,
+    ) -> Result<i128, CalculatorErrors> {
+        match self {
+            Self::Wrapping => Ok(width.wrap(wrapping)),
+            Self::Saturating => Ok(width.saturate(saturating)),
+            Self::Checked => match checked {
+                Some(value) if width.contains(value) => Ok(value),
+                _ => Err(CalculatorErrors::Overflow),
+            },
+        }
+    }
+}
+
+#[test]
+fn test_parse_overflow() {
+    assert_eq!(Overflow::from_str("wrapping").unwrap(), Overflow::Wrapping);
+    assert_eq!(
+        Overflow::from_str("saturating").unwrap(),
+        Overflow::Saturating
+    );
+    assert_eq!(Overflow::from_str("checked").unwrap(), Overflow::Checked);
+}
+
+#[test]
+fn test_parse_overflow_invalid() {
+    match Overflow::from_str("clamp") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidArgument(_) => (),
+            _ => panic!("Should throw InvalidArgument error, got: {:?}", e),
+        },
+    }
+}
+
+#[test]
+fn test_resolve_in_range() {
+    for overflow in [Overflow::Wrapping, Overflow::Saturating, Overflow::Checked] {
+        let value = overflow.resolve(Width::default(), Some(7), 7, 7).unwrap();
+        assert_eq!(value, 7);
+    }
+}
+
+#[test]
+fn test_resolve_out_of_range() {
+    let width = Width::default();
+    assert_eq!(
+        Overflow::Wrapping
+            .resolve(width, Some(256), 256, 256)
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        Overflow::Saturating
+            .resolve(width, Some(256), 256, 256)
+            .unwrap(),
+        255
+    );
+    assert!(
+        Overflow::Checked
+            .resolve(width, Some(256), 256, 256)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_resolve_out_of_i128_range() {
+    let width = Width::from_str("u64").unwrap();
+    let saturated = Overflow::Saturating.resolve(width, None, i128::MAX, 1);
+    assert_eq!(saturated.unwrap(), u64::MAX as i128);
+    assert!(
+        Overflow::Checked
+            .resolve(width, None, i128::MAX, 1)
+            .is_err()
+    );
+}
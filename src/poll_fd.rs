@@ -0,0 +1,121 @@
+use crate::errors::CalculatorErrors;
+use std::{
+    ffi::{c_int, c_short},
+    io,
+    os::fd::{AsRawFd, RawFd},
+    time::Duration,
+};
+
+/// The event of a descriptor that has data to read, or a pending connection.
+const POLLIN: c_short = 0x1;
+/// The event of a descriptor that can be written without blocking.
+const POLLOUT: c_short = 0x4;
+
+/// The type of the number of descriptors passed to `poll`, `nfds_t` in C, which is an
+/// `unsigned int` on Apple platforms and the BSDs.
+#[cfg(any(
+    target_vendor = "apple",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+type Nfds = std::ffi::c_uint;
+/// The type of the number of descriptors passed to `poll`, `nfds_t` in C, which is an
+/// `unsigned long` on Linux and the other unix targets.
+#[cfg(not(any(
+    target_vendor = "apple",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+type Nfds = std::ffi::c_ulong;
+
+unsafe extern "C" {
+    /// Waits until one of the descriptors is ready, or the timeout in milliseconds expires.
+    fn poll(fds: *mut PollFd, nfds: Nfds, timeout: c_int) -> c_int;
+}
+
+/// A descriptor watched by `poll(2)`, with the events it is waited for and the events it
+/// is ready for. Its layout is the one of `struct pollfd`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct PollFd {
+    fd: RawFd,
+    events: c_short,
+    revents: c_short,
+}
+
+impl PollFd {
+    /// Creates a descriptor to wait for.
+    ///
+    /// # Arguments:
+    ///
+    /// * `source` - The socket.
+    /// * `read` - Whether to wait until it has data to read, or a pending connection.
+    /// * `write` - Whether to wait until it can be written.
+    pub fn new(source: &impl AsRawFd, read: bool, write: bool) -> Self {
+        let read = if read { POLLIN } else { 0 };
+        let write = if write { POLLOUT } else { 0 };
+        Self {
+            fd: source.as_raw_fd(),
+            events: read | write,
+            revents: 0,
+        }
+    }
+
+    /// Returns whether the last wait found the descriptor ready, either for the events
+    /// it was waited for or because its connection failed or was closed.
+    pub fn is_ready(&self) -> bool {
+        self.revents != 0
+    }
+
+    /// Waits until at least one of the descriptors is ready, a signal is received, or the
+    /// timeout expires, and records which of them are ready.
+    ///
+    /// # Arguments:
+    ///
+    /// * `fds` - The descriptors.
+    /// * `timeout` - The longest time to wait.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::SocketFailure` if the descriptors cannot be waited for.
+    pub fn wait(fds: &mut [PollFd], timeout: Duration) -> Result<(), CalculatorErrors> {
+        let timeout = c_int::try_from(timeout.as_millis()).unwrap_or(c_int::MAX);
+        // SAFETY: the pointer and the length describe a valid, exclusively borrowed slice
+        // of `struct pollfd`, which `poll` only accesses until it returns.
+        let ready = unsafe { poll(fds.as_mut_ptr(), fds.len() as Nfds, timeout) };
+        if ready < 0 && io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            return Err(CalculatorErrors::SocketFailure);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_wait_reports_ready_sockets() {
+    use std::{io::Write, net::TcpListener, net::TcpStream};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (server, _) = listener.accept().unwrap();
+
+    let mut fds = [
+        PollFd::new(&listener, true, false),
+        PollFd::new(&server, true, false),
+    ];
+    PollFd::wait(&mut fds, Duration::from_millis(10)).unwrap();
+    assert!(!fds[0].is_ready());
+    assert!(!fds[1].is_ready());
+
+    client.write_all(b"GET\n").unwrap();
+    let mut fds = [
+        PollFd::new(&server, true, false),
+        PollFd::new(&client, false, true),
+    ];
+    PollFd::wait(&mut fds, Duration::from_secs(1)).unwrap();
+    assert!(fds[0].is_ready());
+    assert!(fds[1].is_ready());
+}
@@ -0,0 +1,373 @@
+use crate::{
+    errors::CalculatorErrors,
+    raft_peer::RaftPeer,
+    raft_role::RaftRole,
+    raft_state::RaftState,
+    registry::Registry,
+    response::Response,
+    storage::{Storage, Values},
+};
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The time between two messages of the leader to each follower, and between two checks
+/// of the election timeout.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A node of a Raft cluster, which agrees with the other nodes on the order of the changes
+/// applied to the registers.
+///
+/// Clients send their changes to the leader, which appends them to its log and replicates
+/// them to the followers. Once a majority of the nodes has an entry, it is committed and
+/// every node applies it to its registry, in the same order. A node that does not hear
+/// from the leader within a random timeout starts an election, and the nodes only vote for
+/// candidates whose log is at least as up to date as theirs.
+///
+/// Reads are not part of the log: each node answers them with the registers as it last
+/// applied them, so a follower may answer with a value older than one the leader already
+/// confirmed to a client.
+///
+/// The nodes exchange single-line messages over the same port clients use:
+///
+/// * `VOTE <term> <candidate> <last index> <last term>`, answered with `VOTED <term> <0|1>`.
+/// * `APPEND <term> <leader> <prev index> <prev term> <commit>[\t<entry>]...`, answered with
+///   `APPENDED <term> <0|1> <index>`.
+/// * `SNAPSHOT <term> <leader> <last index> <last term>[\t<register> <value>]...`, sent
+///   instead of the entries the leader replaced with a snapshot, and answered like `APPEND`.
+#[derive(Debug)]
+pub struct Raft {
+    state: Mutex<RaftState>,
+    changed: Condvar,
+    nodes: Vec<String>,
+    me: usize,
+}
+
+impl Raft {
+    /// Creates a node of a cluster, as a follower with an empty log.
+    ///
+    /// # Arguments:
+    ///
+    /// * `nodes` - The addresses of every node of the cluster, including this one.
+    /// * `me` - The index of this node in `nodes`.
+    /// * `interval` - The number of applied entries after which they are replaced by a
+    ///   snapshot.
+    pub fn new(nodes: Vec<String>, me: usize, interval: usize) -> Self {
+        Self {
+            state: Mutex::new(RaftState::new(me, nodes.len(), interval)),
+            changed: Condvar::new(),
+            nodes,
+            me,
+        }
+    }
+
+    /// Restores the term, the vote and the log persisted in the storage, and persists
+    /// every later change to them before answering or sending messages to other nodes.
+    ///
+    /// # Arguments:
+    ///
+    /// * `storage` - The storage of the node.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::StorageFailure` if the storage cannot be read.
+    pub fn attach_storage(&mut self, storage: Storage) -> Result<(), CalculatorErrors> {
+        match self.state.get_mut() {
+            Ok(state) => state.attach_storage(storage),
+            Err(_) => Err(CalculatorErrors::LockFailure),
+        }
+    }
+
+    /// Loads the values of the snapshot restored from the storage into the registry, since
+    /// the entries it replaces are not applied again.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::LockFailure` if the state or a register is poisoned.
+    pub fn load_snapshot(&self, registry: &Registry) -> Result<(), CalculatorErrors> {
+        registry.load_values(self.lock()?.snapshot())
+    }
+
+    /// Starts the threads of the node of the given registry, if it belongs to a cluster:
+    /// one that checks the election timeout, and one per other node that sends it the
+    /// messages of this node.
+    pub fn spawn(registry: &Arc<Registry>) {
+        let (size, me) = match registry.cluster() {
+            Some(raft) => (raft.nodes.len(), raft.me),
+            None => return,
+        };
+
+        let ticker = Arc::clone(registry);
+        thread::spawn(move || {
+            if let Some(raft) = ticker.cluster() {
+                raft.tick();
+            }
+        });
+
+        for peer in (0..size).filter(|peer| *peer != me) {
+            let registry = Arc::clone(registry);
+            thread::spawn(move || {
+                if let Some(raft) = registry.cluster() {
+                    raft.follow_peer(&registry, peer);
+                }
+            });
+        }
+    }
+
+    /// Appends a change to the log of the leader, and waits until it is applied.
+    ///
+    /// # Arguments:
+    ///
+    /// * `registry` - The registry the change is applied to.
+    /// * `register` - The name of the register.
+    /// * `messages` - The protocol messages of the change.
+    /// * `client` - The id of the client that sent the change.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::NotLeader` with the address of the leader if this node
+    /// is not the leader, or if it stops being the leader before the change is applied,
+    /// in which case the change may or may not be applied, or
+    /// `CalculatorErrors::StorageFailure` if the change cannot be persisted. Otherwise,
+    /// returns the error produced while applying the change.
+    ///
+    /// # Returns:
+    ///
+    /// The response to the change, which summarizes it if it is a batch.
+    pub fn submit(
+        &self,
+        registry: &Registry,
+        register: &str,
+        messages: Vec<String>,
+        client: u64,
+    ) -> Result<Response, CalculatorErrors> {
+        let mut state = self.lock()?;
+        if state.role() != RaftRole::Leader {
+            return Err(self.not_leader(&state));
+        }
+
+        let term = state.term();
+        let index = state.push(register, messages, client, registry)?;
+        self.changed.notify_all();
+
+        loop {
+            match state.take_result(index, term) {
+                Some(Err(CalculatorErrors::NotLeader(_))) => return Err(self.not_leader(&state)),
+                Some(result) => return result,
+                None => state = self.wait(state)?,
+            }
+        }
+    }
+
+    /// Processes a message sent by another node of the cluster.
+    ///
+    /// # Arguments:
+    ///
+    /// * `registry` - The registry the committed entries are applied to.
+    /// * `message` - A `VOTE`, `APPEND` or `SNAPSHOT` message.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::UnexpectedMessage` if the message is malformed, or
+    /// `CalculatorErrors::StorageFailure` if the state of the node cannot be persisted
+    /// before answering.
+    ///
+    /// # Returns:
+    ///
+    /// The answer to the message.
+    pub fn handle(&self, registry: &Registry, message: &str) -> Result<String, CalculatorErrors> {
+        let mut parts = message.split('\t');
+        let header: Vec<&str> = parts
+            .next()
+            .unwrap_or_default()
+            .split_whitespace()
+            .collect();
+        let (kind, numbers) = match header.split_first() {
+            Some((kind, numbers)) => (*kind, parse_numbers(numbers)?),
+            None => return Err(CalculatorErrors::UnexpectedMessage(message.to_owned())),
+        };
+
+        let mut state = self.lock()?;
+        let answer = match (kind, &numbers[..]) {
+            ("VOTE", &[term, candidate, last_index, last_term]) => {
+                let (term, granted) =
+                    state.vote(term, candidate as usize, last_index as usize, last_term);
+                format!("VOTED {} {}", term, u8::from(granted))
+            }
+            ("APPEND", &[term, leader, prev_index, prev_term, commit]) => {
+                let entries = parts.map(|entry| entry.parse()).collect::<Result<_, _>>()?;
+                let header = (term, leader as usize);
+                let prev = (prev_index as usize, prev_term);
+                appended(state.append(header, prev, commit as usize, entries, registry))
+            }
+            ("SNAPSHOT", &[term, leader, last_index, last_term]) => {
+                let values = parse_values(parts)?;
+                let header = (term, leader as usize);
+                let last = (last_index as usize, last_term);
+                appended(state.install(header, last, values, registry))
+            }
+            _ => return Err(CalculatorErrors::UnexpectedMessage(message.to_owned())),
+        };
+
+        state.persist()?;
+        self.changed.notify_all();
+        Ok(answer)
+    }
+
+    /// Returns the `NotLeader` error, with the address of the leader if it is known.
+    fn not_leader(&self, state: &RaftState) -> CalculatorErrors {
+        let leader = state.leader().and_then(|leader| self.nodes.get(leader));
+        CalculatorErrors::NotLeader(leader.map(|addr| addr.to_owned()))
+    }
+
+    /// Starts an election whenever the leader has not been heard of for too long.
+    fn tick(&self) {
+        loop {
+            thread::sleep(HEARTBEAT_INTERVAL);
+            if let Ok(mut state) = self.lock()
+                && state.election_due(Instant::now())
+            {
+                state.start_election();
+                self.changed.notify_all();
+            }
+        }
+    }
+
+    /// Sends the messages of this node to another one: the vote request of each election,
+    /// or the entries the peer misses while this node is the leader. The state is not
+    /// locked while waiting for the answer.
+    fn follow_peer(&self, registry: &Registry, index: usize) {
+        let mut peer = RaftPeer::new(&self.nodes[index]);
+        let mut asked = 0;
+        loop {
+            let message = match self.lock() {
+                Ok(mut state) => next_message(&mut state, index, &mut asked),
+                Err(_) => return,
+            };
+
+            if let Some(message) = message
+                && let Ok(reply) = peer.call(&message)
+            {
+                self.receive(registry, index, &reply);
+            }
+
+            let waited = match self.lock() {
+                Ok(state) => self.wait(state).is_ok(),
+                Err(_) => false,
+            };
+            if !waited {
+                return;
+            }
+        }
+    }
+
+    /// Processes the answer of a peer to a `VOTE` or `APPEND` message. Malformed answers
+    /// are ignored.
+    fn receive(&self, registry: &Registry, index: usize, reply: &str) {
+        let tokens: Vec<&str> = reply.split_whitespace().collect();
+        let (kind, numbers) = match tokens.split_first() {
+            Some((kind, numbers)) => (*kind, parse_numbers(numbers)),
+            None => return,
+        };
+        let (numbers, mut state) = match (numbers, self.lock()) {
+            (Ok(numbers), Ok(state)) => (numbers, state),
+            _ => return,
+        };
+
+        let changed = match (kind, &numbers[..]) {
+            ("VOTED", &[term, granted]) => state.vote_reply(term, granted == 1),
+            ("APPENDED", &[term, success, last]) => {
+                let reply = (term, success == 1, last as usize);
+                state.append_reply(index, reply, registry)
+            }
+            _ => false,
+        };
+
+        if changed {
+            self.changed.notify_all();
+        }
+    }
+
+    /// Waits until the state changes, or for a heartbeat interval at most.
+    fn wait<'a>(
+        &self,
+        state: MutexGuard<'a, RaftState>,
+    ) -> Result<MutexGuard<'a, RaftState>, CalculatorErrors> {
+        match self.changed.wait_timeout(state, HEARTBEAT_INTERVAL) {
+            Ok((state, _)) => Ok(state),
+            Err(_) => Err(CalculatorErrors::LockFailure),
+        }
+    }
+
+    /// Locks the state of the node.
+    fn lock(&self) -> Result<MutexGuard<'_, RaftState>, CalculatorErrors> {
+        match self.state.lock() {
+            Ok(state) => Ok(state),
+            Err(_) => Err(CalculatorErrors::LockFailure),
+        }
+    }
+}
+
+/// Returns the message to send to a peer: the vote request of an election not yet asked
+/// to it, or the entries it misses if this node is the leader. Nothing is sent until the
+/// state of the node is persisted.
+fn next_message(state: &mut RaftState, index: usize, asked: &mut u64) -> Option<String> {
+    state.persist().ok()?;
+    match state.role() {
+        RaftRole::Leader => Some(state.append_message(index)),
+        RaftRole::Candidate if *asked != state.term() => {
+            *asked = state.term();
+            Some(state.vote_message())
+        }
+        _ => None,
+    }
+}
+
+/// Returns the answer to an `APPEND` or `SNAPSHOT` message.
+fn appended((term, success, index): (u64, bool, usize)) -> String {
+    format!("APPENDED {} {} {}", term, u8::from(success), index)
+}
+
+/// Parses the `<register> <value>` fields of a `SNAPSHOT` message.
+fn parse_values<'a>(fields: impl Iterator<Item = &'a str>) -> Result<Values, CalculatorErrors> {
+    let mut values = vec![];
+    for field in fields {
+        match field
+            .split_once(' ')
+            .map(|(name, value)| (name, value.parse()))
+        {
+            Some((name, Ok(value))) => values.push((name.to_owned(), value)),
+            _ => return Err(CalculatorErrors::UnexpectedMessage(field.to_owned())),
+        }
+    }
+    Ok(values)
+}
+
+/// Parses the numeric fields of a message between nodes.
+fn parse_numbers(tokens: &[&str]) -> Result<Vec<u64>, CalculatorErrors> {
+    let mut numbers = vec![];
+    for token in tokens {
+        match token.parse() {
+            Ok(number) => numbers.push(number),
+            Err(_) => return Err(CalculatorErrors::UnexpectedMessage(token.to_string())),
+        }
+    }
+    Ok(numbers)
+}
+
+#[test]
+fn test_empty_messages_are_rejected() {
+    let registry = Registry::default();
+    let raft = Raft::new(vec!["127.0.0.1:0".to_owned()], 0, 100);
+    raft.receive(&registry, 0, "");
+    raft.receive(&registry, 0, "   ");
+    match raft.handle(&registry, "") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::UnexpectedMessage(_) => (),
+            _ => panic!("Should throw UnexpectedMessage error"),
+        },
+    }
+}
@@ -0,0 +1,91 @@
+use crate::errors::CalculatorErrors;
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+/// The time to wait for a connection to a peer.
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(100);
+/// The time to wait for the answer of a peer.
+const REPLY_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// The connection to another node of a Raft cluster.
+///
+/// The connection is opened on the first call and kept open, and is reopened on the
+/// next call after any failure.
+#[derive(Debug)]
+pub struct RaftPeer {
+    addr: String,
+    connection: Option<BufReader<TcpStream>>,
+}
+
+impl RaftPeer {
+    /// Creates the connection to the peer with the given address, without opening it.
+    pub fn new(addr: &str) -> Self {
+        Self {
+            addr: addr.to_owned(),
+            connection: None,
+        }
+    }
+
+    /// Sends a message to the peer and waits for its answer.
+    ///
+    /// # Arguments:
+    ///
+    /// * `message` - The message, without its terminator.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::SocketFailure` if the peer cannot be reached, or
+    /// `CalculatorErrors::ListeningFailure` if it does not answer in time.
+    ///
+    /// # Returns:
+    ///
+    /// The answer, without its terminator.
+    pub fn call(&mut self, message: &str) -> Result<String, CalculatorErrors> {
+        let result = self.try_call(message);
+        if result.is_err() {
+            self.connection = None;
+        }
+        result
+    }
+
+    /// Sends a message to the peer, opening the connection if needed.
+    fn try_call(&mut self, message: &str) -> Result<String, CalculatorErrors> {
+        let connection = match &mut self.connection {
+            Some(connection) => connection,
+            None => self.connection.insert(connect(&self.addr)?),
+        };
+
+        let line = format!("{}\n", message);
+        if connection.get_mut().write_all(line.as_bytes()).is_err() {
+            return Err(CalculatorErrors::WritingFailure);
+        }
+
+        let mut reply = String::new();
+        match connection.read_line(&mut reply) {
+            Ok(0) | Err(_) => Err(CalculatorErrors::ListeningFailure),
+            Ok(_) => Ok(reply.trim_end().to_owned()),
+        }
+    }
+}
+
+/// Opens a connection to the given address.
+fn connect(addr: &str) -> Result<BufReader<TcpStream>, CalculatorErrors> {
+    let socket = match addr
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+    {
+        Some(socket) => socket,
+        None => return Err(CalculatorErrors::SocketFailure),
+    };
+
+    match TcpStream::connect_timeout(&socket, CONNECT_TIMEOUT) {
+        Ok(stream) if stream.set_read_timeout(Some(REPLY_TIMEOUT)).is_ok() => {
+            Ok(BufReader::new(stream))
+        }
+        _ => Err(CalculatorErrors::SocketFailure),
+    }
+}
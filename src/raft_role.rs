@@ -0,0 +1,11 @@
+/// The role of a node of a Raft cluster.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum RaftRole {
+    /// Accepts entries from the leader, and votes in elections.
+    #[default]
+    Follower,
+    /// Requests the votes of the other nodes to become the leader.
+    Candidate,
+    /// Accepts changes from clients, and replicates them to the followers.
+    Leader,
+}
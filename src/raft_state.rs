@@ -0,0 +1,975 @@
+use crate::{
+    errors::CalculatorErrors,
+    log_entry::LogEntry,
+    raft_role::RaftRole,
+    registry::Registry,
+    response::Response,
+    storage::{Storage, Values},
+};
+use std::{
+    collections::{HashMap, hash_map::RandomState},
+    hash::{BuildHasher, Hasher},
+    time::{Duration, Instant},
+};
+
+/// The minimum time a follower waits for the leader before starting an election.
+const MIN_ELECTION_TIMEOUT: u64 = 300;
+/// The maximum number of entries sent to a follower in a single message.
+const MAX_ENTRIES_PER_MESSAGE: usize = 100;
+/// The name the records of the node are logged under in the storage.
+const STORAGE_RECORD: &str = "raft";
+/// The keyword of the records with the current term and vote.
+const TERM_RECORD: &str = "TERM";
+/// The keyword of the records with an entry of the log and its index.
+const ENTRY_RECORD: &str = "ENTRY";
+/// The keyword of the record with the index and term of the last entry of the snapshot.
+const SNAPSHOT_RECORD: &str = "SNAPSHOT";
+/// The keyword of the records with the value of a register in the snapshot.
+const VALUE_RECORD: &str = "VALUE";
+/// The vote of a term in which the node has not voted yet.
+const NO_VOTE: &str = "-";
+
+/// The result of applying an entry, kept until the client that sent it collects it.
+type EntryResult = Option<Result<Response, CalculatorErrors>>;
+
+/// The state of a node of a Raft cluster.
+///
+/// The log starts with a sentinel entry, so the first real entry has index 1. Entries are
+/// applied to the registry in order, once they are committed: replicated to a majority of
+/// the nodes by a leader of their same term.
+///
+/// Once enough entries are applied, they are replaced by a snapshot with the value of
+/// every register, and the sentinel takes the index and term of the last of them. A
+/// follower that misses entries the leader no longer has receives the snapshot instead.
+///
+/// With a storage, the term, the vote and the log are appended to it as
+/// `TERM <term> <vote|->` and `ENTRY <index> <entry>` records, where an entry replaces the
+/// one with the same index and every entry after it. After a snapshot, the log is
+/// rewritten to start with a `SNAPSHOT <index> <term>` record followed by a
+/// `VALUE <register> <value>` record per register.
+#[derive(Debug)]
+pub struct RaftState {
+    me: usize,
+    size: usize,
+    term: u64,
+    voted_for: Option<usize>,
+    role: RaftRole,
+    leader: Option<usize>,
+    log: Vec<LogEntry>,
+    commit: usize,
+    applied: usize,
+    votes: usize,
+    next_index: Vec<usize>,
+    match_index: Vec<usize>,
+    deadline: Instant,
+    results: HashMap<usize, (u64, EntryResult)>,
+    storage: Option<Storage>,
+    saved_vote: (u64, Option<usize>),
+    persisted: usize,
+    offset: usize,
+    saved_offset: usize,
+    snapshot: Values,
+    interval: usize,
+}
+
+impl RaftState {
+    /// Creates the state of a follower with an empty log.
+    ///
+    /// # Arguments:
+    ///
+    /// * `me` - The index of the node in the cluster.
+    /// * `size` - The number of nodes of the cluster.
+    /// * `interval` - The number of applied entries after which they are replaced by a
+    ///   snapshot.
+    pub fn new(me: usize, size: usize, interval: usize) -> Self {
+        Self {
+            me,
+            size,
+            term: 0,
+            voted_for: None,
+            role: RaftRole::Follower,
+            leader: None,
+            log: vec![LogEntry::noop(0)],
+            commit: 0,
+            applied: 0,
+            votes: 0,
+            next_index: vec![1; size],
+            match_index: vec![0; size],
+            deadline: Instant::now() + election_timeout(),
+            results: HashMap::new(),
+            storage: None,
+            saved_vote: (0, None),
+            persisted: 1,
+            offset: 0,
+            saved_offset: 0,
+            snapshot: vec![],
+            interval,
+        }
+    }
+
+    /// Restores the term, the vote and the log persisted in the storage, and persists
+    /// every later change to them.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::StorageFailure` if the storage cannot be read, or holds
+    /// a malformed record.
+    pub fn attach_storage(&mut self, storage: Storage) -> Result<(), CalculatorErrors> {
+        let (_, records) = storage.recover()?;
+        for (_, record) in records {
+            if self.restore(&record).is_none() {
+                return Err(CalculatorErrors::StorageFailure);
+            }
+        }
+
+        self.saved_vote = (self.term, self.voted_for);
+        self.persisted = self.last_index() + 1;
+        self.saved_offset = self.offset;
+        self.storage = Some(storage);
+        Ok(())
+    }
+
+    /// Writes the term, the vote and the entries that are not durable yet to the storage,
+    /// if there is one. Must be called before answering or sending a message to another
+    /// node, and the leader only counts its own entries once they are durable.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::StorageFailure` if the storage cannot be written, in
+    /// which case the next call writes the changes again.
+    pub fn persist(&mut self) -> Result<(), CalculatorErrors> {
+        if let Some(storage) = &self.storage {
+            if self.saved_offset != self.offset {
+                storage.rewrite(STORAGE_RECORD, &self.snapshot_records())?;
+            } else {
+                let mut records = vec![];
+                if self.saved_vote != (self.term, self.voted_for) {
+                    records.push(self.term_record());
+                }
+                records.extend(self.entry_records(self.persisted));
+                if !records.is_empty() {
+                    storage.append(STORAGE_RECORD, &records)?;
+                }
+            }
+        }
+
+        self.saved_vote = (self.term, self.voted_for);
+        self.saved_offset = self.offset;
+        self.persisted = self.last_index() + 1;
+        if self.role == RaftRole::Leader {
+            self.match_index[self.me] = self.persisted - 1;
+        }
+        Ok(())
+    }
+
+    /// Returns the current term.
+    pub fn term(&self) -> u64 {
+        self.term
+    }
+
+    /// Returns the role of the node.
+    pub fn role(&self) -> RaftRole {
+        self.role
+    }
+
+    /// Returns the index of the leader of the current term, if known.
+    pub fn leader(&self) -> Option<usize> {
+        self.leader
+    }
+
+    /// Returns the index of the last applied entry.
+    pub fn applied(&self) -> usize {
+        self.applied
+    }
+
+    /// Returns the value of every register in the snapshot, which replaces the entries up
+    /// to the first one of the log.
+    pub fn snapshot(&self) -> &Values {
+        &self.snapshot
+    }
+
+    /// Returns whether the leader has not been heard of for longer than the election timeout.
+    pub fn election_due(&self, now: Instant) -> bool {
+        self.role != RaftRole::Leader && now >= self.deadline
+    }
+
+    /// Starts an election for the next term, voting for itself.
+    pub fn start_election(&mut self) {
+        self.term += 1;
+        self.role = RaftRole::Candidate;
+        self.voted_for = Some(self.me);
+        self.leader = None;
+        self.votes = 1;
+        self.deadline = Instant::now() + election_timeout();
+        if self.is_majority(self.votes) {
+            self.become_leader();
+        }
+    }
+
+    /// Returns the vote request of the current election.
+    pub fn vote_message(&self) -> String {
+        format!(
+            "VOTE {} {} {} {}",
+            self.term,
+            self.me,
+            self.last_index(),
+            self.last_term()
+        )
+    }
+
+    /// Processes a vote request.
+    ///
+    /// # Arguments:
+    ///
+    /// * `term` - The term of the candidate.
+    /// * `candidate` - The index of the candidate.
+    /// * `last_index` - The index of the last entry of the candidate's log.
+    /// * `last_term` - The term of the last entry of the candidate's log.
+    ///
+    /// # Returns:
+    ///
+    /// The current term, and whether the vote is granted.
+    pub fn vote(
+        &mut self,
+        term: u64,
+        candidate: usize,
+        last_index: usize,
+        last_term: u64,
+    ) -> (u64, bool) {
+        if term > self.term {
+            self.step_down(term);
+        }
+
+        let up_to_date = (last_term, last_index) >= (self.last_term(), self.last_index());
+        let free = self.voted_for.is_none() || self.voted_for == Some(candidate);
+        let granted = term == self.term && free && up_to_date;
+
+        if granted {
+            self.voted_for = Some(candidate);
+            self.deadline = Instant::now() + election_timeout();
+        }
+        (self.term, granted)
+    }
+
+    /// Processes the answer to a vote request.
+    ///
+    /// # Returns:
+    ///
+    /// Whether the node became the leader.
+    pub fn vote_reply(&mut self, term: u64, granted: bool) -> bool {
+        if term > self.term {
+            self.step_down(term);
+            return false;
+        }
+
+        if self.role != RaftRole::Candidate || term != self.term || !granted {
+            return false;
+        }
+
+        self.votes += 1;
+        if self.is_majority(self.votes) {
+            self.become_leader();
+            return true;
+        }
+        false
+    }
+
+    /// Returns the message that replicates the log to a follower, starting at the entry
+    /// the follower is known to miss, or the snapshot if that entry was replaced by it.
+    pub fn append_message(&self, peer: usize) -> String {
+        let prev = self.next_index[peer] - 1;
+        let prev_term = match self.term_at(prev) {
+            Some(term) if prev >= self.offset => term,
+            _ => return self.snapshot_message(),
+        };
+        let mut message = format!(
+            "APPEND {} {} {} {} {}",
+            self.term, self.me, prev, prev_term, self.commit
+        );
+
+        let entries = self.log.iter().skip(prev - self.offset + 1);
+        for entry in entries.take(MAX_ENTRIES_PER_MESSAGE) {
+            message.push_str(&format!("\t{}", entry));
+        }
+        message
+    }
+
+    /// Processes the entries sent by a leader, applying the newly committed ones.
+    ///
+    /// # Arguments:
+    ///
+    /// * `term` - The term of the leader.
+    /// * `leader` - The index of the leader.
+    /// * `prev` - The index and term of the entry that precedes the sent ones.
+    /// * `commit` - The index of the last entry committed by the leader.
+    /// * `entries` - The entries that follow `prev` in the leader's log.
+    /// * `registry` - The registry the committed entries are applied to.
+    ///
+    /// # Returns:
+    ///
+    /// The current term, whether the entries were accepted, and the index of the last
+    /// entry that matches the leader's log (or of the last entry, if not accepted).
+    pub fn append(
+        &mut self,
+        (term, leader): (u64, usize),
+        prev: (usize, u64),
+        commit: usize,
+        entries: Vec<LogEntry>,
+        registry: &Registry,
+    ) -> (u64, bool, usize) {
+        if term < self.term {
+            return (self.term, false, self.last_index());
+        }
+
+        self.step_down(term);
+        self.leader = Some(leader);
+        self.deadline = Instant::now() + election_timeout();
+
+        let ((prev_index, prev_term), entries) = self.skip_compacted(prev, entries);
+        if self.term_at(prev_index) != Some(prev_term) {
+            let hint = self.last_index().min(prev_index.saturating_sub(1));
+            return (self.term, false, hint);
+        }
+
+        let last = self.merge(prev_index, entries);
+        if commit > self.commit {
+            self.commit = commit.min(last);
+            self.apply(registry);
+        }
+        (self.term, true, last)
+    }
+
+    /// Replaces the log with a snapshot sent by a leader, unless the node already applied
+    /// the entries it replaces. The entries that follow the snapshot are kept if the log
+    /// agrees with it.
+    ///
+    /// # Arguments:
+    ///
+    /// * `term` - The term of the leader.
+    /// * `leader` - The index of the leader.
+    /// * `last` - The index and term of the last entry replaced by the snapshot.
+    /// * `values` - The value of every register in the snapshot.
+    /// * `registry` - The registry the values are loaded into.
+    ///
+    /// # Returns:
+    ///
+    /// The current term, whether the snapshot was accepted, and the index of its last
+    /// entry (or of the last entry of the log, if not accepted).
+    pub fn install(
+        &mut self,
+        (term, leader): (u64, usize),
+        (index, last_term): (usize, u64),
+        values: Values,
+        registry: &Registry,
+    ) -> (u64, bool, usize) {
+        if term < self.term {
+            return (self.term, false, self.last_index());
+        }
+
+        self.step_down(term);
+        self.leader = Some(leader);
+        self.deadline = Instant::now() + election_timeout();
+        if index <= self.commit {
+            return (self.term, true, index);
+        }
+        if registry.load_values(&values).is_err() {
+            return (self.term, false, self.last_index());
+        }
+
+        let mut log = vec![LogEntry::noop(last_term)];
+        if self.term_at(index) == Some(last_term) {
+            log.extend(self.log.split_off(index - self.offset + 1));
+        }
+        self.log = log;
+        (self.offset, self.commit, self.applied) = (index, index, index);
+        self.snapshot = values;
+        (self.term, true, index)
+    }
+
+    /// Processes the answer of a follower to the entries sent to it.
+    ///
+    /// # Returns:
+    ///
+    /// Whether new entries were committed and applied.
+    pub fn append_reply(
+        &mut self,
+        peer: usize,
+        (term, success, index): (u64, bool, usize),
+        registry: &Registry,
+    ) -> bool {
+        if term > self.term {
+            self.step_down(term);
+            return false;
+        }
+
+        if self.role != RaftRole::Leader || term != self.term {
+            return false;
+        }
+
+        if !success {
+            self.next_index[peer] = (self.next_index[peer] - 1).min(index + 1).max(1);
+            return false;
+        }
+
+        self.match_index[peer] = self.match_index[peer].max(index);
+        self.next_index[peer] = self.match_index[peer] + 1;
+        self.advance_commit(registry)
+    }
+
+    /// Appends an entry to the log of the leader, and persists it.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::StorageFailure` if the entry cannot be persisted, in
+    /// which case it is removed from the log.
+    ///
+    /// # Returns:
+    ///
+    /// The index of the entry.
+    pub fn push(
+        &mut self,
+        register: &str,
+        messages: Vec<String>,
+        client: u64,
+        registry: &Registry,
+    ) -> Result<usize, CalculatorErrors> {
+        self.log.push(LogEntry {
+            term: self.term,
+            client,
+            register: register.to_owned(),
+            messages,
+        });
+        if let Err(e) = self.persist() {
+            self.log.pop();
+            return Err(e);
+        }
+
+        let index = self.last_index();
+        self.results.insert(index, (self.term, None));
+        self.advance_commit(registry);
+        Ok(index)
+    }
+
+    /// Takes the result of applying the entry with the given index, once it was applied.
+    ///
+    /// # Returns:
+    ///
+    /// `None` if the entry was not applied yet, or the result of applying it, which is a
+    /// `CalculatorErrors::NotLeader` error if the entry was replaced by another leader.
+    pub fn take_result(&mut self, index: usize, term: u64) -> EntryResult {
+        if self.applied < index && self.role == RaftRole::Leader && self.term == term {
+            return None;
+        }
+
+        let result = match self.results.remove(&index) {
+            Some((_, Some(result))) => result,
+            _ => Err(CalculatorErrors::NotLeader(None)),
+        };
+        Some(result)
+    }
+
+    /// Makes the node a follower, moving to the given term if it is newer.
+    fn step_down(&mut self, term: u64) {
+        if term > self.term {
+            self.term = term;
+            self.voted_for = None;
+            self.leader = None;
+        }
+        self.role = RaftRole::Follower;
+    }
+
+    /// Makes the node the leader of the current term, appending a no-op entry so that
+    /// the entries of the previous terms are committed.
+    fn become_leader(&mut self) {
+        self.role = RaftRole::Leader;
+        self.leader = Some(self.me);
+        self.log.push(LogEntry::noop(self.term));
+
+        let last = self.last_index();
+        self.next_index = vec![last; self.size];
+        self.match_index = vec![0; self.size];
+        // If the no-op cannot be persisted now, a later call persists and counts it.
+        let _ = self.persist();
+    }
+
+    /// Drops the sent entries that the snapshot replaces, which are committed and so agree
+    /// with the log, so that the rest follow the last entry of the snapshot.
+    ///
+    /// # Returns:
+    ///
+    /// The index and term of the entry that precedes the remaining entries, and the
+    /// remaining entries.
+    fn skip_compacted(
+        &self,
+        prev: (usize, u64),
+        mut entries: Vec<LogEntry>,
+    ) -> ((usize, u64), Vec<LogEntry>) {
+        let (prev_index, _) = prev;
+        if prev_index >= self.offset {
+            return (prev, entries);
+        }
+
+        let skipped = (self.offset - prev_index).min(entries.len());
+        entries.drain(..skipped);
+        ((self.offset, self.log[0].term), entries)
+    }
+
+    /// Appends the entries sent by the leader after the given index, removing any
+    /// conflicting entry.
+    ///
+    /// # Returns:
+    ///
+    /// The index of the last entry sent.
+    fn merge(&mut self, prev_index: usize, entries: Vec<LogEntry>) -> usize {
+        let mut index = prev_index;
+        for entry in entries {
+            index += 1;
+            match self.term_at(index) {
+                Some(term) if term == entry.term => continue,
+                Some(_) => {
+                    self.log.truncate(index - self.offset);
+                    self.persisted = self.persisted.min(index);
+                }
+                None => (),
+            }
+            self.log.push(entry);
+        }
+        index
+    }
+
+    /// Commits the entries of the current term replicated to a majority, and applies them.
+    fn advance_commit(&mut self, registry: &Registry) -> bool {
+        let replicated = |index: usize| self.match_index.iter().filter(|m| **m >= index).count();
+        let committed = (self.commit + 1..=self.last_index()).rev().find(|index| {
+            self.term_at(*index) == Some(self.term) && self.is_majority(replicated(*index))
+        });
+
+        match committed {
+            Some(index) => {
+                self.commit = index;
+                self.apply(registry);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Applies the committed entries that were not applied yet, and replaces them with a
+    /// snapshot once there are enough of them. The result of an entry is only kept for
+    /// its client if the entry is the one the client sent, from the same term.
+    fn apply(&mut self, registry: &Registry) {
+        while self.applied < self.commit {
+            self.applied += 1;
+            let entry = &self.log[self.applied - self.offset];
+            if entry.is_noop() {
+                continue;
+            }
+
+            let result = registry.execute(&entry.register, &entry.messages, entry.client);
+            if let Some((term, slot)) = self.results.get_mut(&self.applied) {
+                *slot = match *term == entry.term {
+                    true => Some(result),
+                    false => Some(Err(CalculatorErrors::NotLeader(None))),
+                };
+            }
+        }
+
+        if self.applied - self.offset >= self.interval {
+            self.compact(registry);
+        }
+    }
+
+    /// Replaces the applied entries with a snapshot of the registers, which is persisted
+    /// by the next call to `persist`. If the values cannot be read, the entries are kept.
+    fn compact(&mut self, registry: &Registry) {
+        let values = match registry.values() {
+            Ok(values) => values,
+            Err(_) => return,
+        };
+
+        self.log.drain(..self.applied - self.offset);
+        self.log[0] = LogEntry::noop(self.log[0].term);
+        self.offset = self.applied;
+        self.snapshot = values;
+    }
+
+    /// Restores a record of the storage.
+    ///
+    /// # Returns:
+    ///
+    /// `None` if the record is malformed.
+    fn restore(&mut self, record: &str) -> Option<()> {
+        let (kind, record) = record.split_once(' ')?;
+        let (first, rest) = record.split_once(' ')?;
+        match kind {
+            TERM_RECORD => {
+                self.term = first.parse().ok()?;
+                self.voted_for = match rest {
+                    NO_VOTE => None,
+                    vote => Some(vote.parse().ok()?),
+                };
+            }
+            ENTRY_RECORD => {
+                let index: usize = first.parse().ok()?;
+                if index <= self.offset || index > self.last_index() + 1 {
+                    return None;
+                }
+                self.log.truncate(index - self.offset);
+                self.log.push(rest.parse().ok()?);
+            }
+            SNAPSHOT_RECORD => {
+                self.offset = first.parse().ok()?;
+                self.log = vec![LogEntry::noop(rest.parse().ok()?)];
+                (self.commit, self.applied) = (self.offset, self.offset);
+            }
+            VALUE_RECORD => self.snapshot.push((first.to_owned(), rest.parse().ok()?)),
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// Returns the records that replace the storage after a snapshot: the snapshot, the
+    /// term and the vote, and every entry that follows the snapshot.
+    fn snapshot_records(&self) -> Vec<String> {
+        let last_term = self.log[0].term;
+        let mut records = vec![format!("{} {} {}", SNAPSHOT_RECORD, self.offset, last_term)];
+        for (register, value) in &self.snapshot {
+            records.push(format!("{} {} {}", VALUE_RECORD, register, value));
+        }
+        records.push(self.term_record());
+        records.extend(self.entry_records(self.offset + 1));
+        records
+    }
+
+    /// Returns the record with the current term and vote.
+    fn term_record(&self) -> String {
+        let vote = self
+            .voted_for
+            .map_or(NO_VOTE.to_owned(), |vote| vote.to_string());
+        format!("{} {} {}", TERM_RECORD, self.term, vote)
+    }
+
+    /// Returns the records of the entries from the given index on.
+    fn entry_records(&self, from: usize) -> Vec<String> {
+        let entries = self
+            .log
+            .iter()
+            .enumerate()
+            .skip(from.saturating_sub(self.offset));
+        entries
+            .map(|(index, entry)| format!("{} {} {}", ENTRY_RECORD, index + self.offset, entry))
+            .collect()
+    }
+
+    /// Returns the message that sends the snapshot to a follower.
+    fn snapshot_message(&self) -> String {
+        let mut message = format!(
+            "SNAPSHOT {} {} {} {}",
+            self.term, self.me, self.offset, self.log[0].term
+        );
+        for (register, value) in &self.snapshot {
+            message.push_str(&format!("\t{} {}", register, value));
+        }
+        message
+    }
+
+    /// Returns the index of the last entry of the log.
+    fn last_index(&self) -> usize {
+        self.offset + self.log.len() - 1
+    }
+
+    /// Returns the term of the last entry of the log.
+    fn last_term(&self) -> u64 {
+        self.log.last().map_or(0, |entry| entry.term)
+    }
+
+    /// Returns the term of the entry with the given index, or of the last entry replaced
+    /// by the snapshot, or `None` if there is no such entry in the log.
+    fn term_at(&self, index: usize) -> Option<u64> {
+        let entry = index.checked_sub(self.offset).and_then(|i| self.log.get(i));
+        entry.map(|entry| entry.term)
+    }
+
+    /// Returns whether the given number of nodes is a majority of the cluster.
+    fn is_majority(&self, count: usize) -> bool {
+        count * 2 > self.size
+    }
+}
+
+/// Returns a random election timeout, so that the nodes rarely start elections at once.
+fn election_timeout() -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    Duration::from_millis(MIN_ELECTION_TIMEOUT + random % MIN_ELECTION_TIMEOUT)
+}
+
+#[cfg(test)]
+fn entry(term: u64, message: &str) -> LogEntry {
+    LogEntry {
+        term,
+        client: 1,
+        register: "default".to_owned(),
+        messages: vec![message.to_owned()],
+    }
+}
+
+#[cfg(test)]
+fn value(registry: &Registry) -> i128 {
+    registry
+        .get_or_create("default")
+        .unwrap()
+        .lock()
+        .unwrap()
+        .value()
+}
+
+#[test]
+fn test_single_node_elects_itself_and_commits() {
+    let registry = Registry::default();
+    let mut state = RaftState::new(0, 1, 100);
+    state.start_election();
+    assert_eq!(state.role(), RaftRole::Leader);
+
+    let index = state
+        .push("default", vec!["OP + 5".to_owned()], 1, &registry)
+        .unwrap();
+    assert_eq!(value(&registry), 5);
+    assert!(matches!(
+        state.take_result(index, 1),
+        Some(Ok(Response::Ok))
+    ));
+}
+
+#[test]
+fn test_candidate_needs_majority() {
+    let registry = Registry::default();
+    let mut state = RaftState::new(0, 3, 100);
+    state.start_election();
+    assert_eq!(state.role(), RaftRole::Candidate);
+    assert!(state.vote_reply(1, true));
+    assert_eq!(state.role(), RaftRole::Leader);
+
+    state
+        .push("default", vec!["OP + 5".to_owned()], 1, &registry)
+        .unwrap();
+    assert_eq!(value(&registry), 0);
+    assert!(state.append_reply(1, (1, true, 2), &registry));
+    assert_eq!(value(&registry), 5);
+}
+
+#[test]
+fn test_vote_once_per_term() {
+    let mut state = RaftState::new(0, 3, 100);
+    assert_eq!(state.vote(1, 1, 0, 0), (1, true));
+    assert_eq!(state.vote(1, 2, 0, 0), (1, false));
+    assert_eq!(state.vote(2, 2, 0, 0), (2, true));
+}
+
+#[test]
+fn test_vote_rejects_outdated_log() {
+    let registry = Registry::default();
+    let mut state = RaftState::new(0, 3, 100);
+    state.append((2, 1), (0, 0), 0, vec![entry(2, "OP + 1")], &registry);
+    assert_eq!(state.vote(3, 2, 5, 1), (3, false));
+    assert_eq!(state.vote(3, 2, 1, 2), (3, true));
+}
+
+#[test]
+fn test_append_applies_committed_entries() {
+    let registry = Registry::default();
+    let mut state = RaftState::new(1, 3, 100);
+    let entries = vec![entry(1, "OP + 5"), entry(1, "OP * 2")];
+    assert_eq!(
+        state.append((1, 0), (0, 0), 1, entries, &registry),
+        (1, true, 2)
+    );
+    assert_eq!(value(&registry), 5);
+    assert_eq!(state.leader(), Some(0));
+
+    assert_eq!(
+        state.append((1, 0), (2, 1), 2, vec![], &registry),
+        (1, true, 2)
+    );
+    assert_eq!(value(&registry), 10);
+}
+
+#[test]
+fn test_append_rejects_mismatched_log() {
+    let registry = Registry::default();
+    let mut state = RaftState::new(1, 3, 100);
+    assert_eq!(
+        state.append((1, 0), (3, 1), 0, vec![], &registry),
+        (1, false, 0)
+    );
+    assert!(!state.append((0, 0), (0, 0), 0, vec![], &registry).1);
+}
+
+#[test]
+fn test_append_replaces_conflicting_entries() {
+    let registry = Registry::default();
+    let mut state = RaftState::new(1, 3, 100);
+    state.append(
+        (1, 0),
+        (0, 0),
+        0,
+        vec![entry(1, "OP + 1"), entry(1, "OP + 2")],
+        &registry,
+    );
+    state.append(
+        (2, 2),
+        (1, 1),
+        3,
+        vec![entry(2, "OP * 3"), entry(2, "OP + 4")],
+        &registry,
+    );
+    assert_eq!(value(&registry), 7);
+}
+
+#[test]
+fn test_leader_steps_down_on_newer_term() {
+    let registry = Registry::default();
+    let mut state = RaftState::new(0, 3, 100);
+    state.start_election();
+    state.vote_reply(1, true);
+    let index = state
+        .push("default", vec!["OP + 1".to_owned()], 1, &registry)
+        .unwrap();
+    assert!(state.take_result(index, 1).is_none());
+
+    state.append((5, 1), (0, 0), 0, vec![], &registry);
+    assert_eq!(state.role(), RaftRole::Follower);
+    match state.take_result(index, 1) {
+        Some(Err(CalculatorErrors::NotLeader(_))) => (),
+        _ => panic!("Should throw NotLeader error"),
+    }
+    assert_eq!(value(&registry), 0);
+}
+
+#[test]
+fn test_state_survives_restart() {
+    let dir = std::env::temp_dir().join(format!("tpi_raft_state_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let dir = dir.to_string_lossy().into_owned();
+    let registry = Registry::default();
+
+    let mut state = RaftState::new(1, 3, 100);
+    state
+        .attach_storage(Storage::open(&dir, 10).unwrap())
+        .unwrap();
+    let entries = vec![entry(1, "OP + 1"), entry(1, "OP + 2")];
+    state.append((1, 0), (0, 0), 0, entries, &registry);
+    state.persist().unwrap();
+    state.append((2, 2), (1, 1), 0, vec![entry(2, "OP * 3")], &registry);
+    state.persist().unwrap();
+    assert_eq!(state.vote(3, 2, 2, 2), (3, true));
+    state.persist().unwrap();
+
+    let mut restarted = RaftState::new(1, 3, 100);
+    restarted
+        .attach_storage(Storage::open(&dir, 10).unwrap())
+        .unwrap();
+    assert_eq!(restarted.term(), 3);
+    assert_eq!(restarted.vote(3, 0, 2, 2), (3, false));
+    assert_eq!(
+        restarted.log,
+        vec![LogEntry::noop(0), entry(1, "OP + 1"), entry(2, "OP * 3")]
+    );
+
+    restarted.append((3, 2), (2, 2), 2, vec![], &registry);
+    assert_eq!(value(&registry), 3);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_applied_entries_replaced_by_snapshot() {
+    let registry = Registry::default();
+    let mut state = RaftState::new(0, 1, 2);
+    state.start_election();
+    state
+        .push("default", vec!["OP + 5".to_owned()], 1, &registry)
+        .unwrap();
+    assert_eq!(state.log.len(), 1);
+    assert_eq!(state.offset, 2);
+    assert_eq!(state.snapshot(), &vec![("default".to_owned(), 5)]);
+
+    state
+        .push("default", vec!["OP * 2".to_owned()], 1, &registry)
+        .unwrap();
+    assert_eq!(state.vote_message(), "VOTE 1 0 3 1");
+    assert_eq!(value(&registry), 10);
+}
+
+#[test]
+fn test_lagging_follower_installs_snapshot() {
+    let leader_registry = Registry::default();
+    let mut leader = RaftState::new(0, 3, 2);
+    leader.start_election();
+    leader.vote_reply(1, true);
+    leader
+        .push("default", vec!["OP + 5".to_owned()], 1, &leader_registry)
+        .unwrap();
+    leader.append_reply(1, (1, true, 2), &leader_registry);
+    assert_eq!(leader.append_message(2), "SNAPSHOT 1 0 2 1\tdefault 5");
+
+    let registry = Registry::default();
+    let mut follower = RaftState::new(2, 3, 2);
+    let values = vec![("default".to_owned(), 5)];
+    assert_eq!(
+        follower.install((1, 0), (2, 1), values, &registry),
+        (1, true, 2)
+    );
+    assert_eq!(value(&registry), 5);
+    assert_eq!(follower.applied(), 2);
+
+    let entries = vec![entry(1, "OP * 3")];
+    assert_eq!(
+        follower.append((1, 0), (2, 1), 3, entries, &registry),
+        (1, true, 3)
+    );
+    assert_eq!(value(&registry), 15);
+}
+
+#[test]
+fn test_append_skips_entries_replaced_by_snapshot() {
+    let registry = Registry::default();
+    let mut state = RaftState::new(1, 3, 1);
+    let entries = vec![entry(1, "OP + 1"), entry(1, "OP + 2")];
+    state.append((1, 0), (0, 0), 1, entries, &registry);
+    assert_eq!(state.offset, 1);
+
+    let entries = vec![entry(1, "OP + 1"), entry(1, "OP + 2"), entry(1, "OP + 3")];
+    assert_eq!(
+        state.append((1, 0), (0, 0), 3, entries, &registry),
+        (1, true, 3)
+    );
+    assert_eq!(value(&registry), 6);
+}
+
+#[test]
+fn test_snapshot_survives_restart() {
+    let dir = std::env::temp_dir().join(format!("tpi_raft_snapshot_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let dir = dir.to_string_lossy().into_owned();
+    let registry = Registry::default();
+
+    let mut state = RaftState::new(1, 3, 2);
+    state
+        .attach_storage(Storage::open(&dir, 2).unwrap())
+        .unwrap();
+    let entries = vec![entry(1, "OP + 1"), entry(1, "OP + 2"), entry(1, "OP * 3")];
+    state.append((1, 0), (0, 0), 2, entries, &registry);
+    state.persist().unwrap();
+
+    let mut restarted = RaftState::new(1, 3, 2);
+    restarted
+        .attach_storage(Storage::open(&dir, 2).unwrap())
+        .unwrap();
+    assert_eq!(restarted.snapshot(), &vec![("default".to_owned(), 3)]);
+    assert_eq!(restarted.applied(), 2);
+    assert_eq!(restarted.log, vec![LogEntry::noop(1), entry(1, "OP * 3")]);
+
+    let restored = Registry::default();
+    restored.load_values(restarted.snapshot()).unwrap();
+    restarted.append((1, 0), (3, 1), 3, vec![], &restored);
+    assert_eq!(value(&restored), 9);
+    let _ = std::fs::remove_dir_all(&dir);
+}
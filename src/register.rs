@@ -0,0 +1,353 @@
+use crate::{
+    calculator::Calculator, errors::CalculatorErrors, history_entry::HistoryEntry,
+    operation::Operation,
+};
+use std::collections::VecDeque;
+
+/// The number of history entries kept by a register unless configured otherwise.
+pub const DEFAULT_HISTORY_CAPACITY: usize = 1000;
+
+/// The number of values a register can undo unless configured otherwise.
+pub const DEFAULT_UNDO_DEPTH: usize = 100;
+
+/// A named calculator of the server, together with the log of the operations applied to it
+/// and the snapshots of its previous values.
+///
+/// Only the most recent entries are kept in the history, up to its capacity, and only the
+/// most recent values can be undone, up to its undo depth. Undo is linear and shared by
+/// every client: `UNDO` reverts the last change to the register, whoever made it.
+#[derive(Debug)]
+pub struct Register {
+    calculator: Calculator,
+    history: VecDeque<HistoryEntry>,
+    capacity: usize,
+    sequence: u64,
+    undo: VecDeque<i128>,
+    redo: Vec<i128>,
+    depth: usize,
+}
+
+impl Register {
+    /// Creates a register with an empty history.
+    ///
+    /// # Arguments:
+    ///
+    /// * `calculator` - The calculator of the register.
+    /// * `capacity` - The maximum number of entries kept in the history.
+    /// * `depth` - The maximum number of values that can be undone.
+    pub fn new(calculator: Calculator, capacity: usize, depth: usize) -> Self {
+        Self {
+            calculator,
+            history: VecDeque::new(),
+            capacity,
+            sequence: 0,
+            undo: VecDeque::new(),
+            redo: vec![],
+            depth,
+        }
+    }
+
+    /// Returns the current value of the register.
+    pub fn value(&self) -> i128 {
+        self.calculator.value()
+    }
+
+    /// Replaces the value of the register without recording the change, as when it is
+    /// restored from a snapshot.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::Overflow` if the value does not fit in the width of the
+    /// register and the policy is checked.
+    pub fn load(&mut self, value: i128) -> Result<(), CalculatorErrors> {
+        self.calculator.apply(Operation::Set(value))?;
+        Ok(())
+    }
+
+    /// Applies an operation to the calculator, recording it in the history if it modifies
+    /// the value.
+    ///
+    /// # Arguments:
+    ///
+    /// * `op` - The operation to apply.
+    /// * `client` - The id of the client that sent the operation.
+    ///
+    /// # Errors:
+    ///
+    /// Returns the error produced by the calculator, in which case nothing is recorded.
+    pub fn apply(&mut self, op: Operation, client: u64) -> Result<Option<i128>, CalculatorErrors> {
+        if op == Operation::Get {
+            return self.calculator.apply(op);
+        }
+
+        let operation = op.to_string();
+        let previous = self.calculator.value();
+        self.calculator.apply(op)?;
+        self.save(previous);
+        self.record(operation, client, self.calculator.value());
+        Ok(None)
+    }
+
+    /// Applies a sequence of operations atomically, recording each of them in the history
+    /// if all of them succeed.
+    ///
+    /// # Errors:
+    ///
+    /// Returns the error produced by the calculator, in which case nothing is recorded.
+    pub fn apply_all(&mut self, ops: Vec<Operation>, client: u64) -> Result<(), CalculatorErrors> {
+        let operations: Vec<String> = ops.iter().map(|op| op.to_string()).collect();
+        let previous = self.calculator.value();
+        let values = self.calculator.apply_all(ops)?;
+        self.save(previous);
+
+        for (operation, value) in operations.into_iter().zip(values) {
+            self.record(operation, client, value);
+        }
+        Ok(())
+    }
+
+    /// Computes the value an operation would produce from the given value of the register,
+    /// without applying it.
+    ///
+    /// # Arguments:
+    ///
+    /// * `value` - The value the operation starts from.
+    /// * `op` - The operation.
+    ///
+    /// # Errors:
+    ///
+    /// Returns the error `apply` would return.
+    pub fn check(&self, value: i128, op: &Operation) -> Result<i128, CalculatorErrors> {
+        self.calculator.with_value(value).compute(op)
+    }
+
+    /// Checks that a sequence of operations would be applied, without applying them.
+    ///
+    /// # Errors:
+    ///
+    /// Returns the error `apply_all` would return.
+    pub fn check_all(&self, ops: &[Operation]) -> Result<(), CalculatorErrors> {
+        let mut value = self.value();
+        for (index, op) in ops.iter().enumerate() {
+            value = match self.check(value, op) {
+                Ok(value) => value,
+                Err(e) => return Err(CalculatorErrors::TransactionAborted(index + 1, Box::new(e))),
+            };
+        }
+        Ok(())
+    }
+
+    /// Returns the value `undo` or `redo` would restore, without restoring it.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::NothingToUndo` or `CalculatorErrors::NothingToRedo` if
+    /// there is no value to restore.
+    pub fn restorable(&self, redo: bool) -> Result<i128, CalculatorErrors> {
+        match (redo, self.undo.back(), self.redo.last()) {
+            (false, Some(previous), _) => Ok(*previous),
+            (false, None, _) => Err(CalculatorErrors::NothingToUndo),
+            (true, _, Some(next)) => Ok(*next),
+            (true, _, None) => Err(CalculatorErrors::NothingToRedo),
+        }
+    }
+
+    /// Restores the value the register had before its last change.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::NothingToUndo` if there is no previous value.
+    pub fn undo(&mut self, client: u64) -> Result<(), CalculatorErrors> {
+        let previous = match self.undo.pop_back() {
+            Some(previous) => previous,
+            None => return Err(CalculatorErrors::NothingToUndo),
+        };
+
+        self.redo.push(self.calculator.value());
+        self.restore(previous, "UNDO", client)
+    }
+
+    /// Restores the value the register had before the last `undo`.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::NothingToRedo` if there is no undone value, or if the
+    /// register changed since it was undone.
+    pub fn redo(&mut self, client: u64) -> Result<(), CalculatorErrors> {
+        let next = match self.redo.pop() {
+            Some(next) => next,
+            None => return Err(CalculatorErrors::NothingToRedo),
+        };
+
+        self.push_undo(self.calculator.value());
+        self.restore(next, "REDO", client)
+    }
+
+    /// Returns the last entries of the history, oldest first.
+    ///
+    /// # Arguments:
+    ///
+    /// * `count` - The maximum number of entries to return.
+    pub fn history(&self, count: usize) -> Vec<HistoryEntry> {
+        let skip = self.history.len().saturating_sub(count);
+        self.history.iter().skip(skip).cloned().collect()
+    }
+
+    /// Replaces the value of the calculator, recording the change in the history.
+    fn restore(&mut self, value: i128, message: &str, client: u64) -> Result<(), CalculatorErrors> {
+        self.calculator.apply(Operation::Set(value))?;
+        self.record(message.to_owned(), client, value);
+        Ok(())
+    }
+
+    /// Saves the value prior to a change, so that it can be undone. Any undone value can no
+    /// longer be redone.
+    fn save(&mut self, previous: i128) {
+        self.push_undo(previous);
+        self.redo.clear();
+    }
+
+    /// Pushes a value to the undo stack, discarding the oldest one if it is full.
+    fn push_undo(&mut self, value: i128) {
+        if self.undo.len() == self.depth {
+            self.undo.pop_front();
+        }
+        if self.depth > 0 {
+            self.undo.push_back(value);
+        }
+    }
+
+    /// Appends an entry to the history, discarding the oldest one if it is full.
+    fn record(&mut self, operation: String, client: u64, value: i128) {
+        self.sequence += 1;
+        if self.history.len() == self.capacity {
+            self.history.pop_front();
+        }
+        if self.capacity > 0 {
+            self.history.push_back(HistoryEntry {
+                sequence: self.sequence,
+                client,
+                operation,
+                value,
+            });
+        }
+    }
+}
+
+#[test]
+fn test_apply_records_history() {
+    let mut register = Register::new(Calculator::default(), 10, 10);
+    register.apply(Operation::Add(5), 1).unwrap();
+    register.apply(Operation::Get, 1).unwrap();
+    register.apply(Operation::Mul(3), 2).unwrap();
+
+    let history = register.history(10);
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].to_string(), "ENTRY 1 1 5 OP + 5");
+    assert_eq!(history[1].to_string(), "ENTRY 2 2 15 OP * 3");
+}
+
+#[test]
+fn test_apply_failure_not_recorded() {
+    let mut register = Register::new(Calculator::default(), 10, 10);
+    assert!(register.apply(Operation::Div(0), 1).is_err());
+    assert!(register.history(10).is_empty());
+}
+
+#[test]
+fn test_apply_all_records_intermediate_values() {
+    let mut register = Register::new(Calculator::default(), 10, 10);
+    register
+        .apply_all(vec![Operation::Add(2), Operation::Pow(3)], 4)
+        .unwrap();
+
+    let values: Vec<i128> = register.history(10).iter().map(|e| e.value).collect();
+    assert_eq!(values, vec![2, 8]);
+}
+
+#[test]
+fn test_history_keeps_last_entries() {
+    let mut register = Register::new(Calculator::default(), 3, 10);
+    for _ in 0..5 {
+        register.apply(Operation::Add(1), 1).unwrap();
+    }
+
+    let sequences: Vec<u64> = register.history(2).iter().map(|e| e.sequence).collect();
+    assert_eq!(sequences, vec![4, 5]);
+    assert_eq!(register.history(10).len(), 3);
+}
+
+#[test]
+fn test_undo_and_redo() {
+    let mut register = Register::new(Calculator::default(), 10, 10);
+    register.apply(Operation::Add(5), 1).unwrap();
+    register.apply(Operation::Mul(0), 1).unwrap();
+
+    register.undo(1).unwrap();
+    assert_eq!(register.value(), 5);
+    register.undo(1).unwrap();
+    assert_eq!(register.value(), 0);
+    register.redo(1).unwrap();
+    assert_eq!(register.value(), 5);
+    register.redo(1).unwrap();
+    assert_eq!(register.value(), 0);
+}
+
+#[test]
+fn test_undo_empty() {
+    let mut register = Register::new(Calculator::default(), 10, 10);
+    match register.undo(1) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::NothingToUndo => (),
+            _ => panic!("Should throw NothingToUndo error"),
+        },
+    }
+}
+
+#[test]
+fn test_redo_discarded_after_change() {
+    let mut register = Register::new(Calculator::default(), 10, 10);
+    register.apply(Operation::Add(5), 1).unwrap();
+    register.undo(1).unwrap();
+    register.apply(Operation::Add(1), 2).unwrap();
+    match register.redo(1) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::NothingToRedo => (),
+            _ => panic!("Should throw NothingToRedo error"),
+        },
+    }
+    assert_eq!(register.value(), 1);
+}
+
+#[test]
+fn test_undo_transaction_as_a_unit() {
+    let mut register = Register::new(Calculator::default(), 10, 10);
+    register.apply(Operation::Add(1), 1).unwrap();
+    register
+        .apply_all(vec![Operation::Add(2), Operation::Mul(10)], 1)
+        .unwrap();
+    register.undo(1).unwrap();
+    assert_eq!(register.value(), 1);
+}
+
+#[test]
+fn test_undo_depth() {
+    let mut register = Register::new(Calculator::default(), 10, 2);
+    for _ in 0..4 {
+        register.apply(Operation::Add(1), 1).unwrap();
+    }
+    register.undo(1).unwrap();
+    register.undo(1).unwrap();
+    assert!(register.undo(1).is_err());
+    assert_eq!(register.value(), 2);
+}
+
+#[test]
+fn test_undo_recorded_in_history() {
+    let mut register = Register::new(Calculator::default(), 10, 10);
+    register.apply(Operation::Add(5), 1).unwrap();
+    register.undo(2).unwrap();
+    assert_eq!(register.history(1)[0].to_string(), "ENTRY 2 2 0 UNDO");
+}
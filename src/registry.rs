@@ -0,0 +1,877 @@
+use crate::{
+    atomic_calculator::AtomicCalculator,
+    backend::Backend,
+    calculator::Calculator,
+    errors::CalculatorErrors,
+    log_event::LogEvent,
+    logger::Logger,
+    operation::Operation,
+    overflow::Overflow,
+    raft::Raft,
+    register::{DEFAULT_HISTORY_CAPACITY, DEFAULT_UNDO_DEPTH, Register},
+    replica_set::ReplicaSet,
+    request::Request,
+    request_cache::RequestCache,
+    response::Response,
+    session_table::SessionTable,
+    storage::{Records, Storage, Values},
+    width::Width,
+};
+use std::{
+    collections::HashMap,
+    net::TcpStream,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+
+/// The name of the register used by a client that has not selected one.
+pub const DEFAULT_REGISTER: &str = "default";
+
+/// The number of registers a server holds unless configured otherwise.
+pub const DEFAULT_MAX_REGISTERS: usize = 10000;
+
+/// The client id recorded in the history for the operations recovered from storage.
+pub const RECOVERED_CLIENT: u64 = 0;
+
+/// A registry of named calculators shared by every client of the server.
+///
+/// Each register is an independent `Calculator` with its own history, created lazily
+/// the first time its name is requested. Clients can only create registers up to the
+/// maximum of the registry, while the changes from storage, from a primary or from the
+/// cluster always create theirs, so that every copy of the state agrees. Every register operates on the same numeric
+/// width and overflow policy. The registry also hands out the ids of the clients and,
+/// if the server has a data directory, persists every change to the registers.
+///
+/// The registry of a primary server forwards every change to its replicas. The registry
+/// of a replica is read-only for clients: it only changes with the records received from
+/// its primary, until it is promoted. Each promotion starts a new epoch: a replica does
+/// not follow a primary of an older epoch, and a primary fenced with a newer epoch becomes
+/// read-only. The registry of a node of a Raft cluster only
+/// changes with the entries committed by the cluster.
+///
+/// Once a client with the admin token asks the server to shut down, or the process
+/// receives `SIGTERM` or `SIGINT`, the registry reports it, so that the server stops
+/// accepting connections.
+///
+/// With the atomic backend, the value of each register is instead an `AtomicCalculator`,
+/// which clients change without locking. Such a registry keeps neither history nor undo
+/// stacks, and cannot be persisted nor replicated.
+#[derive(Debug)]
+pub struct Registry {
+    registers: Mutex<HashMap<String, Arc<Mutex<Register>>>>,
+    width: Width,
+    overflow: Overflow,
+    history: usize,
+    undo: usize,
+    next_client: AtomicU64,
+    storage: Option<Storage>,
+    replica: AtomicBool,
+    epoch: AtomicU64,
+    replicas: ReplicaSet,
+    cluster: Option<Raft>,
+    sessions: Mutex<SessionTable>,
+    shutdown: AtomicBool,
+    admin_token: Option<String>,
+    backend: Backend,
+    atomics: Mutex<HashMap<String, Arc<AtomicCalculator>>>,
+    max_registers: usize,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new(
+            Width::default(),
+            Overflow::default(),
+            DEFAULT_HISTORY_CAPACITY,
+            DEFAULT_UNDO_DEPTH,
+        )
+    }
+}
+
+impl Registry {
+    /// Creates an empty registry.
+    ///
+    /// # Arguments:
+    ///
+    /// * `width` - The numeric width of every register.
+    /// * `overflow` - The overflow policy of every register.
+    /// * `history` - The number of history entries kept by every register.
+    /// * `undo` - The number of values every register can undo.
+    pub fn new(width: Width, overflow: Overflow, history: usize, undo: usize) -> Self {
+        Self {
+            registers: Mutex::new(HashMap::new()),
+            width,
+            overflow,
+            history,
+            undo,
+            next_client: AtomicU64::new(1),
+            storage: None,
+            replica: AtomicBool::new(false),
+            epoch: AtomicU64::new(0),
+            replicas: ReplicaSet::default(),
+            cluster: None,
+            sessions: Mutex::new(SessionTable::default()),
+            shutdown: AtomicBool::new(false),
+            admin_token: None,
+            backend: Backend::default(),
+            atomics: Mutex::new(HashMap::new()),
+            max_registers: DEFAULT_MAX_REGISTERS,
+        }
+    }
+
+    /// Restores the registers from the storage, and persists every later change to it. If
+    /// the log ends with an incomplete transaction, a snapshot replaces the log, so that the
+    /// next records are not taken as part of the transaction.
+    ///
+    /// # Arguments:
+    ///
+    /// * `storage` - The storage of the server.
+    /// * `logger` - The logger of the server, which logs the records that fail.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::StorageFailure` if the storage cannot be read.
+    pub fn attach_storage(
+        &mut self,
+        storage: Storage,
+        logger: &Logger,
+    ) -> Result<(), CalculatorErrors> {
+        let (values, records) = storage.recover()?;
+        self.load_values(&values)?;
+
+        let incomplete = self.replay(records, logger)?;
+        self.storage = Some(storage);
+        if incomplete {
+            self.snapshot()?;
+        }
+        Ok(())
+    }
+
+    /// Sets how the values of the registers are shared between clients.
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    /// Sets the number of registers the clients can create.
+    pub fn set_max_registers(&mut self, max_registers: usize) {
+        self.max_registers = max_registers;
+    }
+
+    /// Returns how the values of the registers are shared between clients.
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Makes the registry a node of a Raft cluster, loading the registers of the snapshot
+    /// the node restored. The threads of the node must be started with `Raft::spawn` once
+    /// the registry is shared.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::LockFailure` if a register cannot be locked.
+    pub fn attach_cluster(&mut self, raft: Raft) -> Result<(), CalculatorErrors> {
+        raft.load_snapshot(self)?;
+        self.cluster = Some(raft);
+        Ok(())
+    }
+
+    /// Returns the node of the Raft cluster the registry belongs to, if any.
+    pub fn cluster(&self) -> Option<&Raft> {
+        self.cluster.as_ref()
+    }
+
+    /// Persists changes about to be applied to a register, and forwards them to the
+    /// replicas. Must be called while the register is locked and before the changes are
+    /// applied, so that they are logged in the order they are applied and a change that
+    /// cannot be logged is never seen by other clients.
+    ///
+    /// # Arguments:
+    ///
+    /// * `name` - The name of the register.
+    /// * `messages` - The protocol messages that reproduce the changes.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::StorageFailure` if the changes cannot be persisted.
+    ///
+    /// # Returns:
+    ///
+    /// Whether a snapshot is due.
+    pub fn persist(&self, name: &str, messages: &[String]) -> Result<bool, CalculatorErrors> {
+        let snapshot_due = match &self.storage {
+            Some(storage) => storage.append(name, messages)?,
+            None => false,
+        };
+
+        self.replicas.forward(name, messages)?;
+        Ok(snapshot_due)
+    }
+
+    /// Writes a snapshot of every register to the storage, if there is one. Every register
+    /// is locked while the snapshot is written.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::StorageFailure` if the snapshot cannot be written.
+    pub fn snapshot(&self) -> Result<(), CalculatorErrors> {
+        match &self.storage {
+            Some(storage) => self.with_values(|values| storage.snapshot(values)),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the value of every register. Every register is locked while the values are
+    /// read.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::LockFailure` if the registry or a register is poisoned.
+    pub fn values(&self) -> Result<Values, CalculatorErrors> {
+        let mut copy = vec![];
+        self.with_values(|values| {
+            copy.extend(values.iter().map(|(name, value)| (name.to_owned(), *value)));
+            Ok(())
+        })?;
+        Ok(copy)
+    }
+
+    /// Sets the value of the registers, creating the ones that do not exist.
+    ///
+    /// # Arguments:
+    ///
+    /// * `values` - The `(register, value)` pairs.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::LockFailure` if the registry or a register is poisoned.
+    pub fn load_values(&self, values: &Values) -> Result<(), CalculatorErrors> {
+        for (name, value) in values {
+            let register = self.get_or_create(name)?;
+            lock(&register)?.load(*value)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a replica that follows this server, sending it the current value of every
+    /// register. Every register is locked while the values are sent.
+    ///
+    /// # Arguments:
+    ///
+    /// * `stream` - The connection of the replica.
+    /// * `logger` - The logger of the server, which logs the replica if it is dropped.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::ReadOnlyReplica` if this server is a replica itself,
+    /// `CalculatorErrors::UnexpectedMessage` if the registry uses the atomic backend, or
+    /// `CalculatorErrors::WritingFailure` if the replica cannot be written.
+    pub fn add_replica(&self, stream: &TcpStream, logger: &Logger) -> Result<(), CalculatorErrors> {
+        if self.is_replica() {
+            return Err(CalculatorErrors::ReadOnlyReplica);
+        }
+        if self.backend == Backend::Atomic {
+            return Err(CalculatorErrors::UnexpectedMessage("REPLICATE".to_owned()));
+        }
+        self.with_values(|values| self.replicas.add(stream, values, self.epoch(), logger))
+    }
+
+    /// Persists changes received from the primary, and then applies them to a register. A
+    /// change that fails is logged anyway, and fails the same way when the log is replayed.
+    ///
+    /// # Arguments:
+    ///
+    /// * `name` - The name of the register.
+    /// * `messages` - Either a single change, or a complete `BEGIN` ... `COMMIT` block.
+    ///
+    /// # Errors:
+    ///
+    /// Returns the error produced while applying or persisting the changes.
+    pub fn replicate(&self, name: &str, messages: &[String]) -> Result<(), CalculatorErrors> {
+        let register = self.get_or_create(name)?;
+        let snapshot_due = {
+            let mut register = lock(&register)?;
+            let snapshot_due = self.persist(name, messages)?;
+            self.apply_messages(&mut register, messages, RECOVERED_CLIENT)?;
+            snapshot_due
+        };
+
+        if snapshot_due {
+            self.snapshot()?;
+        }
+        Ok(())
+    }
+
+    /// Applies a change committed by the Raft cluster to a register.
+    ///
+    /// # Arguments:
+    ///
+    /// * `name` - The name of the register.
+    /// * `messages` - Either a single change, a complete `BEGIN` ... `COMMIT` block, or a
+    ///   `BATCH <count>` message followed by the operations of a batch.
+    /// * `client` - The id of the client that sent the change.
+    ///
+    /// # Errors:
+    ///
+    /// Returns the error produced while applying the change, in which case the register
+    /// does not change. The operations of a batch that fail are reported in its summary
+    /// instead.
+    ///
+    /// # Returns:
+    ///
+    /// The response to the change, which summarizes it if it is a batch.
+    pub fn execute(
+        &self,
+        name: &str,
+        messages: &[String],
+        client: u64,
+    ) -> Result<Response, CalculatorErrors> {
+        let register = self.get_or_create(name)?;
+        let mut register = lock(&register)?;
+        match messages.split_first() {
+            Some((first, ops))
+                if matches!(Request::parse(first, self.width), Ok(Request::Batch(_))) =>
+            {
+                Ok(self.apply_batch(&mut register, ops, client))
+            }
+            _ => {
+                self.apply_messages(&mut register, messages, client)?;
+                Ok(Response::Ok)
+            }
+        }
+    }
+
+    /// Sets whether the server is a read-only replica.
+    pub fn set_replica(&self, replica: bool) {
+        self.replica.store(replica, Ordering::SeqCst);
+    }
+
+    /// Returns whether the server is a read-only replica.
+    pub fn is_replica(&self) -> bool {
+        self.replica.load(Ordering::SeqCst)
+    }
+
+    /// Sets the token that authorizes the administrative messages. Without one, every
+    /// administrative message is rejected.
+    pub fn set_admin_token(&mut self, token: Option<String>) {
+        self.admin_token = token;
+    }
+
+    /// Checks that the given token is the admin token of the server.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::Unauthorized` if the token is not the admin token, or
+    /// the server has none.
+    pub fn authorize(&self, token: &str) -> Result<(), CalculatorErrors> {
+        match &self.admin_token {
+            Some(admin_token) if admin_token == token => Ok(()),
+            _ => Err(CalculatorErrors::Unauthorized),
+        }
+    }
+
+    /// Returns the epoch of the server, which counts the promotions of the replicas it
+    /// followed or fenced, and its own.
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::SeqCst)
+    }
+
+    /// Promotes the server to primary, starting a new epoch, if it is a replica.
+    ///
+    /// # Returns:
+    ///
+    /// Whether the server was a replica.
+    pub fn promote(&self) -> bool {
+        let promoted = self.replica.swap(false, Ordering::SeqCst);
+        if promoted {
+            self.epoch.fetch_add(1, Ordering::SeqCst);
+        }
+        promoted
+    }
+
+    /// Checks the epoch of the primary the replica is about to follow, and takes it as the
+    /// epoch of the replica.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::StaleEpoch` if the primary was replaced by a promoted
+    /// replica, since its epoch is older than the replica's.
+    pub fn follow_epoch(&self, epoch: u64) -> Result<(), CalculatorErrors> {
+        let current = self.epoch.fetch_max(epoch, Ordering::SeqCst);
+        if epoch < current {
+            return Err(CalculatorErrors::StaleEpoch(current));
+        }
+        Ok(())
+    }
+
+    /// Fences the server, which was replaced by a replica promoted with the given epoch: it
+    /// becomes read-only, and drops its replicas so they look for the new primary.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::StaleEpoch` if the epoch is not newer than the server's,
+    /// or `CalculatorErrors::LockFailure` if the mutex of the replicas is poisoned.
+    pub fn fence(&self, epoch: u64) -> Result<(), CalculatorErrors> {
+        let current = self.epoch.fetch_max(epoch, Ordering::SeqCst);
+        if epoch <= current {
+            return Err(CalculatorErrors::StaleEpoch(current));
+        }
+        self.set_replica(true);
+        self.replicas.clear()
+    }
+
+    /// Returns the token that authorizes the administrative messages, if there is one.
+    pub fn admin_token(&self) -> Option<&str> {
+        self.admin_token.as_deref()
+    }
+
+    /// Records that the server must shut down.
+    pub fn request_shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns whether the server must shut down, because a client asked it to or the
+    /// process received `SIGTERM` or `SIGINT`.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst) || signal_received()
+    }
+
+    /// Returns a new client id, different from every id returned before.
+    pub fn next_client_id(&self) -> u64 {
+        self.next_client.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Returns the cache of the client session with the given token, creating it if it
+    /// does not exist. Only the most recently used sessions are kept.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::LockFailure` if the sessions mutex is poisoned.
+    pub fn client_session(
+        &self,
+        token: &str,
+    ) -> Result<Arc<Mutex<RequestCache>>, CalculatorErrors> {
+        let mut sessions = match self.sessions.lock() {
+            Ok(sessions) => sessions,
+            Err(_) => return Err(CalculatorErrors::LockFailure),
+        };
+
+        Ok(sessions.get_or_create(token))
+    }
+
+    /// Returns the numeric width of the registers.
+    pub fn width(&self) -> Width {
+        self.width
+    }
+
+    /// Returns the register with the given name, creating it if it does not exist.
+    ///
+    /// # Arguments:
+    ///
+    /// * `name` - The name of the register.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::LockFailure` if the registry mutex is poisoned.
+    pub fn get_or_create(&self, name: &str) -> Result<Arc<Mutex<Register>>, CalculatorErrors> {
+        self.lookup(name, usize::MAX)
+    }
+
+    /// Returns the register with the given name for a client, creating it if it does not
+    /// exist and the registry holds fewer registers than its maximum.
+    ///
+    /// # Arguments:
+    ///
+    /// * `name` - The name of the register.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::TooManyRegisters` if the register does not exist and the
+    /// registry is full, or `CalculatorErrors::LockFailure` if the registry mutex is
+    /// poisoned.
+    pub fn select(&self, name: &str) -> Result<Arc<Mutex<Register>>, CalculatorErrors> {
+        self.lookup(name, self.max_registers)
+    }
+
+    /// Returns the register with the given name, creating it if it does not exist and
+    /// there are fewer registers than the given maximum.
+    fn lookup(&self, name: &str, max: usize) -> Result<Arc<Mutex<Register>>, CalculatorErrors> {
+        let mut registers = match self.registers.lock() {
+            Ok(registers) => registers,
+            Err(_) => return Err(CalculatorErrors::LockFailure),
+        };
+        if !registers.contains_key(name) && registers.len() >= max {
+            return Err(CalculatorErrors::TooManyRegisters(max));
+        }
+
+        let register = registers.entry(name.to_owned()).or_insert_with(|| {
+            let calculator = Calculator::new(self.width, self.overflow);
+            Arc::new(Mutex::new(Register::new(
+                calculator,
+                self.history,
+                self.undo,
+            )))
+        });
+        Ok(Arc::clone(register))
+    }
+
+    /// Returns the atomic calculator of the register with the given name, creating it if
+    /// it does not exist, if the registry uses the atomic backend.
+    ///
+    /// # Arguments:
+    ///
+    /// * `name` - The name of the register.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::LockFailure` if the mutex of the atomic calculators is
+    /// poisoned, which is only locked to look them up.
+    pub fn atomic_calculator(
+        &self,
+        name: &str,
+    ) -> Result<Option<Arc<AtomicCalculator>>, CalculatorErrors> {
+        if self.backend != Backend::Atomic {
+            return Ok(None);
+        }
+        let mut atomics = match self.atomics.lock() {
+            Ok(atomics) => atomics,
+            Err(_) => return Err(CalculatorErrors::LockFailure),
+        };
+
+        let calculator = atomics
+            .entry(name.to_owned())
+            .or_insert_with(|| Arc::new(AtomicCalculator::new(self.width, self.overflow)));
+        Ok(Some(Arc::clone(calculator)))
+    }
+
+    /// Calls the given function with the value of every register, while every register
+    /// is locked.
+    fn with_values<F>(&self, f: F) -> Result<(), CalculatorErrors>
+    where
+        F: FnOnce(&Values) -> Result<(), CalculatorErrors>,
+    {
+        let registers = match self.registers.lock() {
+            Ok(registers) => registers,
+            Err(_) => return Err(CalculatorErrors::LockFailure),
+        };
+
+        let mut values = vec![];
+        let mut guards = vec![];
+        for (name, register) in registers.iter() {
+            let guard = lock(register)?;
+            values.push((name.to_owned(), guard.value()));
+            guards.push(guard);
+        }
+        f(&values)
+    }
+
+    /// Applies the protocol messages of a change to a register.
+    fn apply_messages(
+        &self,
+        register: &mut Register,
+        messages: &[String],
+        client: u64,
+    ) -> Result<(), CalculatorErrors> {
+        let mut transaction = None;
+        for message in messages {
+            let request = Request::parse(message, self.width)?;
+            apply_request(register, request, &mut transaction, client)?;
+        }
+        Ok(())
+    }
+
+    /// Applies the operations of a batch to a register, one by one. An operation that fails
+    /// does not stop the batch: its error is reported in the summary, along with its index
+    /// in the batch.
+    fn apply_batch(&self, register: &mut Register, messages: &[String], client: u64) -> Response {
+        let mut errors = vec![];
+        for (index, message) in messages.iter().enumerate() {
+            let result =
+                Operation::parse(message, self.width).and_then(|op| register.apply(op, client));
+            if let Err(e) = result {
+                errors.push((index + 1, e));
+            }
+        }
+
+        Response::Batch {
+            applied: messages.len() - errors.len(),
+            errors,
+            value: register.value(),
+        }
+    }
+
+    /// Applies the records recovered from the storage. Transactions are applied as a
+    /// unit, and a transaction left incomplete at the end of the log is discarded. The
+    /// records that fail are logged.
+    ///
+    /// # Returns:
+    ///
+    /// Whether a transaction was left incomplete.
+    fn replay(&self, records: Records, logger: &Logger) -> Result<bool, CalculatorErrors> {
+        let mut transaction = None;
+        for (name, message) in records {
+            let register = self.get_or_create(&name)?;
+            let result = match Request::parse(&message, self.width) {
+                Ok(request) => apply_request(
+                    &mut *lock(&register)?,
+                    request,
+                    &mut transaction,
+                    RECOVERED_CLIENT,
+                ),
+                Err(e) => Err(e),
+            };
+
+            if let Err(error) = result {
+                logger.log(LogEvent::RecordFailed {
+                    register: name,
+                    message,
+                    error,
+                });
+            }
+        }
+        Ok(transaction.is_some())
+    }
+}
+
+/// Applies a request recovered from the storage, received from the primary or committed
+/// by the cluster to a register, or queues it in the transaction being applied.
+fn apply_request(
+    register: &mut Register,
+    request: Request,
+    transaction: &mut Option<Vec<Operation>>,
+    client: u64,
+) -> Result<(), CalculatorErrors> {
+    match (request, transaction.take()) {
+        (Request::Begin, _) => *transaction = Some(vec![]),
+        (Request::Calc(op), Some(mut queue)) => {
+            queue.push(op);
+            *transaction = Some(queue);
+        }
+        (Request::Calc(op), None) => {
+            register.apply(op, client)?;
+        }
+        (Request::Commit, Some(queue)) => register.apply_all(queue, client)?,
+        (Request::Undo, None) => register.undo(client)?,
+        (Request::Redo, None) => register.redo(client)?,
+        _ => return Err(CalculatorErrors::StorageFailure),
+    }
+    Ok(())
+}
+
+/// Locks a register.
+fn lock(register: &Mutex<Register>) -> Result<MutexGuard<'_, Register>, CalculatorErrors> {
+    match register.lock() {
+        Ok(register) => Ok(register),
+        Err(_) => Err(CalculatorErrors::LockFailure),
+    }
+}
+
+/// Returns whether the process received `SIGTERM` or `SIGINT`.
+#[cfg(unix)]
+fn signal_received() -> bool {
+    crate::signal::received()
+}
+
+/// Returns `false`, since the termination signals are only handled on unix targets.
+#[cfg(not(unix))]
+fn signal_received() -> bool {
+    false
+}
+
+#[test]
+fn test_follow_epoch_rejects_stale_primary() {
+    let registry = Registry::default();
+    registry.follow_epoch(2).unwrap();
+    assert_eq!(registry.epoch(), 2);
+
+    match registry.follow_epoch(1) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::StaleEpoch(2) => (),
+            _ => panic!("Should throw StaleEpoch error, got: {:?}", e),
+        },
+    };
+    assert_eq!(registry.epoch(), 2);
+}
+
+#[test]
+fn test_execute_batch_reports_failed_operations() {
+    let registry = Registry::default();
+    let messages = ["BATCH 3", "OP + 5", "OP / 0", "OP * 2"].map(|m| m.to_owned());
+
+    match registry.execute("a", &messages, 1).unwrap() {
+        Response::Batch {
+            applied,
+            errors,
+            value,
+        } => {
+            assert_eq!(applied, 2);
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].0, 2);
+            assert_eq!(value, 10);
+        }
+        _ => panic!("Should return the summary of the batch"),
+    }
+}
+
+#[test]
+fn test_get_or_create_same_name_same_register() {
+    let registry = Registry::default();
+    let first = registry.get_or_create("a").unwrap();
+    let second = registry.get_or_create("a").unwrap();
+    assert!(Arc::ptr_eq(&first, &second));
+}
+
+#[test]
+fn test_get_or_create_different_names_are_independent() {
+    use crate::operation::Operation;
+
+    let registry = Registry::default();
+    let a = registry.get_or_create("a").unwrap();
+    let b = registry.get_or_create("b").unwrap();
+    a.lock().unwrap().apply(Operation::Add(5), 1).unwrap();
+    assert_eq!(a.lock().unwrap().value(), 5);
+    assert_eq!(b.lock().unwrap().value(), 0);
+}
+
+#[test]
+fn test_select_bounded_by_max_registers() {
+    let mut registry = Registry::default();
+    registry.set_max_registers(2);
+    registry.select("a").unwrap();
+    registry.select("b").unwrap();
+    registry.select("a").unwrap();
+
+    match registry.select("c") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::TooManyRegisters(2) => (),
+            _ => panic!("Should throw TooManyRegisters error, got: {:?}", e),
+        },
+    };
+    registry.get_or_create("c").unwrap();
+}
+
+#[test]
+fn test_get_or_create_uses_registry_width() {
+    use crate::operation::Operation;
+
+    let registry = Registry::new("u16".parse().unwrap(), Overflow::Checked, 10, 10);
+    let register = registry.get_or_create("a").unwrap();
+    register
+        .lock()
+        .unwrap()
+        .apply(Operation::Add(65535), 1)
+        .unwrap();
+    assert!(
+        register
+            .lock()
+            .unwrap()
+            .apply(Operation::Add(1), 1)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_next_client_id_is_unique() {
+    let registry = Registry::default();
+    assert_ne!(registry.next_client_id(), registry.next_client_id());
+}
+
+#[test]
+fn test_attach_storage_replays_log() {
+    let dir = std::env::temp_dir().join(format!("tpi_registry_replay_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let log =
+        "EPOCH 0\ndefault OP + 5\na BEGIN\na OP + 2\na COMMIT\ndefault BEGIN\ndefault OP + 1\n";
+    std::fs::write(dir.join("wal"), log).unwrap();
+
+    let mut registry = Registry::default();
+    let storage = Storage::open(&dir.to_string_lossy(), 10).unwrap();
+    registry
+        .attach_storage(storage, &Logger::default())
+        .unwrap();
+    assert_eq!(
+        registry
+            .get_or_create("default")
+            .unwrap()
+            .lock()
+            .unwrap()
+            .value(),
+        5
+    );
+    assert_eq!(
+        registry.get_or_create("a").unwrap().lock().unwrap().value(),
+        2
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_attach_storage_discards_incomplete_transaction() {
+    let dir = std::env::temp_dir().join(format!("tpi_registry_dangling_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("wal"), "EPOCH 0\na BEGIN\na OP + 2\n").unwrap();
+    let path = dir.to_string_lossy().into_owned();
+
+    let mut registry = Registry::default();
+    registry
+        .attach_storage(Storage::open(&path, 10).unwrap(), &Logger::default())
+        .unwrap();
+    registry.persist("a", &["OP + 5".to_owned()]).unwrap();
+
+    let mut restarted = Registry::default();
+    restarted
+        .attach_storage(Storage::open(&path, 10).unwrap(), &Logger::default())
+        .unwrap();
+    let value = restarted
+        .get_or_create("a")
+        .unwrap()
+        .lock()
+        .unwrap()
+        .value();
+    assert_eq!(value, 5);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_replicate_applies_blocks() {
+    let registry = Registry::default();
+    registry.replicate("a", &["OP + 5".to_owned()]).unwrap();
+    let block = ["BEGIN", "OP * 2", "OP + 1", "COMMIT"].map(|m| m.to_owned());
+    registry.replicate("a", &block).unwrap();
+    assert_eq!(
+        registry.get_or_create("a").unwrap().lock().unwrap().value(),
+        11
+    );
+}
+
+#[test]
+fn test_attach_storage_logs_failed_records() {
+    let dir = std::env::temp_dir().join(format!("tpi_registry_failed_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("wal"), "EPOCH 0\na OP + 2\na OP / 0\n").unwrap();
+    let log = dir.join("server.log").to_string_lossy().into_owned();
+    let (logger, handle) = Logger::spawn(&log, crate::log_level::LogLevel::Error, 1024).unwrap();
+
+    let mut registry = Registry::default();
+    registry
+        .attach_storage(Storage::open(&dir.to_string_lossy(), 10).unwrap(), &logger)
+        .unwrap();
+    {
+        let _closed = logger;
+    }
+    handle.join().unwrap();
+
+    let content = std::fs::read_to_string(&log).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(
+        content.contains("record a OP / 0 -> ERROR \"division by zero\""),
+        "Got: '{}'",
+        content
+    );
+}
@@ -0,0 +1,147 @@
+use crate::{
+    errors::CalculatorErrors, log_event::LogEvent, logger::Logger, operation::Operation,
+    storage::Values,
+};
+use std::{
+    io::Write,
+    net::TcpStream,
+    sync::{
+        Mutex, MutexGuard,
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+    },
+    thread,
+    time::Duration,
+};
+
+/// The time a write to a replica may block before the replica is dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+/// The number of changes that may wait to be written to a replica before it is dropped.
+const REPLICA_QUEUE: usize = 1024;
+
+/// The queue of the changes to write to a replica, and the logger that logs it if it is
+/// dropped.
+type Queue = (SyncSender<String>, Logger);
+
+/// The replicas following a primary server.
+///
+/// A replica connects to the primary and sends `REPLICATE`. The primary answers with its
+/// epoch, as `EPOCH <epoch>`, followed by one `<register> OP = <value>` line per register
+/// with its current value, and from then on forwards every change applied to its registers
+/// as `<register> <message>` lines, in the same format as the write-ahead log. A replica
+/// that cannot keep up, or whose connection fails, is dropped, and the failure is logged.
+///
+/// Each replica has a sender thread that writes the changes queued for it, so that the
+/// registers are never locked while writing to the network.
+#[derive(Debug, Default)]
+pub struct ReplicaSet {
+    queues: Mutex<Vec<Queue>>,
+}
+
+impl ReplicaSet {
+    /// Adds a replica, sending it the current value of every register. The caller must
+    /// prevent any change to the registers until this returns.
+    ///
+    /// # Arguments:
+    ///
+    /// * `stream` - The connection of the replica.
+    /// * `values` - The current values of the registers.
+    /// * `epoch` - The epoch of the primary.
+    /// * `logger` - The logger of the server, which logs the replica if it is dropped.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::WritingFailure` if the replica cannot be written.
+    pub fn add(
+        &self,
+        stream: &TcpStream,
+        values: &Values,
+        epoch: u64,
+        logger: &Logger,
+    ) -> Result<(), CalculatorErrors> {
+        let mut stream = match stream.try_clone() {
+            Ok(stream) => stream,
+            Err(_) => return Err(CalculatorErrors::SocketFailure),
+        };
+
+        let mut lines = format!("EPOCH {}\n", epoch);
+        for (name, value) in values {
+            lines.push_str(&format!("{} {}\n", name, Operation::Set(*value)));
+        }
+
+        let mut queues = self.lock()?;
+        let written = stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_ok()
+            && stream.write_all(lines.as_bytes()).is_ok();
+
+        if !written {
+            return Err(CalculatorErrors::WritingFailure);
+        }
+        let (sender, receiver) = mpsc::sync_channel(REPLICA_QUEUE);
+        let sender_logger = logger.clone();
+        thread::spawn(move || send(stream, &receiver, &sender_logger));
+        queues.push((sender, logger.clone()));
+        Ok(())
+    }
+
+    /// Queues changes about to be applied to a register for every replica. Must be called
+    /// while the register is locked, so that the replicas apply them in the same order.
+    ///
+    /// # Arguments:
+    ///
+    /// * `name` - The name of the register.
+    /// * `messages` - The protocol messages that reproduce the changes.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::LockFailure` if the mutex of the replicas is poisoned.
+    pub fn forward(&self, name: &str, messages: &[String]) -> Result<(), CalculatorErrors> {
+        let mut queues = self.lock()?;
+        if queues.is_empty() {
+            return Ok(());
+        }
+
+        let mut lines = String::new();
+        for message in messages {
+            lines.push_str(&format!("{} {}\n", name, message));
+        }
+
+        // A replica whose queue is full, or whose sender thread stopped, is dropped.
+        queues.retain(|(queue, logger)| match queue.try_send(lines.to_owned()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                logger.log(LogEvent::ReplicaDropped(CalculatorErrors::ServerBusy));
+                false
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+        Ok(())
+    }
+
+    /// Drops every replica, whose connection is closed once its sender thread stops.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::LockFailure` if the mutex of the replicas is poisoned.
+    pub fn clear(&self) -> Result<(), CalculatorErrors> {
+        self.lock()?.clear();
+        Ok(())
+    }
+
+    /// Locks the queues of the replicas.
+    fn lock(&self) -> Result<MutexGuard<'_, Vec<Queue>>, CalculatorErrors> {
+        match self.queues.lock() {
+            Ok(queues) => Ok(queues),
+            Err(_) => Err(CalculatorErrors::LockFailure),
+        }
+    }
+}
+
+/// Writes the changes queued for a replica, until its connection fails or the replica is
+/// dropped.
+fn send(mut stream: TcpStream, receiver: &Receiver<String>, logger: &Logger) {
+    while let Ok(lines) = receiver.recv() {
+        if stream.write_all(lines.as_bytes()).is_err() {
+            let error = CalculatorErrors::WritingFailure;
+            return logger.log(LogEvent::ReplicaDropped(error));
+        }
+    }
+}
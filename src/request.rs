@@ -0,0 +1,376 @@
+use crate::{errors::CalculatorErrors, operation::Operation, width::Width};
+use std::str::FromStr;
+
+/// The number of history entries returned by `HISTORY` when no count is given.
+pub const DEFAULT_HISTORY_COUNT: usize = 10;
+/// The maximum number of operations in a `BATCH`.
+pub const MAX_BATCH_SIZE: usize = 10_000;
+
+/// An enum representing the messages a client can send to the server.
+///
+/// Besides the calculator operations (`OP`, `RESET` and `GET`), a client can select the
+/// named register its following operations are applied to, and group operations in
+/// transactions.
+#[derive(PartialEq, Eq, Debug)]
+pub enum Request {
+    /// Applies an operation to the current register.
+    Calc(Operation),
+    /// Selects (creating it if needed) the register with the given name.
+    Use(String),
+    /// Starts a transaction: the following operations are queued until `Commit`.
+    Begin,
+    /// Applies the queued operations atomically.
+    Commit,
+    /// Discards the queued operations.
+    Rollback,
+    /// Restores the value the current register had before its last change.
+    Undo,
+    /// Restores the value the current register had before the last `Undo`.
+    Redo,
+    /// Retrieves the last entries of the current register's history.
+    History(usize),
+    /// Turns the connection into a replication stream, sent by a replica to its primary.
+    Replicate,
+    /// Promotes a replica to primary, so it stops following and accepts changes, if the
+    /// given token is the admin token of the server.
+    Promote(String),
+    /// Fences a primary replaced by a replica promoted with the given epoch, so it stops
+    /// accepting changes, if the given token is the admin token of the server.
+    Fence(u64, String),
+    /// Shuts the server down once every client has disconnected, if the given token is the
+    /// admin token of the server.
+    Shutdown(String),
+    /// A `VOTE`, `APPEND` or `SNAPSHOT` message sent by another node of the Raft cluster.
+    Cluster(String),
+    /// Resumes (creating it if needed) the client session with the given token, whose
+    /// identified requests are not applied twice.
+    Session(String),
+    /// A request with an id, answered with the original response if the id was already
+    /// seen in the client session.
+    Identified(u64, Box<Request>),
+    /// Applies the given number of operations, sent in the following lines, under a single
+    /// lock of the current register.
+    Batch(usize),
+}
+
+impl FromStr for Request {
+    type Err = CalculatorErrors;
+
+    /// Parses a line received from a client into a `Request`, with operands of the
+    /// default width (u8).
+    ///
+    /// See `Request::parse`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, Width::default())
+    }
+}
+
+impl Request {
+    /// Parses a line received from a client into a `Request`.
+    ///
+    /// # Arguments:
+    ///
+    /// * `s` - The line received, e.g. `USE <name>`, `OP <operator> <operand>` or `GET`.
+    /// * `width` - The width operands must fit in.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::ArgsLenFailure` if the message has the wrong number
+    /// of arguments, or any error produced while parsing the operation.
+    pub fn parse(s: &str, width: Width) -> Result<Self, CalculatorErrors> {
+        let tokens: Vec<&str> = s.split_whitespace().collect();
+        if let Some(&"VOTE" | &"APPEND" | &"SNAPSHOT") = tokens.first() {
+            return Ok(Self::Cluster(s.to_owned()));
+        }
+        if tokens.len() > 2 && tokens[tokens.len() - 2] == "ID" {
+            return Self::get_identified(&tokens, width);
+        }
+
+        match tokens.first() {
+            Some(&"USE") => {
+                if tokens.len() != 2 {
+                    return Err(CalculatorErrors::ArgsLenFailure);
+                }
+                Ok(Self::Use(tokens[1].to_owned()))
+            }
+            Some(&"BEGIN") => Self::get_without_args(&tokens, Self::Begin),
+            Some(&"COMMIT") => Self::get_without_args(&tokens, Self::Commit),
+            Some(&"ROLLBACK") => Self::get_without_args(&tokens, Self::Rollback),
+            Some(&"UNDO") => Self::get_without_args(&tokens, Self::Undo),
+            Some(&"REDO") => Self::get_without_args(&tokens, Self::Redo),
+            Some(&"SESSION") => {
+                if tokens.len() != 2 {
+                    return Err(CalculatorErrors::ArgsLenFailure);
+                }
+                Ok(Self::Session(tokens[1].to_owned()))
+            }
+            Some(&"HISTORY") => Self::get_history(&tokens),
+            Some(&"BATCH") => Self::get_batch(&tokens),
+            Some(&"REPLICATE") => Self::get_without_args(&tokens, Self::Replicate),
+            Some(&"PROMOTE") => {
+                if tokens.len() != 2 {
+                    return Err(CalculatorErrors::ArgsLenFailure);
+                }
+                Ok(Self::Promote(tokens[1].to_owned()))
+            }
+            Some(&"FENCE") => Self::get_fence(&tokens),
+            Some(&"SHUTDOWN") => {
+                if tokens.len() != 2 {
+                    return Err(CalculatorErrors::ArgsLenFailure);
+                }
+                Ok(Self::Shutdown(tokens[1].to_owned()))
+            }
+            Some(_) => Ok(Self::Calc(Operation::parse(s, width)?)),
+            None => Err(CalculatorErrors::UnexpectedMessage(String::new())),
+        }
+    }
+
+    /// Parses a `<request> ID <id>` message.
+    fn get_identified(tokens: &[&str], width: Width) -> Result<Self, CalculatorErrors> {
+        let (request, id) = tokens.split_at(tokens.len() - 2);
+        let id = match id[1].parse() {
+            Ok(id) => id,
+            Err(_) => return Err(CalculatorErrors::InvalidInteger(id[1].to_owned())),
+        };
+
+        match Self::parse(&request.join(" "), width)? {
+            Self::Identified(..) | Self::Batch(_) => {
+                Err(CalculatorErrors::UnexpectedMessage("ID".to_owned()))
+            }
+            request => Ok(Self::Identified(id, Box::new(request))),
+        }
+    }
+
+    /// Parses a `FENCE <epoch> <token>` message.
+    fn get_fence(tokens: &[&str]) -> Result<Self, CalculatorErrors> {
+        match tokens[1..] {
+            [epoch, token] => match epoch.parse() {
+                Ok(epoch) => Ok(Self::Fence(epoch, token.to_owned())),
+                Err(_) => Err(CalculatorErrors::InvalidInteger(epoch.to_owned())),
+            },
+            _ => Err(CalculatorErrors::ArgsLenFailure),
+        }
+    }
+
+    /// Parses a `HISTORY [count]` message.
+    fn get_history(tokens: &[&str]) -> Result<Self, CalculatorErrors> {
+        match tokens[1..] {
+            [] => Ok(Self::History(DEFAULT_HISTORY_COUNT)),
+            [count] => match count.parse() {
+                Ok(count) => Ok(Self::History(count)),
+                Err(_) => Err(CalculatorErrors::InvalidInteger(count.to_owned())),
+            },
+            _ => Err(CalculatorErrors::ArgsLenFailure),
+        }
+    }
+
+    /// Parses a `BATCH <count>` message.
+    fn get_batch(tokens: &[&str]) -> Result<Self, CalculatorErrors> {
+        match tokens[1..] {
+            [count] => match count.parse() {
+                Ok(count) if count <= MAX_BATCH_SIZE => Ok(Self::Batch(count)),
+                Ok(_) => Err(CalculatorErrors::InvalidArgument(count.to_owned())),
+                Err(_) => Err(CalculatorErrors::InvalidInteger(count.to_owned())),
+            },
+            _ => Err(CalculatorErrors::ArgsLenFailure),
+        }
+    }
+
+    /// Returns the given request if the message has no arguments.
+    fn get_without_args(tokens: &[&str], request: Self) -> Result<Self, CalculatorErrors> {
+        if tokens.len() != 1 {
+            return Err(CalculatorErrors::ArgsLenFailure);
+        }
+        Ok(request)
+    }
+}
+
+#[test]
+fn test_parse_use() {
+    let request = Request::from_str("USE team_a").unwrap();
+    assert_eq!(request, Request::Use("team_a".to_owned()));
+}
+
+#[test]
+fn test_parse_use_error_lenght() {
+    match Request::from_str("USE") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::ArgsLenFailure => (),
+            _ => panic!("Should throw an args length error, got: {:?}", e),
+        },
+    };
+}
+
+#[test]
+fn test_parse_calc() {
+    let request = Request::from_str("OP + 10").unwrap();
+    assert_eq!(request, Request::Calc(Operation::Add(10)));
+}
+
+#[test]
+fn test_parse_empty_line() {
+    match Request::from_str("") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::UnexpectedMessage(_) => (),
+            _ => panic!("Should throw UnexpectedMessage error, got: {:?}", e),
+        },
+    };
+}
+
+#[test]
+fn test_parse_transaction() {
+    assert_eq!(Request::from_str("BEGIN").unwrap(), Request::Begin);
+    assert_eq!(Request::from_str("COMMIT").unwrap(), Request::Commit);
+    assert_eq!(Request::from_str("ROLLBACK").unwrap(), Request::Rollback);
+}
+
+#[test]
+fn test_parse_transaction_error_lenght() {
+    match Request::from_str("BEGIN now") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::ArgsLenFailure => (),
+            _ => panic!("Should throw an args length error, got: {:?}", e),
+        },
+    };
+}
+
+#[test]
+fn test_parse_history() {
+    assert_eq!(
+        Request::from_str("HISTORY").unwrap(),
+        Request::History(DEFAULT_HISTORY_COUNT)
+    );
+    assert_eq!(Request::from_str("HISTORY 3").unwrap(), Request::History(3));
+}
+
+#[test]
+fn test_parse_history_invalid_count() {
+    match Request::from_str("HISTORY -3") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidInteger(_) => (),
+            _ => panic!("Should throw InvalidInteger error, got: {:?}", e),
+        },
+    };
+}
+
+#[test]
+fn test_parse_undo_redo() {
+    assert_eq!(Request::from_str("UNDO").unwrap(), Request::Undo);
+    assert_eq!(Request::from_str("REDO").unwrap(), Request::Redo);
+}
+
+#[test]
+fn test_parse_replication() {
+    assert_eq!(Request::from_str("REPLICATE").unwrap(), Request::Replicate);
+    assert_eq!(
+        Request::from_str("PROMOTE secret").unwrap(),
+        Request::Promote("secret".to_owned())
+    );
+    assert_eq!(
+        Request::from_str("FENCE 2 secret").unwrap(),
+        Request::Fence(2, "secret".to_owned())
+    );
+    match Request::from_str("FENCE x secret") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidInteger(_) => (),
+            _ => panic!("Should throw InvalidInteger error, got: {:?}", e),
+        },
+    };
+}
+
+#[test]
+fn test_parse_shutdown() {
+    assert_eq!(
+        Request::from_str("SHUTDOWN secret").unwrap(),
+        Request::Shutdown("secret".to_owned())
+    );
+    match Request::from_str("SHUTDOWN") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::ArgsLenFailure => (),
+            _ => panic!("Should throw an args length error, got: {:?}", e),
+        },
+    };
+}
+
+#[test]
+fn test_parse_cluster_message() {
+    let message = "APPEND 2 0 4 1 3\t2 7 a OP + 1";
+    assert_eq!(
+        Request::from_str(message).unwrap(),
+        Request::Cluster(message.to_owned())
+    );
+    let message = "SNAPSHOT 2 0 4 1\tdefault 3\tID 5";
+    assert_eq!(
+        Request::from_str(message).unwrap(),
+        Request::Cluster(message.to_owned())
+    );
+}
+
+#[test]
+fn test_parse_identified() {
+    assert_eq!(
+        Request::from_str("OP + 1 ID 7").unwrap(),
+        Request::Identified(7, Box::new(Request::Calc(Operation::Add(1))))
+    );
+    assert_eq!(
+        Request::from_str("OP + 1 IF 3 ID 8").unwrap(),
+        Request::Identified(
+            8,
+            Box::new(Request::Calc(Operation::IfEquals(
+                3,
+                Box::new(Operation::Add(1))
+            )))
+        )
+    );
+}
+
+#[test]
+fn test_parse_identified_errors() {
+    match Request::from_str("OP + 1 ID x") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidInteger(_) => (),
+            _ => panic!("Should throw InvalidInteger error, got: {:?}", e),
+        },
+    };
+    match Request::from_str("OP + 1 ID 2 ID 3") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::UnexpectedMessage(_) => (),
+            _ => panic!("Should throw UnexpectedMessage error, got: {:?}", e),
+        },
+    };
+}
+
+#[test]
+fn test_parse_session() {
+    assert_eq!(
+        Request::from_str("SESSION abc").unwrap(),
+        Request::Session("abc".to_owned())
+    );
+}
+
+#[test]
+fn test_parse_batch() {
+    assert_eq!(Request::from_str("BATCH 3").unwrap(), Request::Batch(3));
+    match Request::from_str("BATCH 100000") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidArgument(_) => (),
+            _ => panic!("Should throw InvalidArgument error, got: {:?}", e),
+        },
+    };
+    match Request::from_str("BATCH 3 ID 1") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::UnexpectedMessage(_) => (),
+            _ => panic!("Should throw UnexpectedMessage error, got: {:?}", e),
+        },
+    };
+}
@@ -0,0 +1,77 @@
+use crate::response::Response;
+use std::collections::{HashMap, VecDeque};
+
+/// The number of responses kept by each client session unless configured otherwise.
+pub const DEFAULT_REQUEST_CACHE: usize = 1000;
+
+/// The responses to the last identified requests of a client session.
+///
+/// A client that does not know whether a request was applied, because its connection
+/// dropped before the response arrived, can send it again with the same id and gets the
+/// original response instead of applying it twice. Only the most recent ids are kept, up
+/// to the capacity of the cache.
+#[derive(Debug)]
+pub struct RequestCache {
+    responses: HashMap<u64, Response>,
+    order: VecDeque<u64>,
+    capacity: usize,
+}
+
+impl RequestCache {
+    /// Creates an empty cache.
+    ///
+    /// # Arguments:
+    ///
+    /// * `capacity` - The maximum number of responses kept.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            responses: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Returns the response to the request with the given id, if it is known.
+    pub fn get(&self, id: u64) -> Option<&Response> {
+        self.responses.get(&id)
+    }
+
+    /// Records the response to the request with the given id, forgetting the oldest one
+    /// if the cache is full.
+    pub fn insert(&mut self, id: u64, response: Response) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.responses.insert(id, response).is_none() {
+            self.order.push_back(id);
+        }
+        if self.order.len() > self.capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.responses.remove(&oldest);
+        }
+    }
+}
+
+#[test]
+fn test_insert_and_get() {
+    let mut cache = RequestCache::new(10);
+    cache.insert(1, Response::Value(5));
+    match cache.get(1) {
+        Some(Response::Value(5)) => (),
+        _ => panic!("Should return the cached response"),
+    }
+    assert!(cache.get(2).is_none());
+}
+
+#[test]
+fn test_oldest_forgotten() {
+    let mut cache = RequestCache::new(2);
+    cache.insert(1, Response::Ok);
+    cache.insert(2, Response::Ok);
+    cache.insert(3, Response::Ok);
+    assert!(cache.get(1).is_none());
+    assert!(cache.get(2).is_some());
+    assert!(cache.get(3).is_some());
+}
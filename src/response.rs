@@ -1,15 +1,26 @@
-use crate::errors::CalculatorErrors;
+use crate::{errors::CalculatorErrors, history_entry::HistoryEntry};
 use std::{io::Write, net::TcpStream};
 
 /// An enum representing the possible responses that the server can send to a client.
 ///
 /// Each variant corresponds to a specific type of response, such as a success message,
 /// the current value of the calculator, or an error message.
+#[derive(Debug, Clone)]
 pub enum Response {
     /// Indicates that the operation was successful.
     Ok,
     /// Returns the current value of the calculator.
-    Value(u8),
+    Value(i128),
+    /// Returns the last entries of the history, as a `HISTORY <count>` line followed
+    /// by one `ENTRY` line per entry.
+    History(Vec<HistoryEntry>),
+    /// Summarizes a batch, as a `BATCH <applied> <failed> VALUE <value>` line followed by
+    /// one `LINE <index> ERROR` line per operation that failed, with its index in the batch.
+    Batch {
+        applied: usize,
+        errors: Vec<(usize, CalculatorErrors)>,
+        value: i128,
+    },
     /// Indicates that an error occurred, with the associated error details.
     Error(CalculatorErrors),
 }
@@ -41,10 +52,28 @@ impl Response {
     /// # Returns:
     ///
     /// A string representation of the response message, formatted for display.
-    fn get_message(&self) -> String {
+    pub(crate) fn get_message(&self) -> String {
         match self {
             Self::Ok => "OK".to_owned(),
             Self::Value(value) => format!("VALUE {}", value),
+            Self::History(entries) => {
+                let mut message = format!("HISTORY {}", entries.len());
+                for entry in entries {
+                    message.push_str(&format!("\n{}", entry));
+                }
+                message
+            }
+            Self::Batch {
+                applied,
+                errors,
+                value,
+            } => {
+                let mut message = format!("BATCH {} {} VALUE {}", applied, errors.len(), value);
+                for (index, e) in errors {
+                    message.push_str(&format!("\nLINE {} {}", index, e.get_message()));
+                }
+                message
+            }
             Self::Error(e) => e.get_message().to_owned(),
         }
     }
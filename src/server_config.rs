@@ -0,0 +1,408 @@
+use crate::{
+    backend::Backend,
+    errors::CalculatorErrors,
+    log_level::LogLevel,
+    logger::DEFAULT_LOG_MAX_SIZE,
+    overflow::Overflow,
+    register::{DEFAULT_HISTORY_CAPACITY, DEFAULT_UNDO_DEPTH},
+    registry::DEFAULT_MAX_REGISTERS,
+    server_mode::ServerMode,
+    storage::DEFAULT_SNAPSHOT_INTERVAL,
+    width::Width,
+    worker_pool::{DEFAULT_ACCEPT_QUEUE, DEFAULT_WORKERS},
+};
+
+/// The configuration of the server, parsed from its command line arguments.
+///
+/// The server is started as `server <address> [--<option> <value>]...`, where the
+/// supported options are:
+///
+/// * `--width <width>` - The numeric width of the calculators (default `u8`).
+/// * `--overflow <policy>` - The overflow policy: `wrapping` (default), `saturating` or `checked`.
+/// * `--history <entries>` - The number of history entries kept by each calculator (default 1000).
+/// * `--undo <depth>` - The number of values each calculator can undo (default 100).
+/// * `--max-registers <registers>` - The number of registers clients can create (default 10000).
+/// * `--data-dir <dir>` - The directory where the state is persisted (default none).
+/// * `--snapshot-every <records>` - The number of logged changes between snapshots (default 1000).
+/// * `--replica-of <addresses>` - The comma-separated addresses of the servers that may be the
+///   primary, which makes the server a replica (default none).
+/// * `--cluster <addresses>` - The comma-separated addresses of every node of the Raft cluster,
+///   including the server's own address (default none).
+/// * `--http <address>` - The address the HTTP front-end listens on (default none).
+/// * `--log-file <path>` - The file the events of the server are logged to (default none).
+/// * `--log-level <level>` - The least severe events logged: `error`, `info` (default) or `debug`.
+/// * `--log-max-size <bytes>` - The size the log file grows to before it is rotated (default 1 MiB).
+/// * `--workers <threads>` - The number of threads that handle connections (default 64).
+/// * `--accept-queue <connections>` - The number of connections that may wait for a free
+///   worker before new ones are rejected (default 64).
+/// * `--mode <mode>` - How client connections are handled: by a pool of `threads` (default),
+///   or by a single `event-loop` thread.
+/// * `--backend <backend>` - How the values of the registers are shared: behind a `mutex`
+///   (default), or as `atomic` integers, without history, undo, persistence nor replication.
+/// * `--admin-token <token>` - The token a client must send along with the administrative
+///   messages, such as `SHUTDOWN` (default none, which rejects them).
+#[derive(Debug)]
+pub struct ServerConfig {
+    /// The address the server listens on.
+    pub addr: String,
+    /// The numeric width of every calculator of the server.
+    pub width: Width,
+    /// The overflow policy of every calculator of the server.
+    pub overflow: Overflow,
+    /// The number of history entries kept by each calculator of the server.
+    pub history: usize,
+    /// The number of values each calculator of the server can undo.
+    pub undo: usize,
+    /// The number of registers the clients of the server can create.
+    pub max_registers: usize,
+    /// The directory where the state of the server is persisted, if any.
+    pub data_dir: Option<String>,
+    /// The number of changes logged between two snapshots of the state.
+    pub snapshot_every: usize,
+    /// The addresses of the servers that may be the primary, if the server is a replica.
+    pub replica_of: Vec<String>,
+    /// The addresses of every node of the Raft cluster, if the server belongs to one.
+    pub cluster: Vec<String>,
+    /// The address the HTTP front-end listens on, if it is enabled.
+    pub http: Option<String>,
+    /// The file the events of the server are logged to, if any.
+    pub log_file: Option<String>,
+    /// The least severe level of the events logged.
+    pub log_level: LogLevel,
+    /// The size in bytes the log file grows to before it is rotated.
+    pub log_max_size: usize,
+    /// The number of threads that handle the connections of each listener.
+    pub workers: usize,
+    /// The number of connections that may wait for a free worker of each listener.
+    pub accept_queue: usize,
+    /// How the client connections are handled.
+    pub mode: ServerMode,
+    /// How the values of the registers are shared between clients.
+    pub backend: Backend,
+    /// The token that authorizes the administrative messages, if they are enabled.
+    pub admin_token: Option<String>,
+}
+
+impl ServerConfig {
+    /// Parses the command line arguments of the server.
+    ///
+    /// # Arguments:
+    ///
+    /// * `args` - The command line arguments, including the program name.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::ArgsLenFailure` if the address or an option value is
+    /// missing, or `CalculatorErrors::InvalidArgument` if an option is unknown or invalid.
+    pub fn from_args(args: &[String]) -> Result<Self, CalculatorErrors> {
+        let mut config = match args.get(1) {
+            Some(addr) => Self {
+                addr: addr.to_owned(),
+                width: Width::default(),
+                overflow: Overflow::default(),
+                history: DEFAULT_HISTORY_CAPACITY,
+                undo: DEFAULT_UNDO_DEPTH,
+                max_registers: DEFAULT_MAX_REGISTERS,
+                data_dir: None,
+                snapshot_every: DEFAULT_SNAPSHOT_INTERVAL,
+                replica_of: vec![],
+                cluster: vec![],
+                http: None,
+                log_file: None,
+                log_level: LogLevel::default(),
+                log_max_size: DEFAULT_LOG_MAX_SIZE,
+                workers: DEFAULT_WORKERS,
+                accept_queue: DEFAULT_ACCEPT_QUEUE,
+                mode: ServerMode::default(),
+                backend: Backend::default(),
+                admin_token: None,
+            },
+            None => return Err(CalculatorErrors::ArgsLenFailure),
+        };
+
+        let mut options = args[2..].iter();
+        while let Some(option) = options.next() {
+            match options.next() {
+                Some(value) => config.set_option(option, value)?,
+                None => return Err(CalculatorErrors::ArgsLenFailure),
+            }
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Returns the index of the server in the addresses of the Raft cluster, if it
+    /// belongs to one.
+    pub fn cluster_index(&self) -> Option<usize> {
+        self.cluster.iter().position(|addr| *addr == self.addr)
+    }
+
+    /// Checks that the options are compatible. The server needs at least one worker, and
+    /// the event loop is only available on unix targets. The atomic backend keeps only the values, so it can neither persist nor replicate them. A
+    /// node of a Raft cluster gets its changes from the leader, so it cannot be a replica,
+    /// and its own address must be one of the cluster's. It also waits for its peers on
+    /// every change, which would stall an event loop.
+    fn validate(&self) -> Result<(), CalculatorErrors> {
+        if self.workers == 0 {
+            return Err(CalculatorErrors::InvalidArgument("--workers".to_owned()));
+        }
+        if self.mode == ServerMode::EventLoop && !cfg!(unix) {
+            return Err(CalculatorErrors::InvalidArgument("--mode".to_owned()));
+        }
+        if self.backend == Backend::Atomic {
+            self.validate_atomic()?;
+        }
+        if self.cluster.is_empty() {
+            return Ok(());
+        }
+
+        if self.mode == ServerMode::EventLoop {
+            return Err(CalculatorErrors::InvalidArgument("--mode".to_owned()));
+        }
+        if !self.replica_of.is_empty() {
+            return Err(CalculatorErrors::InvalidArgument("--replica-of".to_owned()));
+        }
+        match self.cluster_index() {
+            Some(_) => Ok(()),
+            None => Err(CalculatorErrors::InvalidArgument(self.addr.to_owned())),
+        }
+    }
+
+    /// Checks that no option needs more than the values of the registers, which is all
+    /// the atomic backend keeps.
+    fn validate_atomic(&self) -> Result<(), CalculatorErrors> {
+        let option = if self.data_dir.is_some() {
+            "--data-dir"
+        } else if !self.replica_of.is_empty() {
+            "--replica-of"
+        } else if !self.cluster.is_empty() {
+            "--cluster"
+        } else {
+            return Ok(());
+        };
+        Err(CalculatorErrors::InvalidArgument(option.to_owned()))
+    }
+
+    /// Sets the option with the given name to the given value.
+    fn set_option(&mut self, option: &str, value: &str) -> Result<(), CalculatorErrors> {
+        match option {
+            "--width" => self.width = value.parse()?,
+            "--overflow" => self.overflow = value.parse()?,
+            "--history" => self.history = parse_count(value)?,
+            "--undo" => self.undo = parse_count(value)?,
+            "--max-registers" => self.max_registers = parse_count(value)?,
+            "--data-dir" => self.data_dir = Some(value.to_owned()),
+            "--snapshot-every" => self.snapshot_every = parse_count(value)?,
+            "--replica-of" => self.replica_of = parse_addresses(value),
+            "--cluster" => self.cluster = parse_addresses(value),
+            "--http" => self.http = Some(value.to_owned()),
+            "--log-file" => self.log_file = Some(value.to_owned()),
+            "--log-level" => self.log_level = value.parse()?,
+            "--log-max-size" => self.log_max_size = parse_count(value)?,
+            "--workers" => self.workers = parse_count(value)?,
+            "--accept-queue" => self.accept_queue = parse_count(value)?,
+            "--mode" => self.mode = value.parse()?,
+            "--backend" => self.backend = value.parse()?,
+            "--admin-token" => self.admin_token = Some(value.to_owned()),
+            _ => return Err(CalculatorErrors::InvalidArgument(option.to_owned())),
+        }
+        Ok(())
+    }
+}
+
+/// Parses a comma-separated list of addresses given as the value of an option.
+fn parse_addresses(value: &str) -> Vec<String> {
+    value.split(',').map(|addr| addr.to_owned()).collect()
+}
+
+/// Parses a non-negative count given as the value of an option.
+fn parse_count(value: &str) -> Result<usize, CalculatorErrors> {
+    match value.parse() {
+        Ok(count) => Ok(count),
+        Err(_) => Err(CalculatorErrors::InvalidArgument(value.to_owned())),
+    }
+}
+
+#[cfg(test)]
+fn args(line: &str) -> Vec<String> {
+    line.split_whitespace().map(|arg| arg.to_owned()).collect()
+}
+
+#[test]
+fn test_from_args_address_only() {
+    let config = ServerConfig::from_args(&args("server 127.0.0.1:8080")).unwrap();
+    assert_eq!(config.addr, "127.0.0.1:8080");
+    assert_eq!(config.width, Width::default());
+}
+
+#[test]
+fn test_from_args_width() {
+    let config = ServerConfig::from_args(&args("server 127.0.0.1:8080 --width i64")).unwrap();
+    assert_eq!(config.width, "i64".parse().unwrap());
+}
+
+#[test]
+fn test_from_args_overflow() {
+    let config =
+        ServerConfig::from_args(&args("server 127.0.0.1:8080 --overflow checked")).unwrap();
+    assert_eq!(config.overflow, Overflow::Checked);
+}
+
+#[test]
+fn test_from_args_history() {
+    let config = ServerConfig::from_args(&args("server 127.0.0.1:8080 --history 5")).unwrap();
+    assert_eq!(config.history, 5);
+}
+
+#[test]
+fn test_from_args_max_registers() {
+    let line = "server 127.0.0.1:8080 --max-registers 3";
+    let config = ServerConfig::from_args(&args(line)).unwrap();
+    assert_eq!(config.max_registers, 3);
+}
+
+#[test]
+fn test_from_args_data_dir() {
+    let line = "server 127.0.0.1:8080 --data-dir data --snapshot-every 50";
+    let config = ServerConfig::from_args(&args(line)).unwrap();
+    assert_eq!(config.data_dir, Some("data".to_owned()));
+    assert_eq!(config.snapshot_every, 50);
+}
+
+#[test]
+fn test_from_args_replica_of() {
+    let line = "server 127.0.0.1:8081 --replica-of 127.0.0.1:8080,127.0.0.1:8082";
+    let config = ServerConfig::from_args(&args(line)).unwrap();
+    assert_eq!(config.replica_of, vec!["127.0.0.1:8080", "127.0.0.1:8082"]);
+}
+
+#[test]
+fn test_from_args_cluster() {
+    let line = "server 127.0.0.1:8081 --cluster 127.0.0.1:8080,127.0.0.1:8081";
+    let config = ServerConfig::from_args(&args(line)).unwrap();
+    assert_eq!(config.cluster.len(), 2);
+    assert_eq!(config.cluster_index(), Some(1));
+}
+
+#[test]
+fn test_from_args_cluster_without_own_address() {
+    let line = "server 127.0.0.1:8081 --cluster 127.0.0.1:8080,127.0.0.1:8082";
+    match ServerConfig::from_args(&args(line)) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidArgument(argument) => assert_eq!(argument, "127.0.0.1:8081"),
+            _ => panic!("Should throw InvalidArgument error, got: {:?}", e),
+        },
+    }
+}
+
+#[test]
+fn test_from_args_http() {
+    let line = "server 127.0.0.1:8080 --http 127.0.0.1:8000";
+    let config = ServerConfig::from_args(&args(line)).unwrap();
+    assert_eq!(config.http, Some("127.0.0.1:8000".to_owned()));
+}
+
+#[test]
+fn test_from_args_log() {
+    let line = "server 127.0.0.1:8080 --log-file server.log --log-level debug --log-max-size 10";
+    let config = ServerConfig::from_args(&args(line)).unwrap();
+    assert_eq!(config.log_file, Some("server.log".to_owned()));
+    assert_eq!(config.log_level, LogLevel::Debug);
+    assert_eq!(config.log_max_size, 10);
+}
+
+#[test]
+fn test_from_args_workers() {
+    let line = "server 127.0.0.1:8080 --workers 4 --accept-queue 0";
+    let config = ServerConfig::from_args(&args(line)).unwrap();
+    assert_eq!(config.workers, 4);
+    assert_eq!(config.accept_queue, 0);
+}
+
+#[test]
+fn test_from_args_no_workers() {
+    match ServerConfig::from_args(&args("server 127.0.0.1:8080 --workers 0")) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidArgument(argument) => assert_eq!(argument, "--workers"),
+            _ => panic!("Should throw InvalidArgument error, got: {:?}", e),
+        },
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn test_from_args_mode() {
+    let config = ServerConfig::from_args(&args("server 127.0.0.1:8080 --mode event-loop")).unwrap();
+    assert_eq!(config.mode, ServerMode::EventLoop);
+    let line = "server 127.0.0.1:8080 --mode event-loop --cluster 127.0.0.1:8080";
+    match ServerConfig::from_args(&args(line)) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidArgument(argument) => assert_eq!(argument, "--mode"),
+            _ => panic!("Should throw InvalidArgument error, got: {:?}", e),
+        },
+    }
+}
+
+#[test]
+fn test_from_args_backend() {
+    let config = ServerConfig::from_args(&args("server 127.0.0.1:8080 --backend atomic")).unwrap();
+    assert_eq!(config.backend, Backend::Atomic);
+    let line = "server 127.0.0.1:8080 --backend atomic --data-dir data";
+    match ServerConfig::from_args(&args(line)) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidArgument(argument) => assert_eq!(argument, "--data-dir"),
+            _ => panic!("Should throw InvalidArgument error, got: {:?}", e),
+        },
+    }
+}
+
+#[test]
+fn test_from_args_admin_token() {
+    let line = "server 127.0.0.1:8080 --admin-token secret";
+    let config = ServerConfig::from_args(&args(line)).unwrap();
+    assert_eq!(config.admin_token, Some("secret".to_owned()));
+}
+
+#[test]
+fn test_from_args_undo() {
+    let config = ServerConfig::from_args(&args("server 127.0.0.1:8080 --undo 3")).unwrap();
+    assert_eq!(config.undo, 3);
+}
+
+#[test]
+fn test_from_args_missing_address() {
+    match ServerConfig::from_args(&args("server")) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::ArgsLenFailure => (),
+            _ => panic!("Should throw an args length error, got: {:?}", e),
+        },
+    }
+}
+
+#[test]
+fn test_from_args_missing_option_value() {
+    match ServerConfig::from_args(&args("server 127.0.0.1:8080 --width")) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::ArgsLenFailure => (),
+            _ => panic!("Should throw an args length error, got: {:?}", e),
+        },
+    }
+}
+
+#[test]
+fn test_from_args_unknown_option() {
+    match ServerConfig::from_args(&args("server 127.0.0.1:8080 --colour red")) {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidArgument(_) => (),
+            _ => panic!("Should throw InvalidArgument error, got: {:?}", e),
+        },
+    }
+}
@@ -0,0 +1,55 @@
+use crate::errors::CalculatorErrors;
+use std::{fmt, str::FromStr};
+
+/// The way the server handles its client connections.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ServerMode {
+    /// Each connection is handled by a thread of a pool, with blocking reads.
+    #[default]
+    Threads,
+    /// Every connection is handled by a single thread, with non-blocking reads.
+    EventLoop,
+}
+
+impl FromStr for ServerMode {
+    type Err = CalculatorErrors;
+
+    /// Parses a mode: `threads` or `event-loop`.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::InvalidArgument` if the mode is unknown.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "threads" => Ok(Self::Threads),
+            "event-loop" => Ok(Self::EventLoop),
+            _ => Err(CalculatorErrors::InvalidArgument(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for ServerMode {
+    /// Formats the mode as it is given on the command line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Threads => write!(f, "threads"),
+            Self::EventLoop => write!(f, "event-loop"),
+        }
+    }
+}
+
+#[test]
+fn test_parse_mode() {
+    assert_eq!(
+        "event-loop".parse::<ServerMode>().unwrap(),
+        ServerMode::EventLoop
+    );
+    assert_eq!(ServerMode::default().to_string(), "threads");
+    match "async".parse::<ServerMode>() {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidArgument(_) => (),
+            _ => panic!("Should throw InvalidArgument error, got: {:?}", e),
+        },
+    };
+}
@@ -0,0 +1,876 @@
+use crate::{
+    action::Action,
+    atomic_calculator::AtomicCalculator,
+    errors::CalculatorErrors,
+    operation::Operation,
+    register::Register,
+    registry::{DEFAULT_REGISTER, Registry},
+    request::Request,
+    request_cache::{DEFAULT_REQUEST_CACHE, RequestCache},
+    response::Response,
+};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// The state of a single client connection.
+///
+/// A session keeps track of the register the client is currently operating on,
+/// which is the default register until the client sends `USE <name>`, and of the
+/// operations queued in the transaction in progress, if any. Every session is
+/// identified by a client id, recorded in the history of the registers.
+///
+/// Every change applied by the session is persisted through the registry before the
+/// register is unlocked, as the protocol messages that reproduce it. In a Raft cluster,
+/// changes are instead submitted to the leader's log, and applied once committed.
+///
+/// The responses to identified requests are kept in the cache of the client session, which
+/// is private to the connection unless the client resumes a session with `SESSION <token>`.
+///
+/// With the atomic backend, operations are applied to the atomic calculator of the current
+/// register instead, without locking it; `HISTORY`, `UNDO` and `REDO` are not supported.
+pub struct Session {
+    registry: Arc<Registry>,
+    name: String,
+    register: Arc<Mutex<Register>>,
+    atomic: Option<Arc<AtomicCalculator>>,
+    transaction: Option<Vec<Operation>>,
+    client: u64,
+    cache: Arc<Mutex<RequestCache>>,
+}
+
+impl Session {
+    /// Creates a new session operating on the default register.
+    ///
+    /// # Arguments:
+    ///
+    /// * `registry` - The registry shared by every client of the server.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::LockFailure` if the registry mutex is poisoned.
+    pub fn new(registry: Arc<Registry>) -> Result<Self, CalculatorErrors> {
+        let register = registry.get_or_create(DEFAULT_REGISTER)?;
+        let atomic = registry.atomic_calculator(DEFAULT_REGISTER)?;
+        let client = registry.next_client_id();
+        Ok(Self {
+            registry,
+            name: DEFAULT_REGISTER.to_owned(),
+            register,
+            atomic,
+            transaction: None,
+            client,
+            cache: Arc::new(Mutex::new(RequestCache::new(DEFAULT_REQUEST_CACHE))),
+        })
+    }
+
+    /// Returns the id of the client of the session.
+    pub fn client(&self) -> u64 {
+        self.client
+    }
+
+    /// Processes a line received from the client.
+    ///
+    /// # Arguments:
+    ///
+    /// * `line` - The message received from the client.
+    ///
+    /// # Returns:
+    ///
+    /// The `Action` the handler of the connection must take, which is usually sending
+    /// back a response.
+    pub fn handle_line(&mut self, line: &str) -> Action {
+        let result = match Request::parse(line, self.registry.width()) {
+            Ok(Request::Replicate) => return Action::Replicate,
+            Ok(Request::Batch(count)) => return Action::ReadBatch(count),
+            Ok(Request::Cluster(message)) => self.handle_cluster(&message).map(Action::Cluster),
+            Ok(request) => self.handle_request(request).map(Action::Respond),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(action) => action,
+            Err(e) => Action::Respond(Response::Error(e)),
+        }
+    }
+
+    /// Processes an operation received through the binary protocol.
+    ///
+    /// # Arguments:
+    ///
+    /// * `op` - The operation decoded from the frame received from the client.
+    ///
+    /// # Returns:
+    ///
+    /// The `Response` that must be sent back to the client.
+    pub fn handle_operation(&mut self, op: Operation) -> Response {
+        match self.handle_request(Request::Calc(op)) {
+            Ok(response) => response,
+            Err(e) => Response::Error(e),
+        }
+    }
+
+    /// Applies the lines of a batch, announced with `BATCH <count>`, to the current register,
+    /// which stays locked while they are applied. An operation that fails does not stop the
+    /// batch: its error is reported in the summary, along with its index in the batch.
+    ///
+    /// # Arguments:
+    ///
+    /// * `lines` - The operations of the batch, e.g. `OP + 1`.
+    ///
+    /// # Returns:
+    ///
+    /// The `Response` that summarizes the batch.
+    pub fn handle_batch(&mut self, lines: &[String]) -> Response {
+        if self.transaction.is_some() {
+            return Response::Error(CalculatorErrors::UnexpectedMessage("BATCH".to_owned()));
+        }
+
+        let width = self.registry.width();
+        let ops = lines.iter().map(|line| Operation::parse(line, width));
+        let ops: Vec<Result<Operation, CalculatorErrors>> = ops.collect();
+        let result = match (self.check_writable(), self.atomic.as_ref().map(Arc::clone)) {
+            (Ok(()), Some(atomic)) => Self::update_batch(&atomic, &ops),
+            (Ok(()), None) if self.registry.cluster().is_some() => self.submit_batch(ops),
+            (Ok(()), None) => self.apply_batch(ops),
+            (Err(e), _) => Err(e),
+        };
+
+        match result {
+            Ok(response) => response,
+            Err(e) => Response::Error(e),
+        }
+    }
+
+    /// Processes a request received from the client, which is answered with a response.
+    fn handle_request(&mut self, request: Request) -> Result<Response, CalculatorErrors> {
+        let result = match request {
+            Request::Calc(op) => self.handle_op(op),
+            Request::Use(name) => self.handle_use(&name),
+            Request::Begin => self.handle_begin(),
+            Request::Commit => self.handle_commit(),
+            Request::Rollback => self.handle_rollback(),
+            Request::Undo => self.handle_undo(false),
+            Request::Redo => self.handle_undo(true),
+            Request::History(count) => {
+                self.check_locking("HISTORY")?;
+                return Ok(Response::History(self.lock()?.history(count)));
+            }
+            Request::Replicate => {
+                return Err(CalculatorErrors::UnexpectedMessage("REPLICATE".to_owned()));
+            }
+            Request::Promote(token) => self.handle_promote(&token),
+            Request::Fence(epoch, token) => self.handle_fence(epoch, &token),
+            Request::Shutdown(token) => {
+                self.registry.authorize(&token)?;
+                self.registry.request_shutdown();
+                Ok(None)
+            }
+            Request::Cluster(message) => return Err(CalculatorErrors::UnexpectedMessage(message)),
+            Request::Session(token) => self.handle_session(&token),
+            Request::Identified(id, request) => return self.handle_identified(id, *request),
+            Request::Batch(_) => {
+                return Err(CalculatorErrors::UnexpectedMessage("BATCH".to_owned()));
+            }
+        };
+
+        match result? {
+            Some(value) => Ok(Response::Value(value)),
+            None => Ok(Response::Ok),
+        }
+    }
+
+    /// Applies an operation to the current register, or queues it if a transaction
+    /// is in progress. `GET` is never queued, so it returns the committed value.
+    fn handle_op(&mut self, op: Operation) -> Result<Option<i128>, CalculatorErrors> {
+        if let Some(atomic) = &self.atomic
+            && (op == Operation::Get || self.transaction.is_none())
+        {
+            self.check_writable()?;
+            return atomic.apply(&op);
+        }
+        if op == Operation::Get {
+            return self.lock()?.apply(op, self.client);
+        }
+
+        self.check_writable()?;
+        if let Some(queue) = &mut self.transaction {
+            queue.push(op);
+            return Ok(None);
+        }
+
+        if let Some(raft) = self.registry.cluster() {
+            raft.submit(
+                &self.registry,
+                &self.name,
+                vec![op.to_string()],
+                self.client,
+            )?;
+            return Ok(None);
+        }
+
+        let message = op.to_string();
+        let register = self.lock()?;
+        register.check(register.value(), &op)?;
+        let client = self.client;
+        self.persist(register, &[message], |register| {
+            register.apply(op, client).map(|_| ())
+        })?;
+        Ok(None)
+    }
+
+    /// Selects the register with the given name as the current one.
+    fn handle_use(&mut self, name: &str) -> Result<Option<i128>, CalculatorErrors> {
+        if self.transaction.is_some() {
+            return Err(CalculatorErrors::UnexpectedMessage("USE".to_owned()));
+        }
+
+        self.register = self.registry.select(name)?;
+        self.atomic = self.registry.atomic_calculator(name)?;
+        self.name = name.to_owned();
+        Ok(None)
+    }
+
+    /// Undoes or redoes the last change to the current register.
+    fn handle_undo(&mut self, redo: bool) -> Result<Option<i128>, CalculatorErrors> {
+        let message = if redo { "REDO" } else { "UNDO" };
+        if self.transaction.is_some() {
+            return Err(CalculatorErrors::UnexpectedMessage(message.to_owned()));
+        }
+        self.check_locking(message)?;
+
+        self.check_writable()?;
+        if let Some(raft) = self.registry.cluster() {
+            raft.submit(
+                &self.registry,
+                &self.name,
+                vec![message.to_owned()],
+                self.client,
+            )?;
+            return Ok(None);
+        }
+
+        let register = self.lock()?;
+        let message = Operation::Set(register.restorable(redo)?).to_string();
+        let client = self.client;
+        self.persist(register, &[message], |register| match redo {
+            true => register.redo(client),
+            false => register.undo(client),
+        })?;
+        Ok(None)
+    }
+
+    /// Starts a transaction.
+    fn handle_begin(&mut self) -> Result<Option<i128>, CalculatorErrors> {
+        if self.transaction.is_some() {
+            return Err(CalculatorErrors::NestedTransaction);
+        }
+
+        self.transaction = Some(vec![]);
+        Ok(None)
+    }
+
+    /// Applies every queued operation under a single lock of the current register, or with
+    /// a single compare-and-swap of its atomic calculator.
+    fn handle_commit(&mut self) -> Result<Option<i128>, CalculatorErrors> {
+        let queue = match self.transaction.take() {
+            Some(queue) => queue,
+            None => return Err(CalculatorErrors::NoTransaction),
+        };
+
+        self.check_writable()?;
+        if let Some(atomic) = &self.atomic {
+            atomic.apply_all(&queue)?;
+            return Ok(None);
+        }
+        let mut messages = vec!["BEGIN".to_owned()];
+        messages.extend(queue.iter().map(|op| op.to_string()));
+        messages.push("COMMIT".to_owned());
+
+        if let Some(raft) = self.registry.cluster() {
+            raft.submit(&self.registry, &self.name, messages, self.client)?;
+            return Ok(None);
+        }
+
+        let register = self.lock()?;
+        register.check_all(&queue)?;
+        let client = self.client;
+        self.persist(register, &messages, |register| {
+            register.apply_all(queue, client)
+        })?;
+        Ok(None)
+    }
+
+    /// Discards every queued operation.
+    fn handle_rollback(&mut self) -> Result<Option<i128>, CalculatorErrors> {
+        match self.transaction.take() {
+            Some(_) => Ok(None),
+            None => Err(CalculatorErrors::NoTransaction),
+        }
+    }
+
+    /// Resumes the client session with the given token.
+    fn handle_session(&mut self, token: &str) -> Result<Option<i128>, CalculatorErrors> {
+        self.cache = self.registry.client_session(token)?;
+        Ok(None)
+    }
+
+    /// Processes an identified request, unless its id was already seen in the client
+    /// session, in which case the original response is returned. Only the responses of
+    /// requests that were applied are kept: a request that failed, for instance because
+    /// the server is not the leader or its storage failed, is processed again if retried.
+    /// The cache stays locked while the request is processed, so a retry sent on another
+    /// connection waits for it.
+    fn handle_identified(
+        &mut self,
+        id: u64,
+        request: Request,
+    ) -> Result<Response, CalculatorErrors> {
+        let cache = Arc::clone(&self.cache);
+        let mut cache = match cache.lock() {
+            Ok(cache) => cache,
+            Err(_) => return Err(CalculatorErrors::LockFailure),
+        };
+
+        if let Some(response) = cache.get(id) {
+            return Ok(response.clone());
+        }
+
+        let response = match self.handle_request(request) {
+            Ok(response) => response,
+            Err(e) => Response::Error(e),
+        };
+        if !matches!(response, Response::Error(_)) {
+            cache.insert(id, response.clone());
+        }
+        Ok(response)
+    }
+
+    /// Checks the operations of a batch under a single lock of the current register, then
+    /// persists and applies the ones that succeed.
+    fn apply_batch(
+        &mut self,
+        ops: Vec<Result<Operation, CalculatorErrors>>,
+    ) -> Result<Response, CalculatorErrors> {
+        let register = self.lock()?;
+        let (mut value, mut valid, mut errors) = (register.value(), vec![], vec![]);
+        for (index, op) in ops.into_iter().enumerate() {
+            match op.and_then(|op| register.check(value, &op).map(|result| (op, result))) {
+                Ok((op, result)) => {
+                    value = result;
+                    valid.push(op);
+                }
+                Err(e) => errors.push((index + 1, e)),
+            }
+        }
+
+        let applied = valid.len();
+        let messages: Vec<String> = valid
+            .iter()
+            .filter(|op| **op != Operation::Get)
+            .map(|op| op.to_string())
+            .collect();
+        let client = self.client;
+        self.persist(register, &messages, |register| {
+            valid
+                .into_iter()
+                .try_for_each(|op| register.apply(op, client).map(|_| ()))
+        })?;
+        Ok(Response::Batch {
+            applied,
+            errors,
+            value,
+        })
+    }
+
+    /// Applies the operations of a batch with a single compare-and-swap of the atomic
+    /// calculator of the current register, so no other change is interleaved with them.
+    fn update_batch(
+        atomic: &AtomicCalculator,
+        ops: &[Result<Operation, CalculatorErrors>],
+    ) -> Result<Response, CalculatorErrors> {
+        let (value, (applied, errors)) = atomic.update(|mut value| {
+            let (mut applied, mut errors) = (0, vec![]);
+            for (index, op) in ops.iter().enumerate() {
+                let result = match op {
+                    Ok(op) => atomic.compute(value, op),
+                    Err(e) => Err(e.clone()),
+                };
+                match result {
+                    Ok(result) => {
+                        applied += 1;
+                        value = result;
+                    }
+                    Err(e) => errors.push((index + 1, e)),
+                }
+            }
+            Ok((value, (applied, errors)))
+        })?;
+        Ok(Response::Batch {
+            applied,
+            errors,
+            value,
+        })
+    }
+
+    /// Submits the valid operations of a batch to the Raft cluster as a single entry, after
+    /// a `BATCH <count>` message, so they are applied under a single lock of the current
+    /// register of every node, with no other change interleaved with them. The operations
+    /// that fail are reported in the summary, along with their index in the batch.
+    fn submit_batch(
+        &mut self,
+        ops: Vec<Result<Operation, CalculatorErrors>>,
+    ) -> Result<Response, CalculatorErrors> {
+        let raft = match self.registry.cluster() {
+            Some(raft) => raft,
+            None => return Err(CalculatorErrors::UnexpectedMessage("BATCH".to_owned())),
+        };
+        let (mut messages, mut indexes, mut errors) = (vec![String::new()], vec![], vec![]);
+        for (index, op) in ops.into_iter().enumerate() {
+            match op {
+                Ok(op) => {
+                    messages.push(op.to_string());
+                    indexes.push(index + 1);
+                }
+                Err(e) => errors.push((index + 1, e)),
+            }
+        }
+        messages[0] = format!("BATCH {}", indexes.len());
+
+        let response = raft.submit(&self.registry, &self.name, messages, self.client)?;
+        if let Response::Batch {
+            applied,
+            errors: failed,
+            value,
+        } = response
+        {
+            errors.extend(failed.into_iter().map(|(index, e)| (indexes[index - 1], e)));
+            errors.sort_by_key(|(index, _)| *index);
+            return Ok(Response::Batch {
+                applied,
+                errors,
+                value,
+            });
+        }
+        Ok(response)
+    }
+
+    /// Promotes the server to primary, if it is a replica and the token is the admin token.
+    fn handle_promote(&mut self, token: &str) -> Result<Option<i128>, CalculatorErrors> {
+        self.registry.authorize(token)?;
+        self.registry.promote();
+        Ok(None)
+    }
+
+    /// Fences the server, replaced by a replica promoted with the given epoch, if the token
+    /// is the admin token.
+    fn handle_fence(&mut self, epoch: u64, token: &str) -> Result<Option<i128>, CalculatorErrors> {
+        self.registry.authorize(token)?;
+        self.registry.fence(epoch)?;
+        Ok(None)
+    }
+
+    /// Answers a message sent by another node of the Raft cluster.
+    fn handle_cluster(&mut self, message: &str) -> Result<String, CalculatorErrors> {
+        match self.registry.cluster() {
+            Some(raft) => raft.handle(&self.registry, message),
+            None => Err(CalculatorErrors::UnexpectedMessage(message.to_owned())),
+        }
+    }
+
+    /// Checks that the registers can be changed by clients, which is not the case in a
+    /// replica.
+    fn check_writable(&self) -> Result<(), CalculatorErrors> {
+        if self.registry.is_replica() {
+            return Err(CalculatorErrors::ReadOnlyReplica);
+        }
+        Ok(())
+    }
+
+    /// Checks that the registry keeps the state of the registers that a message needs,
+    /// which is not the case with the atomic backend.
+    fn check_locking(&self, message: &str) -> Result<(), CalculatorErrors> {
+        if self.atomic.is_some() {
+            return Err(CalculatorErrors::UnexpectedMessage(message.to_owned()));
+        }
+        Ok(())
+    }
+
+    /// Persists changes already checked against the current register, applies them only
+    /// once they are in the log, and then unlocks the register. `UNDO` and `REDO` are
+    /// persisted as the value they restore, since the snapshots do not keep the undo stacks.
+    ///
+    /// # Arguments:
+    ///
+    /// * `register` - The locked current register.
+    /// * `messages` - The messages of the changes.
+    /// * `apply` - Applies the changes to the register.
+    fn persist<F>(
+        &self,
+        register: MutexGuard<'_, Register>,
+        messages: &[String],
+        apply: F,
+    ) -> Result<(), CalculatorErrors>
+    where
+        F: FnOnce(&mut Register) -> Result<(), CalculatorErrors>,
+    {
+        // The register is unlocked at the end of the block, before taking the snapshot.
+        let snapshot_due = {
+            let mut register = register;
+            let snapshot_due = self.registry.persist(&self.name, messages)?;
+            apply(&mut register)?;
+            snapshot_due
+        };
+
+        if snapshot_due {
+            self.registry.snapshot()?;
+        }
+        Ok(())
+    }
+
+    /// Locks the current register.
+    fn lock(&self) -> Result<MutexGuard<'_, Register>, CalculatorErrors> {
+        match self.register.lock() {
+            Ok(calculator) => Ok(calculator),
+            Err(_) => Err(CalculatorErrors::LockFailure),
+        }
+    }
+}
+
+#[test]
+fn test_sessions_share_default_register() {
+    let registry = Arc::new(Registry::default());
+    let mut first = Session::new(Arc::clone(&registry)).unwrap();
+    let mut second = Session::new(registry).unwrap();
+
+    first.handle_line("OP + 5");
+    match respond(&mut second, "GET") {
+        Response::Value(value) => assert_eq!(value, 5),
+        _ => panic!("Should return a value"),
+    }
+}
+
+#[test]
+fn test_sessions_use_independent_registers() {
+    let registry = Arc::new(Registry::default());
+    let mut first = Session::new(Arc::clone(&registry)).unwrap();
+    let mut second = Session::new(registry).unwrap();
+
+    first.handle_line("USE team_a");
+    first.handle_line("OP + 5");
+    second.handle_line("USE team_b");
+    second.handle_line("OP + 7");
+
+    match respond(&mut first, "GET") {
+        Response::Value(value) => assert_eq!(value, 5),
+        _ => panic!("Should return a value"),
+    }
+    match respond(&mut second, "GET") {
+        Response::Value(value) => assert_eq!(value, 7),
+        _ => panic!("Should return a value"),
+    }
+}
+
+#[cfg(test)]
+fn respond(session: &mut Session, line: &str) -> Response {
+    match session.handle_line(line) {
+        Action::Respond(response) => response,
+        action => panic!("Should respond, got: {:?}", action),
+    }
+}
+
+#[cfg(test)]
+fn get_value(session: &mut Session) -> i128 {
+    match respond(session, "GET") {
+        Response::Value(value) => value,
+        _ => panic!("Should return a value"),
+    }
+}
+
+#[test]
+fn test_transaction_commit() {
+    let mut session = Session::new(Arc::new(Registry::default())).unwrap();
+
+    session.handle_line("BEGIN");
+    session.handle_line("OP + 5");
+    session.handle_line("OP * 3");
+    assert_eq!(get_value(&mut session), 0);
+    session.handle_line("COMMIT");
+    assert_eq!(get_value(&mut session), 15);
+}
+
+#[test]
+fn test_transaction_rollback() {
+    let mut session = Session::new(Arc::new(Registry::default())).unwrap();
+
+    session.handle_line("BEGIN");
+    session.handle_line("OP + 5");
+    session.handle_line("ROLLBACK");
+    assert_eq!(get_value(&mut session), 0);
+}
+
+#[test]
+fn test_transaction_commit_failure_rolls_back() {
+    let mut session = Session::new(Arc::new(Registry::default())).unwrap();
+
+    session.handle_line("OP + 1");
+    session.handle_line("BEGIN");
+    session.handle_line("OP + 5");
+    session.handle_line("OP / 0");
+    match respond(&mut session, "COMMIT") {
+        Response::Error(CalculatorErrors::TransactionAborted(2, _)) => (),
+        _ => panic!("Should abort the transaction at operation 2"),
+    }
+    assert_eq!(get_value(&mut session), 1);
+}
+
+#[test]
+fn test_transaction_state_errors() {
+    let mut session = Session::new(Arc::new(Registry::default())).unwrap();
+
+    match respond(&mut session, "COMMIT") {
+        Response::Error(CalculatorErrors::NoTransaction) => (),
+        _ => panic!("Should throw NoTransaction error"),
+    }
+    session.handle_line("BEGIN");
+    match respond(&mut session, "BEGIN") {
+        Response::Error(CalculatorErrors::NestedTransaction) => (),
+        _ => panic!("Should throw NestedTransaction error"),
+    }
+}
+
+#[test]
+fn test_history_records_client_and_register() {
+    let registry = Arc::new(Registry::default());
+    let mut first = Session::new(Arc::clone(&registry)).unwrap();
+    let mut second = Session::new(registry).unwrap();
+
+    first.handle_line("OP + 5");
+    second.handle_line("OP * 2");
+    second.handle_line("USE other");
+    second.handle_line("OP + 1");
+
+    match respond(&mut first, "HISTORY") {
+        Response::History(entries) => {
+            assert_eq!(entries.len(), 2);
+            assert_eq!(entries[0].client, first.client);
+            assert_eq!(entries[1].client, second.client);
+            assert_eq!(entries[1].value, 10);
+        }
+        _ => panic!("Should return the history"),
+    }
+}
+
+#[test]
+fn test_replica_is_read_only_until_promoted() {
+    let mut registry = Registry::default();
+    registry.set_admin_token(Some("secret".to_owned()));
+    registry.set_replica(true);
+    let registry = Arc::new(registry);
+    let mut session = Session::new(Arc::clone(&registry)).unwrap();
+
+    match respond(&mut session, "OP + 5") {
+        Response::Error(CalculatorErrors::ReadOnlyReplica) => (),
+        _ => panic!("Should throw ReadOnlyReplica error"),
+    }
+    assert_eq!(get_value(&mut session), 0);
+    match respond(&mut session, "PROMOTE wrong") {
+        Response::Error(CalculatorErrors::Unauthorized) => (),
+        _ => panic!("Should throw Unauthorized error"),
+    }
+    session.handle_line("PROMOTE secret");
+    session.handle_line("OP + 5");
+    assert_eq!(get_value(&mut session), 5);
+    assert_eq!(registry.epoch(), 1);
+}
+
+#[test]
+fn test_fenced_primary_is_read_only() {
+    let mut registry = Registry::default();
+    registry.set_admin_token(Some("secret".to_owned()));
+    let mut session = Session::new(Arc::new(registry)).unwrap();
+
+    session.handle_line("OP + 5");
+    match respond(&mut session, "FENCE 0 secret") {
+        Response::Error(CalculatorErrors::StaleEpoch(0)) => (),
+        _ => panic!("Should throw StaleEpoch error"),
+    }
+    match respond(&mut session, "FENCE 1 secret") {
+        Response::Ok => (),
+        _ => panic!("Should accept the fence"),
+    }
+    match respond(&mut session, "OP + 1") {
+        Response::Error(CalculatorErrors::ReadOnlyReplica) => (),
+        _ => panic!("Should throw ReadOnlyReplica error"),
+    }
+    assert_eq!(get_value(&mut session), 5);
+}
+
+#[test]
+fn test_identified_request_applied_once() {
+    let mut session = Session::new(Arc::new(Registry::default())).unwrap();
+
+    session.handle_line("OP + 5 ID 1");
+    session.handle_line("OP + 5 ID 1");
+    assert_eq!(get_value(&mut session), 5);
+    match respond(&mut session, "OP / 0 ID 2") {
+        Response::Error(CalculatorErrors::DivisionByZero) => (),
+        _ => panic!("Should throw DivisionByZero error"),
+    }
+    match respond(&mut session, "OP / 0 ID 2") {
+        Response::Error(CalculatorErrors::DivisionByZero) => (),
+        _ => panic!("Should repeat the DivisionByZero error"),
+    }
+}
+
+#[test]
+fn test_identified_request_retried_after_error() {
+    let mut registry = Registry::default();
+    registry.set_admin_token(Some("secret".to_owned()));
+    registry.set_replica(true);
+    let mut session = Session::new(Arc::new(registry)).unwrap();
+
+    match respond(&mut session, "OP + 5 ID 1") {
+        Response::Error(CalculatorErrors::ReadOnlyReplica) => (),
+        _ => panic!("Should throw ReadOnlyReplica error"),
+    }
+    session.handle_line("PROMOTE secret");
+    match respond(&mut session, "OP + 5 ID 1") {
+        Response::Ok => (),
+        _ => panic!("Should apply the retried request"),
+    }
+    assert_eq!(get_value(&mut session), 5);
+}
+
+#[test]
+fn test_session_resumed_on_another_connection() {
+    let registry = Arc::new(Registry::default());
+    let mut first = Session::new(Arc::clone(&registry)).unwrap();
+    let mut second = Session::new(Arc::clone(&registry)).unwrap();
+    let mut other = Session::new(registry).unwrap();
+
+    first.handle_line("SESSION abc");
+    first.handle_line("OP + 5 ID 1");
+    second.handle_line("SESSION abc");
+    second.handle_line("OP + 5 ID 1");
+    assert_eq!(get_value(&mut second), 5);
+    other.handle_line("OP + 5 ID 1");
+    assert_eq!(get_value(&mut other), 10);
+}
+
+#[test]
+fn test_batch_reports_errors_by_index() {
+    let mut session = Session::new(Arc::new(Registry::default())).unwrap();
+
+    match session.handle_line("BATCH 4") {
+        Action::ReadBatch(4) => (),
+        _ => panic!("Should ask for the lines of the batch"),
+    }
+    let lines = ["OP + 5", "OP $ 3", "OP / 0", "OP * 2"].map(|line| line.to_owned());
+    match session.handle_batch(&lines) {
+        Response::Batch {
+            applied,
+            errors,
+            value,
+        } => {
+            assert_eq!(applied, 2);
+            assert_eq!(value, 10);
+            let indexes: Vec<usize> = errors.iter().map(|(index, _)| *index).collect();
+            assert_eq!(indexes, vec![2, 3]);
+        }
+        _ => panic!("Should summarize the batch"),
+    }
+    assert_eq!(get_value(&mut session), 10);
+}
+
+#[test]
+fn test_connection_actions() {
+    let mut session = Session::new(Arc::new(Registry::default())).unwrap();
+
+    match session.handle_line("REPLICATE") {
+        Action::Replicate => (),
+        _ => panic!("Should hand the connection over to the replicas"),
+    }
+    match respond(&mut session, "REPLICATE ID 3") {
+        Response::Error(CalculatorErrors::UnexpectedMessage(_)) => (),
+        _ => panic!("Should throw UnexpectedMessage error"),
+    }
+    match respond(&mut session, "VOTE 1 0 0 0") {
+        Response::Error(CalculatorErrors::UnexpectedMessage(_)) => (),
+        _ => panic!("Should throw UnexpectedMessage error outside a cluster"),
+    }
+}
+
+#[test]
+fn test_handle_operation() {
+    let mut session = Session::new(Arc::new(Registry::default())).unwrap();
+
+    match session.handle_operation(Operation::Add(5)) {
+        Response::Ok => (),
+        _ => panic!("Should apply the operation"),
+    }
+    match session.handle_operation(Operation::Get) {
+        Response::Value(value) => assert_eq!(value, 5),
+        _ => panic!("Should return a value"),
+    }
+}
+
+#[test]
+fn test_shutdown_is_recorded() {
+    let mut registry = Registry::default();
+    registry.set_admin_token(Some("secret".to_owned()));
+    let registry = Arc::new(registry);
+    let mut session = Session::new(Arc::clone(&registry)).unwrap();
+
+    assert!(!registry.is_shutting_down());
+    match respond(&mut session, "SHUTDOWN secret") {
+        Response::Ok => (),
+        _ => panic!("Should accept the shutdown"),
+    }
+    assert!(registry.is_shutting_down());
+}
+
+#[test]
+fn test_shutdown_requires_admin_token() {
+    let registry = Arc::new(Registry::default());
+    let mut session = Session::new(Arc::clone(&registry)).unwrap();
+
+    match respond(&mut session, "SHUTDOWN secret") {
+        Response::Error(CalculatorErrors::Unauthorized) => (),
+        _ => panic!("Should reject the shutdown without an admin token"),
+    }
+    assert!(!registry.is_shutting_down());
+}
+
+#[cfg(test)]
+fn atomic_registry() -> Arc<Registry> {
+    let mut registry = Registry::default();
+    registry.set_backend(crate::backend::Backend::Atomic);
+    Arc::new(registry)
+}
+
+#[test]
+fn test_atomic_backend_sessions_share_value() {
+    let registry = atomic_registry();
+    let mut first = Session::new(Arc::clone(&registry)).unwrap();
+    let mut second = Session::new(registry).unwrap();
+
+    first.handle_line("OP + 5");
+    second.handle_line("BEGIN");
+    second.handle_line("OP * 3");
+    second.handle_line("COMMIT");
+    first.handle_line("CAS 15 1");
+    assert_eq!(get_value(&mut second), 1);
+    second.handle_line("USE other");
+    assert_eq!(get_value(&mut second), 0);
+}
+
+#[test]
+fn test_atomic_backend_rejects_history() {
+    let mut session = Session::new(atomic_registry()).unwrap();
+
+    for message in ["HISTORY", "UNDO"] {
+        match respond(&mut session, message) {
+            Response::Error(CalculatorErrors::UnexpectedMessage(_)) => (),
+            _ => panic!("Should reject {} with the atomic backend", message),
+        }
+    }
+}
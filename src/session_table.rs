@@ -0,0 +1,89 @@
+use crate::request_cache::{DEFAULT_REQUEST_CACHE, RequestCache};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+/// The number of client sessions kept by a server unless configured otherwise.
+pub const DEFAULT_SESSIONS: usize = 10000;
+
+/// The cache of a client session, shared by every connection that resumes it.
+type SharedCache = Arc<Mutex<RequestCache>>;
+
+/// The request caches of the client sessions, by token.
+///
+/// Only the most recently used sessions are kept, up to the capacity of the table. A
+/// connection keeps using the cache of its session even if it is forgotten, but a client
+/// that resumes a forgotten session starts with an empty cache.
+#[derive(Debug)]
+pub struct SessionTable {
+    sessions: HashMap<String, (SharedCache, u64)>,
+    order: BTreeMap<u64, String>,
+    uses: u64,
+    capacity: usize,
+}
+
+impl Default for SessionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_SESSIONS)
+    }
+}
+
+impl SessionTable {
+    /// Creates an empty table.
+    ///
+    /// # Arguments:
+    ///
+    /// * `capacity` - The maximum number of sessions kept.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            sessions: HashMap::new(),
+            order: BTreeMap::new(),
+            uses: 0,
+            capacity,
+        }
+    }
+
+    /// Returns the cache of the session with the given token, creating it if it does not
+    /// exist, and forgets the least recently used session if the table is full.
+    pub fn get_or_create(&mut self, token: &str) -> SharedCache {
+        self.uses += 1;
+        let (cache, last_use) = self.sessions.entry(token.to_owned()).or_insert_with(|| {
+            let cache = RequestCache::new(DEFAULT_REQUEST_CACHE);
+            (Arc::new(Mutex::new(cache)), 0)
+        });
+        self.order.remove(last_use);
+        *last_use = self.uses;
+        let cache = Arc::clone(cache);
+        self.order.insert(self.uses, token.to_owned());
+
+        if self.sessions.len() > self.capacity
+            && let Some((_, oldest)) = self.order.pop_first()
+        {
+            self.sessions.remove(&oldest);
+        }
+        cache
+    }
+}
+
+#[test]
+fn test_same_token_same_cache() {
+    let mut table = SessionTable::new(2);
+    let first = table.get_or_create("a");
+    let second = table.get_or_create("a");
+    assert!(Arc::ptr_eq(&first, &second));
+}
+
+#[test]
+fn test_least_recently_used_forgotten() {
+    let mut table = SessionTable::new(2);
+    let a = table.get_or_create("a");
+    let b = table.get_or_create("b");
+    table.get_or_create("a");
+    table.get_or_create("c");
+
+    assert!(Arc::ptr_eq(&a, &table.get_or_create("a")));
+    assert!(!Arc::ptr_eq(&b, &table.get_or_create("b")));
+    assert_eq!(table.sessions.len(), 2);
+    assert_eq!(table.order.len(), 2);
+}
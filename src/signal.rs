@@ -0,0 +1,47 @@
+use crate::errors::CalculatorErrors;
+use std::{
+    ffi::c_int,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// The number of the `SIGINT` signal, the same on every unix target.
+const SIGINT: c_int = 2;
+/// The number of the `SIGTERM` signal, the same on every unix target.
+const SIGTERM: c_int = 15;
+/// The value `signal` returns when the handler cannot be installed.
+const SIG_ERR: usize = usize::MAX;
+
+/// Whether the process received `SIGTERM` or `SIGINT`.
+static RECEIVED: AtomicBool = AtomicBool::new(false);
+
+unsafe extern "C" {
+    /// Sets the handler of a signal, returning the previous one or `SIG_ERR`.
+    fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+}
+
+/// Records that a termination signal was received. It only stores to an atomic, which is
+/// safe to do inside a signal handler.
+extern "C" fn handle(_signum: c_int) {
+    RECEIVED.store(true, Ordering::SeqCst);
+}
+
+/// Makes `SIGTERM` and `SIGINT` request an orderly shutdown of the server, instead of
+/// terminating it.
+///
+/// # Errors:
+///
+/// Returns `CalculatorErrors::SignalFailure` if a handler cannot be installed.
+pub fn install() -> Result<(), CalculatorErrors> {
+    for signum in [SIGINT, SIGTERM] {
+        // SAFETY: the handler is a valid `extern "C"` function that only stores to an atomic.
+        if unsafe { signal(signum, handle) } == SIG_ERR {
+            return Err(CalculatorErrors::SignalFailure);
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether the process received `SIGTERM` or `SIGINT`.
+pub fn received() -> bool {
+    RECEIVED.load(Ordering::SeqCst)
+}
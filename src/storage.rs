@@ -0,0 +1,406 @@
+use crate::errors::CalculatorErrors;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex, MutexGuard,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// The name of the file holding the last snapshot, inside the data directory.
+const SNAPSHOT_FILE: &str = "snapshot";
+/// The name of the write-ahead log, inside the data directory.
+const LOG_FILE: &str = "wal";
+/// The keyword of the first line of the snapshot and the log.
+const EPOCH: &str = "EPOCH";
+
+/// The `(register, value)` pairs of a snapshot.
+pub type Values = Vec<(String, i128)>;
+/// The `(register, message)` records of the log.
+pub type Records = Vec<(String, String)>;
+
+/// The number of logged records after which a snapshot is taken unless configured otherwise.
+pub const DEFAULT_SNAPSHOT_INTERVAL: usize = 1000;
+
+/// The persistent state of the server, kept in a data directory.
+///
+/// Every change applied to a register is appended to a write-ahead log as a
+/// `<register> <message>` line. Periodically, the values of every register are written
+/// to a snapshot and the log is truncated. Both files start with an `EPOCH <n>` line:
+/// each snapshot increments the epoch, so a log whose epoch is older than the snapshot's
+/// (because the server stopped between writing the snapshot and truncating the log) is
+/// known to be already included in the snapshot.
+#[derive(Debug)]
+pub struct Storage {
+    dir: PathBuf,
+    log: Mutex<File>,
+    epoch: AtomicUsize,
+    records: AtomicUsize,
+    interval: usize,
+}
+
+impl Storage {
+    /// Opens the storage in the given directory, creating it if it does not exist.
+    ///
+    /// # Arguments:
+    ///
+    /// * `dir` - The data directory.
+    /// * `interval` - The number of logged records after which a snapshot is due.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::StorageFailure` if the directory or the log cannot be opened.
+    pub fn open(dir: &str, interval: usize) -> Result<Self, CalculatorErrors> {
+        let dir = PathBuf::from(dir);
+        if fs::create_dir_all(&dir).is_err() {
+            return Err(CalculatorErrors::StorageFailure);
+        }
+
+        let log = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(dir.join(LOG_FILE));
+
+        match log {
+            Ok(log) => Ok(Self {
+                dir,
+                log: Mutex::new(log),
+                epoch: AtomicUsize::new(0),
+                records: AtomicUsize::new(0),
+                interval,
+            }),
+            Err(_) => Err(CalculatorErrors::StorageFailure),
+        }
+    }
+
+    /// Reads the persisted state, and prepares the log to receive new records.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::StorageFailure` if the files cannot be read or written.
+    ///
+    /// # Returns:
+    ///
+    /// The values of the registers in the snapshot, and the `(register, message)` records
+    /// logged after it, in order.
+    pub fn recover(&self) -> Result<(Values, Records), CalculatorErrors> {
+        let (epoch, values) = match read_lines(&self.dir.join(SNAPSHOT_FILE))? {
+            Some((epoch, lines, _)) => (epoch, parse_values(lines)?),
+            None => (0, vec![]),
+        };
+        self.epoch.store(epoch, Ordering::SeqCst);
+
+        let mut log = self.lock()?;
+        let records = match read_lines(&self.dir.join(LOG_FILE))? {
+            Some((log_epoch, lines, complete)) if log_epoch == epoch => {
+                truncate_log(&mut log, complete)?;
+                parse_records(lines)?
+            }
+            _ => {
+                reset_log(&mut log, epoch)?;
+                vec![]
+            }
+        };
+
+        self.records.store(records.len(), Ordering::SeqCst);
+        Ok((values, records))
+    }
+
+    /// Appends records of a register to the log, waiting until they are durable.
+    ///
+    /// The records are written contiguously, so a transaction is never interleaved with
+    /// records of other registers.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::StorageFailure` if the log cannot be written.
+    ///
+    /// # Returns:
+    ///
+    /// Whether a snapshot is due.
+    pub fn append(&self, register: &str, messages: &[String]) -> Result<bool, CalculatorErrors> {
+        let mut lines = String::new();
+        for message in messages {
+            lines.push_str(&format!("{} {}\n", register, message));
+        }
+
+        let mut log = self.lock()?;
+        if log.write_all(lines.as_bytes()).is_err() || log.sync_data().is_err() {
+            return Err(CalculatorErrors::StorageFailure);
+        }
+
+        let records = self.records.fetch_add(messages.len(), Ordering::SeqCst) + messages.len();
+        Ok(records >= self.interval)
+    }
+
+    /// Writes a snapshot with the values of every register, and truncates the log.
+    ///
+    /// The caller must prevent any change to the registers until this returns, so that
+    /// the snapshot and the log stay consistent.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::StorageFailure` if the files cannot be written.
+    pub fn snapshot(&self, values: &[(String, i128)]) -> Result<(), CalculatorErrors> {
+        let mut log = self.lock()?;
+        let epoch = self.epoch.load(Ordering::SeqCst) + 1;
+
+        let mut content = format!("{} {}\n", EPOCH, epoch);
+        for (register, value) in values {
+            content.push_str(&format!("{} {}\n", register, value));
+        }
+
+        let temporary = self.dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+        write_durably(&temporary, &content)?;
+        if fs::rename(&temporary, self.dir.join(SNAPSHOT_FILE)).is_err() {
+            return Err(CalculatorErrors::StorageFailure);
+        }
+
+        reset_log(&mut log, epoch)?;
+        self.epoch.store(epoch, Ordering::SeqCst);
+        self.records.store(0, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Replaces the log with the given records of a register, which must include
+    /// everything the log held that is still needed. The new log is written to a temporary
+    /// file that then replaces the log, so a crash leaves either the old or the new one.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::StorageFailure` if the log cannot be written, in which
+    /// case it is left unchanged.
+    pub fn rewrite(&self, register: &str, messages: &[String]) -> Result<(), CalculatorErrors> {
+        let mut log = self.lock()?;
+        let mut content = format!("{} {}\n", EPOCH, self.epoch.load(Ordering::SeqCst));
+        for message in messages {
+            content.push_str(&format!("{} {}\n", register, message));
+        }
+
+        let temporary = self.dir.join(format!("{}.tmp", LOG_FILE));
+        write_durably(&temporary, &content)?;
+        if fs::rename(&temporary, self.dir.join(LOG_FILE)).is_err() {
+            return Err(CalculatorErrors::StorageFailure);
+        }
+
+        *log = match OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(self.dir.join(LOG_FILE))
+        {
+            Ok(file) => file,
+            Err(_) => return Err(CalculatorErrors::StorageFailure),
+        };
+        self.records.store(messages.len(), Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Locks the log.
+    fn lock(&self) -> Result<MutexGuard<'_, File>, CalculatorErrors> {
+        match self.log.lock() {
+            Ok(log) => Ok(log),
+            Err(_) => Err(CalculatorErrors::LockFailure),
+        }
+    }
+}
+
+/// Reads the complete lines of a file that starts with an `EPOCH <n>` line.
+///
+/// An incomplete last line, left by a crash in the middle of a write, is discarded.
+///
+/// # Returns:
+///
+/// The epoch, the remaining lines and the length in bytes of the complete lines, or `None`
+/// if the file does not exist or has no header.
+fn read_lines(path: &Path) -> Result<Option<(usize, Vec<String>, u64)>, CalculatorErrors> {
+    let mut content = String::new();
+    match File::open(path) {
+        Ok(mut file) => {
+            if file.read_to_string(&mut content).is_err() {
+                return Err(CalculatorErrors::StorageFailure);
+            }
+        }
+        Err(_) => return Ok(None),
+    }
+
+    let mut lines: Vec<String> = content.split('\n').map(|line| line.to_owned()).collect();
+    let incomplete = lines.pop().map_or(0, |line| line.len());
+    let complete = (content.len() - incomplete) as u64;
+
+    let header = lines.first().and_then(|header| header.strip_prefix(EPOCH));
+    match header.and_then(|epoch| epoch.trim().parse().ok()) {
+        Some(epoch) => Ok(Some((epoch, lines.split_off(1), complete))),
+        None => Ok(None),
+    }
+}
+
+/// Parses the `<register> <value>` lines of a snapshot.
+fn parse_values(lines: Vec<String>) -> Result<Values, CalculatorErrors> {
+    let mut values = vec![];
+    for line in lines {
+        match line.split_once(' ') {
+            Some((register, value)) => match value.parse() {
+                Ok(value) => values.push((register.to_owned(), value)),
+                Err(_) => return Err(CalculatorErrors::StorageFailure),
+            },
+            None => return Err(CalculatorErrors::StorageFailure),
+        }
+    }
+    Ok(values)
+}
+
+/// Parses the `<register> <message>` lines of the log.
+fn parse_records(lines: Vec<String>) -> Result<Records, CalculatorErrors> {
+    let mut records = vec![];
+    for line in lines {
+        match line.split_once(' ') {
+            Some((register, message)) => records.push((register.to_owned(), message.to_owned())),
+            None => return Err(CalculatorErrors::StorageFailure),
+        }
+    }
+    Ok(records)
+}
+
+/// Truncates the log to the given length, removing an incomplete last line, so that the
+/// next record is not appended to it.
+fn truncate_log(log: &mut File, length: u64) -> Result<(), CalculatorErrors> {
+    let truncated = match log.metadata() {
+        Ok(metadata) if metadata.len() == length => true,
+        Ok(_) => log.set_len(length).is_ok() && log.sync_data().is_ok(),
+        Err(_) => false,
+    };
+
+    if !truncated {
+        return Err(CalculatorErrors::StorageFailure);
+    }
+    Ok(())
+}
+
+/// Truncates the log, leaving only the header with the given epoch.
+fn reset_log(log: &mut File, epoch: usize) -> Result<(), CalculatorErrors> {
+    let header = format!("{} {}\n", EPOCH, epoch);
+    let reset = log.set_len(0).is_ok()
+        && log.seek(SeekFrom::Start(0)).is_ok()
+        && log.write_all(header.as_bytes()).is_ok()
+        && log.sync_data().is_ok();
+
+    if !reset {
+        return Err(CalculatorErrors::StorageFailure);
+    }
+    Ok(())
+}
+
+/// Writes a file, waiting until its content is durable.
+fn write_durably(path: &Path, content: &str) -> Result<(), CalculatorErrors> {
+    let written = match File::create(path) {
+        Ok(mut file) => file.write_all(content.as_bytes()).is_ok() && file.sync_all().is_ok(),
+        Err(_) => false,
+    };
+
+    if !written {
+        return Err(CalculatorErrors::StorageFailure);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+fn test_dir(name: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("tpi_storage_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir.to_string_lossy().into_owned()
+}
+
+#[test]
+fn test_recover_empty_dir() {
+    let dir = test_dir("empty");
+    let storage = Storage::open(&dir, 10).unwrap();
+    let (values, records) = storage.recover().unwrap();
+    assert!(values.is_empty());
+    assert!(records.is_empty());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_recover_appended_records() {
+    let dir = test_dir("append");
+    let storage = Storage::open(&dir, 10).unwrap();
+    storage.recover().unwrap();
+    storage.append("default", &["OP + 5".to_owned()]).unwrap();
+    storage
+        .append("a", &["BEGIN".to_owned(), "COMMIT".to_owned()])
+        .unwrap();
+
+    let (_, records) = Storage::open(&dir, 10).unwrap().recover().unwrap();
+    let messages: Vec<&str> = records.iter().map(|(_, m)| m.as_str()).collect();
+    assert_eq!(messages, vec!["OP + 5", "BEGIN", "COMMIT"]);
+    assert_eq!(records[1].0, "a");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_snapshot_truncates_log() {
+    let dir = test_dir("snapshot");
+    let storage = Storage::open(&dir, 2).unwrap();
+    storage.recover().unwrap();
+    assert!(!storage.append("default", &["OP + 5".to_owned()]).unwrap());
+    assert!(storage.append("default", &["OP + 1".to_owned()]).unwrap());
+    storage.snapshot(&[("default".to_owned(), 6)]).unwrap();
+    storage.append("default", &["OP * 2".to_owned()]).unwrap();
+
+    let (values, records) = Storage::open(&dir, 2).unwrap().recover().unwrap();
+    assert_eq!(values, vec![("default".to_owned(), 6)]);
+    assert_eq!(records, vec![("default".to_owned(), "OP * 2".to_owned())]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_rewrite_replaces_log() {
+    let dir = test_dir("rewrite");
+    let storage = Storage::open(&dir, 10).unwrap();
+    storage.recover().unwrap();
+    storage.append("raft", &["ENTRY 1".to_owned()]).unwrap();
+    storage.rewrite("raft", &["SNAPSHOT 1".to_owned()]).unwrap();
+    storage.append("raft", &["ENTRY 2".to_owned()]).unwrap();
+
+    let (_, records) = Storage::open(&dir, 10).unwrap().recover().unwrap();
+    let messages: Vec<&str> = records.iter().map(|(_, m)| m.as_str()).collect();
+    assert_eq!(messages, vec!["SNAPSHOT 1", "ENTRY 2"]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_recover_discards_stale_log() {
+    let dir = test_dir("stale");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(Path::new(&dir).join(SNAPSHOT_FILE), "EPOCH 2\ndefault 6\n").unwrap();
+    fs::write(Path::new(&dir).join(LOG_FILE), "EPOCH 1\ndefault OP + 1\n").unwrap();
+
+    let (values, records) = Storage::open(&dir, 10).unwrap().recover().unwrap();
+    assert_eq!(values, vec![("default".to_owned(), 6)]);
+    assert!(records.is_empty());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_recover_discards_incomplete_line() {
+    let dir = test_dir("incomplete");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        Path::new(&dir).join(LOG_FILE),
+        "EPOCH 0\ndefault OP + 1\ndefault OP",
+    )
+    .unwrap();
+
+    let storage = Storage::open(&dir, 10).unwrap();
+    let (_, records) = storage.recover().unwrap();
+    assert_eq!(records, vec![("default".to_owned(), "OP + 1".to_owned())]);
+    storage.append("default", &["OP + 5".to_owned()]).unwrap();
+
+    let (_, records) = Storage::open(&dir, 10).unwrap().recover().unwrap();
+    let messages: Vec<&str> = records.iter().map(|(_, m)| m.as_str()).collect();
+    assert_eq!(messages, vec!["OP + 1", "OP + 5"]);
+    let _ = fs::remove_dir_all(&dir);
+}
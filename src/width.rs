@@ -0,0 +1,207 @@
+use crate::errors::CalculatorErrors;
+use std::{fmt, str::FromStr};
+
+/// The largest number of bits a calculator value can have.
+const MAX_BITS: u32 = 64;
+
+/// The numeric width of the values handled by a calculator.
+///
+/// A width is written as `u<bits>` for unsigned values or `i<bits>` for signed
+/// (two's complement) values, with `bits` between 1 and 64, e.g. `u8`, `u12` or `i64`.
+/// Values are always stored in an `i128`, which is wide enough to hold the result of
+/// any operation between two in-range operands before it is brought back to the width.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Width {
+    bits: u32,
+    signed: bool,
+}
+
+impl Default for Width {
+    /// The width used by the original protocol: an 8-bit unsigned integer (u8).
+    fn default() -> Self {
+        Self {
+            bits: 8,
+            signed: false,
+        }
+    }
+}
+
+impl FromStr for Width {
+    type Err = CalculatorErrors;
+
+    /// Parses a width in the format `u<bits>` or `i<bits>`.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::InvalidArgument` if the format is wrong or the number
+    /// of bits is not between 1 and 64.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let signed = match s.chars().next() {
+            Some('u') => false,
+            Some('i') => true,
+            _ => return Err(CalculatorErrors::InvalidArgument(s.to_owned())),
+        };
+
+        match s[1..].parse::<u32>() {
+            Ok(bits) if (1..=MAX_BITS).contains(&bits) => Ok(Self { bits, signed }),
+            _ => Err(CalculatorErrors::InvalidArgument(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for Width {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = if self.signed { "i" } else { "u" };
+        write!(f, "{}{}", prefix, self.bits)
+    }
+}
+
+impl Width {
+    /// Returns the number of bits of the width.
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Returns whether the width represents signed values.
+    pub fn is_signed(&self) -> bool {
+        self.signed
+    }
+
+    /// Returns the smallest value representable with this width.
+    pub fn min(&self) -> i128 {
+        if self.signed {
+            -(1 << (self.bits - 1))
+        } else {
+            0
+        }
+    }
+
+    /// Returns the largest value representable with this width.
+    pub fn max(&self) -> i128 {
+        if self.signed {
+            (1 << (self.bits - 1)) - 1
+        } else {
+            (1 << self.bits) - 1
+        }
+    }
+
+    /// Returns whether the given value is representable with this width.
+    pub fn contains(&self, value: i128) -> bool {
+        self.min() <= value && value <= self.max()
+    }
+
+    /// Brings any value into the range of the width, wrapping around its boundaries.
+    ///
+    /// # Arguments:
+    ///
+    /// * `value` - The value to wrap.
+    ///
+    /// # Returns:
+    ///
+    /// The value modulo 2^bits, interpreted as signed or unsigned according to the width.
+    pub fn wrap(&self, value: i128) -> i128 {
+        let modulus: i128 = 1 << self.bits;
+        let wrapped = value & (modulus - 1);
+
+        if self.signed && wrapped > self.max() {
+            wrapped - modulus
+        } else {
+            wrapped
+        }
+    }
+
+    /// Brings any value into the range of the width, clamping it to its boundaries.
+    pub fn saturate(&self, value: i128) -> i128 {
+        value.clamp(self.min(), self.max())
+    }
+
+    /// Parses an operand, checking that it is representable with this width.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::InvalidInteger` if the operand is not an integer or
+    /// is out of range.
+    pub fn parse(&self, operand: &str) -> Result<i128, CalculatorErrors> {
+        match operand.parse::<i128>() {
+            Ok(value) if self.contains(value) => Ok(value),
+            _ => Err(CalculatorErrors::InvalidInteger(operand.to_owned())),
+        }
+    }
+}
+
+#[test]
+fn test_parse_width_unsigned() {
+    let width = Width::from_str("u16").unwrap();
+    assert_eq!(width.min(), 0);
+    assert_eq!(width.max(), 65535);
+}
+
+#[test]
+fn test_parse_width_signed() {
+    let width = Width::from_str("i64").unwrap();
+    assert_eq!(width.min(), i64::MIN as i128);
+    assert_eq!(width.max(), i64::MAX as i128);
+}
+
+#[test]
+fn test_parse_width_arbitrary() {
+    let width = Width::from_str("u12").unwrap();
+    assert_eq!(width.max(), 4095);
+}
+
+#[test]
+fn test_parse_width_invalid() {
+    for s in ["", "u", "u0", "u65", "f32", "i-8"] {
+        match Width::from_str(s) {
+            Ok(_) => panic!("Should throw an error for {:?}", s),
+            Err(e) => match e {
+                CalculatorErrors::InvalidArgument(_) => (),
+                _ => panic!("Should throw InvalidArgument error, got: {:?}", e),
+            },
+        }
+    }
+}
+
+#[test]
+fn test_wrap_unsigned() {
+    let width = Width::default();
+    assert_eq!(width.wrap(256), 0);
+    assert_eq!(width.wrap(-1), 255);
+}
+
+#[test]
+fn test_wrap_signed() {
+    let width = Width::from_str("i8").unwrap();
+    assert_eq!(width.wrap(128), -128);
+    assert_eq!(width.wrap(-129), 127);
+}
+
+#[test]
+fn test_wrap_64_bits() {
+    let width = Width::from_str("u64").unwrap();
+    assert_eq!(width.wrap(u64::MAX as i128 + 1), 0);
+}
+
+#[test]
+fn test_saturate() {
+    let width = Width::from_str("i8").unwrap();
+    assert_eq!(width.saturate(200), 127);
+    assert_eq!(width.saturate(-200), -128);
+    assert_eq!(width.saturate(5), 5);
+}
+
+#[test]
+fn test_display_width() {
+    assert_eq!(Width::from_str("i32").unwrap().to_string(), "i32");
+}
+
+#[test]
+fn test_parse_operand_out_of_range() {
+    match Width::default().parse("256") {
+        Ok(_) => panic!("Should throw an error"),
+        Err(e) => match e {
+            CalculatorErrors::InvalidInteger(_) => (),
+            _ => panic!("Should throw InvalidInteger error, got: {:?}", e),
+        },
+    }
+}
@@ -0,0 +1,233 @@
+use crate::{errors::CalculatorErrors, logger::Logger};
+use std::{
+    collections::HashMap,
+    net::{Shutdown, TcpStream},
+    sync::{
+        Arc, Condvar, Mutex,
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// The default number of worker threads of a pool.
+pub const DEFAULT_WORKERS: usize = 64;
+/// The default number of connections that may wait for a worker.
+pub const DEFAULT_ACCEPT_QUEUE: usize = 64;
+
+/// A connection waiting for a worker, with the number that identifies it in the pool.
+type Job = (usize, TcpStream);
+
+/// The connections queued or in progress, by number, and the condition variable notified
+/// each time one of them finishes.
+type Active = (Mutex<HashMap<usize, TcpStream>>, Condvar);
+
+/// A fixed number of worker threads that handle connections, fed by a bounded queue.
+///
+/// The pool keeps a handle to every connection queued or in progress, so it can close
+/// the ones that outlive the grace period of a shutdown; a worker drops the handle once
+/// it finishes the connection.
+pub struct WorkerPool {
+    /// The sender of the queue of connections, dropped to stop the workers.
+    sender: SyncSender<Job>,
+    /// The worker threads.
+    workers: Vec<JoinHandle<()>>,
+    /// The connections queued or in progress, by number.
+    active: Arc<Active>,
+    /// The number of the next connection.
+    next: usize,
+}
+
+impl WorkerPool {
+    /// Spawns the worker threads of a pool.
+    ///
+    /// # Arguments:
+    ///
+    /// * `workers` - The number of worker threads.
+    /// * `queue` - The number of connections that may wait for a worker.
+    /// * `handle` - The handler of each connection.
+    pub fn spawn<F>(workers: usize, queue: usize, handle: F) -> Self
+    where
+        F: FnOnce(TcpStream) + Clone + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(queue);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let active = Arc::new((Mutex::new(HashMap::new()), Condvar::new()));
+        let workers = (0..workers)
+            .map(|_| {
+                let (receiver, active) = (Arc::clone(&receiver), Arc::clone(&active));
+                let handle = handle.clone();
+                thread::spawn(move || work(&receiver, &active, handle))
+            })
+            .collect();
+
+        Self {
+            sender,
+            workers,
+            active,
+            next: 0,
+        }
+    }
+
+    /// Queues a connection for the next free worker.
+    ///
+    /// # Arguments:
+    ///
+    /// * `stream` - The connection.
+    ///
+    /// # Errors:
+    ///
+    /// Returns the connection back if the queue is full or the connection cannot be
+    /// tracked, so the caller can reject it.
+    pub fn submit(&mut self, stream: TcpStream) -> Result<(), TcpStream> {
+        let closer = match stream
+            .set_nonblocking(false)
+            .and_then(|()| stream.try_clone())
+        {
+            Ok(closer) => closer,
+            Err(_) => return Err(stream),
+        };
+        let mut active = match self.active.0.lock() {
+            Ok(active) => active,
+            Err(_) => return Err(stream),
+        };
+
+        let id = self.next;
+        match self.sender.try_send((id, stream)) {
+            Ok(()) => {
+                self.next += 1;
+                active.insert(id, closer);
+                Ok(())
+            }
+            Err(TrySendError::Full((_, stream)) | TrySendError::Disconnected((_, stream))) => {
+                Err(stream)
+            }
+        }
+    }
+
+    /// Waits for the workers to finish every connection queued or in progress, and then
+    /// stops them. The reading half of the connections that are
+    /// still open once the grace period expires is closed, so their handlers see the end
+    /// of the connection once they answer the message in progress, if any.
+    ///
+    /// # Arguments:
+    ///
+    /// * `grace` - The time the connections have to finish before they are closed.
+    /// * `logger` - The logger of the server.
+    pub fn drain(self, grace: Duration, logger: &Logger) {
+        if let Err(e) = self.close_lingering(grace) {
+            logger.report(e);
+        }
+
+        // The sender is dropped at the end of the block, so idle workers stop waiting.
+        {
+            let _sender = self.sender;
+        }
+        for worker in self.workers {
+            if worker.join().is_err() {
+                logger.report(CalculatorErrors::JoinFailure);
+            }
+        }
+    }
+
+    /// Waits for every connection queued or in progress to finish, up to the grace period,
+    /// and then closes the reading half of the ones still open.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `CalculatorErrors::LockFailure` if the mutex of the connections is poisoned.
+    fn close_lingering(&self, grace: Duration) -> Result<(), CalculatorErrors> {
+        let (active, finished) = &*self.active;
+        let waited = match active.lock() {
+            Ok(active) => finished.wait_timeout_while(active, grace, |active| !active.is_empty()),
+            Err(_) => return Err(CalculatorErrors::LockFailure),
+        };
+        let active = match waited {
+            Ok((active, _)) => active,
+            Err(_) => return Err(CalculatorErrors::LockFailure),
+        };
+
+        for closer in active.values() {
+            let _ = closer.shutdown(Shutdown::Read);
+        }
+        Ok(())
+    }
+}
+
+/// Handles the connections of the queue, one at a time, until the queue is closed.
+fn work<F>(receiver: &Mutex<Receiver<Job>>, active: &Active, handle: F)
+where
+    F: FnOnce(TcpStream) + Clone,
+{
+    loop {
+        let job = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return,
+        };
+        let (id, stream) = match job {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+
+        handle.clone()(stream);
+        let (active, finished) = active;
+        if let Ok(mut active) = active.lock() {
+            active.remove(&id);
+            finished.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+fn connect(listener: &std::net::TcpListener) -> TcpStream {
+    let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    listener.accept().unwrap().0
+}
+
+#[test]
+fn test_pool_rejects_when_busy() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let (started, wait) = mpsc::channel();
+    let (release, released) = mpsc::channel::<()>();
+    let released = Arc::new(Mutex::new(released));
+    let mut pool = WorkerPool::spawn(1, 1, move |_stream| {
+        started.send(()).unwrap();
+        let _ = released.lock().unwrap().recv();
+    });
+
+    assert!(pool.submit(connect(&listener)).is_ok());
+    wait.recv().unwrap();
+    assert!(pool.submit(connect(&listener)).is_ok());
+    assert!(pool.submit(connect(&listener)).is_err());
+
+    release.send(()).unwrap();
+    wait.recv().unwrap();
+    release.send(()).unwrap();
+    pool.drain(Duration::from_secs(1), &Logger::default());
+}
+
+#[test]
+fn test_drain_waits_for_connections_in_progress() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let (finished, done) = mpsc::channel();
+    let mut pool = WorkerPool::spawn(1, 1, move |_stream| {
+        thread::sleep(Duration::from_millis(100));
+        finished.send(()).unwrap();
+    });
+
+    assert!(pool.submit(connect(&listener)).is_ok());
+    pool.drain(Duration::from_secs(5), &Logger::default());
+    assert!(done.try_recv().is_ok());
+}
+
+#[test]
+fn test_drain_closes_connections_after_grace() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut pool = WorkerPool::spawn(1, 1, move |stream| {
+        let _ = std::io::Read::read(&mut &stream, &mut [0; 1]);
+    });
+
+    assert!(pool.submit(listener.accept().unwrap().0).is_ok());
+    pool.drain(Duration::from_millis(50), &Logger::default());
+}
@@ -1,4 +1,6 @@
 use std::fs::{remove_file, write};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
 use std::process::{Child, Command, ExitStatus};
 use std::thread;
 use std::time::Duration;
@@ -9,12 +11,17 @@ struct TestServer {
 
 impl TestServer {
     fn start(addr: &str) -> Result<Self, &'static str> {
+        Self::start_with_args(addr, &[])
+    }
+
+    fn start_with_args(addr: &str, args: &[&str]) -> Result<Self, &'static str> {
         let process = match Command::new("cargo")
             .arg("run")
             .arg("--bin")
             .arg("server")
             .arg("--")
             .arg(addr)
+            .args(args)
             .spawn()
         {
             Ok(output) => output,
@@ -56,6 +63,54 @@ fn run_client_with_input_file(
     Ok((output.status, stdout))
 }
 
+fn send_line(addr: &str, line: &str) -> Result<String, &'static str> {
+    let mut stream = match TcpStream::connect(addr) {
+        Ok(stream) => stream,
+        Err(_) => return Err("Error connecting to server"),
+    };
+    if stream.write_all(format!("{}\n", line).as_bytes()).is_err() {
+        return Err("Error writing to server");
+    }
+
+    let mut response = String::new();
+    match BufReader::new(stream).read_line(&mut response) {
+        Ok(_) => Ok(response.trim_end().to_owned()),
+        Err(_) => Err("Error reading from server"),
+    }
+}
+
+fn start_cluster(nodes: &[&str]) -> Vec<TestServer> {
+    let cluster = nodes.join(",");
+    nodes
+        .iter()
+        .map(|node| TestServer::start_with_args(node, &["--cluster", &cluster]).unwrap())
+        .collect()
+}
+
+fn wait_for_leader(nodes: &[&str]) -> Option<String> {
+    for _ in 0..50 {
+        for node in nodes {
+            if send_line(node, "OP + 0") == Ok("OK".to_owned()) {
+                return Some(node.to_string());
+            }
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    None
+}
+
+fn wait_for_value(addr: &str, expected: &str) -> String {
+    let mut value = String::new();
+    for _ in 0..30 {
+        value = send_line(addr, "GET").unwrap_or_default();
+        if value == expected {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    value
+}
+
 fn run_multiple_clients_concurrent(
     addr: &str,
     inputs_paths: Vec<&str>,
@@ -338,7 +393,7 @@ fn test_invalid_operations_in_file() {
 
     write(
         "tests/data/invalid_ops_test.txt",
-        "+ 5\n% 3\n* 2\nINVALID COMMAND\n- 1\n",
+        "+ 5\n$ 3\n* 2\nINVALID COMMAND\n- 1\n",
     )
     .unwrap();
 
@@ -378,3 +433,247 @@ fn test_empty_file() {
         stdout
     );
 }
+
+#[test]
+fn test_server_invalid_width() {
+    let output = Command::new("cargo")
+        .arg("run")
+        .arg("--bin")
+        .arg("server")
+        .arg("--")
+        .arg("127.0.0.1:8092")
+        .arg("--width")
+        .arg("f32")
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success(), "Should end successful");
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("ERROR \"invalid argument: f32\""),
+        "Should show invalid argument error. Got: '{}'",
+        stderr
+    );
+}
+
+#[test]
+fn test_cluster_replicates_to_followers() {
+    let nodes = ["127.0.0.1:8093", "127.0.0.1:8094", "127.0.0.1:8095"];
+    let servers = start_cluster(&nodes);
+
+    let leader = wait_for_leader(&nodes).unwrap();
+    let (status, stdout) = run_client_with_input_file(&leader, "tests/data/a.txt").unwrap();
+    let followers: Vec<&str> = nodes.into_iter().filter(|node| *node != leader).collect();
+    let redirect = send_line(followers[0], "OP + 1").unwrap();
+    let values: Vec<String> = followers
+        .iter()
+        .map(|node| wait_for_value(node, "VALUE 31"))
+        .collect();
+
+    for server in servers {
+        server.stop();
+    }
+
+    assert!(status.success(), "The program should have succeeded");
+    assert!(stdout.contains("31"), "Got: '{}'", stdout);
+    assert_eq!(redirect, format!("ERROR \"not leader: {}\"", leader));
+    assert_eq!(values, vec!["VALUE 31", "VALUE 31"]);
+}
+
+#[test]
+fn test_cluster_survives_leader_failure() {
+    let nodes = ["127.0.0.1:8096", "127.0.0.1:8097", "127.0.0.1:8098"];
+    let mut servers = start_cluster(&nodes);
+
+    let leader = wait_for_leader(&nodes).unwrap();
+    run_client_with_input_file(&leader, "tests/data/a.txt").unwrap();
+    let position = nodes.iter().position(|node| *node == leader).unwrap();
+    servers.remove(position).stop();
+
+    let survivors: Vec<&str> = nodes.into_iter().filter(|node| *node != leader).collect();
+    let new_leader = wait_for_leader(&survivors).unwrap();
+    let applied = send_line(&new_leader, "OP + 1").unwrap();
+    let follower = survivors
+        .into_iter()
+        .find(|node| *node != new_leader)
+        .unwrap();
+    let value = wait_for_value(follower, "VALUE 32");
+
+    for server in servers {
+        server.stop();
+    }
+
+    assert_eq!(applied, "OK");
+    assert_eq!(value, "VALUE 32");
+}
+
+#[test]
+fn test_cluster_node_recovers_state_after_restart() {
+    let node = "127.0.0.1:8131";
+    let dir = std::env::temp_dir().join(format!("tpi_cluster_restart_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let dir = dir.to_string_lossy().into_owned();
+    let args = ["--cluster", node, "--data-dir", &dir];
+
+    let server = TestServer::start_with_args(node, &args).unwrap();
+    let leader = wait_for_leader(&[node]);
+    let applied = send_line(node, "OP + 5").unwrap();
+    server.stop();
+    thread::sleep(Duration::from_millis(200));
+
+    let server = TestServer::start_with_args(node, &args).unwrap();
+    let restarted = wait_for_leader(&[node]);
+    let value = send_line(node, "GET").unwrap();
+    server.stop();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(leader, Some(node.to_owned()));
+    assert_eq!(applied, "OK");
+    assert_eq!(restarted, Some(node.to_owned()));
+    assert_eq!(value, "VALUE 5");
+}
+
+#[test]
+fn test_cluster_node_recovers_snapshot_after_restart() {
+    let node = "127.0.0.1:8137";
+    let dir = std::env::temp_dir().join(format!("tpi_cluster_snapshot_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.to_string_lossy().into_owned();
+    let args = [
+        "--cluster",
+        node,
+        "--data-dir",
+        &path,
+        "--snapshot-every",
+        "2",
+    ];
+
+    let server = TestServer::start_with_args(node, &args).unwrap();
+    wait_for_leader(&[node]);
+    for _ in 0..5 {
+        send_line(node, "OP + 2").unwrap();
+    }
+    server.stop();
+    thread::sleep(Duration::from_millis(200));
+    let log = std::fs::read_to_string(dir.join("raft").join("wal")).unwrap();
+
+    let server = TestServer::start_with_args(node, &args).unwrap();
+    let restarted = wait_for_leader(&[node]);
+    let value = send_line(node, "GET").unwrap();
+    server.stop();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert!(log.contains("raft SNAPSHOT"), "Got: '{}'", log);
+    assert!(log.lines().count() < 8, "Got: '{}'", log);
+    assert_eq!(restarted, Some(node.to_owned()));
+    assert_eq!(value, "VALUE 10");
+}
+
+#[test]
+fn test_cluster_sends_snapshot_to_lagging_follower() {
+    let nodes = ["127.0.0.1:8138", "127.0.0.1:8139", "127.0.0.1:8140"];
+    let cluster = nodes.join(",");
+    let args = ["--cluster", &cluster, "--snapshot-every", "2"];
+    let mut servers: Vec<TestServer> = nodes
+        .iter()
+        .map(|node| TestServer::start_with_args(node, &args).unwrap())
+        .collect();
+
+    let leader = wait_for_leader(&nodes).unwrap();
+    let position = nodes.iter().position(|node| *node != leader).unwrap();
+    servers.remove(position).stop();
+    for _ in 0..5 {
+        send_line(&leader, "OP + 2").unwrap();
+    }
+
+    servers.push(TestServer::start_with_args(nodes[position], &args).unwrap());
+    let value = wait_for_value(nodes[position], "VALUE 10");
+
+    for server in servers {
+        server.stop();
+    }
+
+    assert_eq!(value, "VALUE 10");
+}
+
+#[test]
+fn test_cluster_applies_batch_as_one_entry() {
+    let node = "127.0.0.1:8136";
+    let server = TestServer::start_with_args(node, &["--cluster", node]).unwrap();
+    let leader = wait_for_leader(&[node]);
+
+    let mut stream = TcpStream::connect(node).unwrap();
+    stream
+        .write_all(b"BATCH 4\nOP + 5\nOP $ 3\nOP / 0\nOP * 2\n")
+        .unwrap();
+    let mut reader = BufReader::new(stream);
+    let lines: Vec<String> = (0..3)
+        .map(|_| {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            line.trim_end().to_owned()
+        })
+        .collect();
+    server.stop();
+
+    assert_eq!(leader, Some(node.to_owned()));
+    assert_eq!(lines[0], "BATCH 2 2 VALUE 10");
+    assert!(lines[1].starts_with("LINE 2 ERROR"), "Got: '{}'", lines[1]);
+    assert_eq!(lines[2], "LINE 3 ERROR \"division by zero\"");
+}
+
+#[test]
+fn test_client_fails_over_to_reachable_server() {
+    let server = TestServer::start("127.0.0.1:8099").unwrap();
+    let (status, stdout) =
+        run_client_with_input_file("127.0.0.1:1,127.0.0.1:8099", "tests/data/a.txt").unwrap();
+
+    server.stop();
+
+    assert!(status.success(), "The program should have succeeded");
+    assert!(stdout.contains("31"), "Got: '{}'", stdout);
+}
+
+#[test]
+fn test_client_pipeline_reports_error_lines() {
+    let server = TestServer::start("127.0.0.1:8120").unwrap();
+
+    write("tests/data/pipeline_test.txt", "+ 5\n$ 3\n* 2\n/ 0\n- 1\n").unwrap();
+
+    let output = Command::new("cargo")
+        .arg("run")
+        .arg("--bin")
+        .arg("client")
+        .arg("--")
+        .arg("127.0.0.1:8120")
+        .arg("tests/data/pipeline_test.txt")
+        .arg("--pipeline")
+        .arg("2")
+        .output()
+        .unwrap();
+
+    let _ = remove_file("tests/data/pipeline_test.txt");
+    server.stop();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "The program should have succeeded");
+    assert!(stdout.contains("9"), "Got: '{}'", stdout);
+    assert!(stderr.contains("line 2: ERROR"), "Got: '{}'", stderr);
+    assert!(stderr.contains("line 4: ERROR"), "Got: '{}'", stderr);
+}
+
+#[test]
+fn test_use_rejects_registers_over_the_maximum() {
+    let addr = "127.0.0.1:8141";
+    let server = TestServer::start_with_args(addr, &["--max-registers", "2"]).unwrap();
+    let first = send_line(addr, "USE a").unwrap();
+    let second = send_line(addr, "USE b").unwrap();
+    let existing = send_line(addr, "USE a").unwrap();
+    server.stop();
+
+    assert_eq!(first, "OK");
+    assert_eq!(second, "ERROR \"too many registers: 2\"");
+    assert_eq!(existing, "OK");
+}
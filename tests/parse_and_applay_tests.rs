@@ -127,3 +127,55 @@ fn test_parse_and_apply_combined() {
         .unwrap();
     assert_eq!(calculator.value(), 45);
 }
+
+#[test]
+fn test_parse_and_apply_mod() {
+    let mut calculator = Calculator::default();
+    calculator
+        .apply(Operation::from_str("OP + 23").unwrap())
+        .unwrap();
+    calculator
+        .apply(Operation::from_str("OP % 7").unwrap())
+        .unwrap();
+    assert_eq!(calculator.value(), 2);
+}
+
+#[test]
+fn test_parse_and_apply_mod_by_zero() {
+    let mut calculator = Calculator::default();
+    let result = calculator.apply(Operation::from_str("OP % 0").unwrap());
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_parse_and_apply_pow() {
+    let mut calculator = Calculator::default();
+    calculator
+        .apply(Operation::from_str("OP + 2").unwrap())
+        .unwrap();
+    calculator
+        .apply(Operation::from_str("OP ^ 7").unwrap())
+        .unwrap();
+    assert_eq!(calculator.value(), 128);
+}
+
+#[test]
+fn test_parse_and_apply_bitwise_and_shifts() {
+    let mut calculator = Calculator::default();
+    calculator
+        .apply(Operation::from_str("OP | 5").unwrap())
+        .unwrap();
+    calculator
+        .apply(Operation::from_str("OP << 4").unwrap())
+        .unwrap();
+    calculator
+        .apply(Operation::from_str("OP xor 255").unwrap())
+        .unwrap();
+    calculator
+        .apply(Operation::from_str("OP & 240").unwrap())
+        .unwrap();
+    calculator
+        .apply(Operation::from_str("OP >> 4").unwrap())
+        .unwrap();
+    assert_eq!(calculator.value(), 10);
+}
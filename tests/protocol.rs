@@ -0,0 +1,863 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::Duration;
+
+struct TestServer {
+    process: Child,
+}
+
+impl TestServer {
+    fn start(args: &[&str]) -> Result<Self, &'static str> {
+        let process = match Command::new("cargo")
+            .arg("run")
+            .arg("--bin")
+            .arg("server")
+            .arg("--")
+            .args(args)
+            .spawn()
+        {
+            Ok(output) => output,
+            Err(_) => return Err("Error starting server"),
+        };
+
+        thread::sleep(Duration::from_millis(500));
+
+        Ok(TestServer { process })
+    }
+
+    fn stop(mut self) {
+        let _ = self.process.kill();
+    }
+
+    fn wait_exit(&mut self) -> bool {
+        for _ in 0..50 {
+            if let Ok(Some(_)) = self.process.try_wait() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        false
+    }
+}
+
+struct TestClient {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl TestClient {
+    fn connect(addr: &str) -> Result<Self, &'static str> {
+        let stream = match TcpStream::connect(addr) {
+            Ok(stream) => stream,
+            Err(_) => return Err("Error connecting to server"),
+        };
+        let reader = match stream.try_clone() {
+            Ok(reader) => BufReader::new(reader),
+            Err(_) => return Err("Error cloning stream"),
+        };
+
+        Ok(TestClient { stream, reader })
+    }
+
+    fn send(&mut self, line: &str) -> String {
+        self.stream
+            .write_all(format!("{}\n", line).as_bytes())
+            .unwrap();
+        self.read_line()
+    }
+
+    fn read_line(&mut self) -> String {
+        let mut response = String::new();
+        self.reader.read_line(&mut response).unwrap();
+        response.trim_end().to_owned()
+    }
+}
+
+#[test]
+fn test_use_named_registers_are_independent() {
+    let server = TestServer::start(&["127.0.0.1:8100"]).unwrap();
+    let mut team_a = TestClient::connect("127.0.0.1:8100").unwrap();
+    let mut team_b = TestClient::connect("127.0.0.1:8100").unwrap();
+
+    assert_eq!(team_a.send("USE team_a"), "OK");
+    assert_eq!(team_b.send("USE team_b"), "OK");
+    assert_eq!(team_a.send("OP + 5"), "OK");
+    assert_eq!(team_b.send("OP + 7"), "OK");
+    let value_a = team_a.send("GET");
+    let value_b = team_b.send("GET");
+
+    server.stop();
+
+    assert_eq!(value_a, "VALUE 5");
+    assert_eq!(value_b, "VALUE 7");
+}
+
+#[test]
+fn test_default_register_is_shared() {
+    let server = TestServer::start(&["127.0.0.1:8101"]).unwrap();
+    let mut first = TestClient::connect("127.0.0.1:8101").unwrap();
+    let mut second = TestClient::connect("127.0.0.1:8101").unwrap();
+
+    first.send("OP + 3");
+    second.send("USE default");
+    second.send("OP + 4");
+    let value = first.send("GET");
+
+    server.stop();
+
+    assert_eq!(value, "VALUE 7");
+}
+
+#[test]
+fn test_width_u16_no_overflow() {
+    let server = TestServer::start(&["127.0.0.1:8102", "--width", "u16"]).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8102").unwrap();
+
+    let add = client.send("OP + 1000");
+    let mul = client.send("OP * 60");
+    let value = client.send("GET");
+
+    server.stop();
+
+    assert_eq!(add, "OK");
+    assert_eq!(mul, "OK");
+    assert_eq!(value, "VALUE 60000");
+}
+
+#[test]
+fn test_width_i64_negative_values() {
+    let server = TestServer::start(&["127.0.0.1:8103", "--width", "i64"]).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8103").unwrap();
+
+    client.send("OP - 5");
+    client.send("OP * -4");
+    client.send("OP - 30");
+    let value = client.send("GET");
+
+    server.stop();
+
+    assert_eq!(value, "VALUE -10");
+}
+
+#[test]
+fn test_width_rejects_out_of_range_operand() {
+    let server = TestServer::start(&["127.0.0.1:8104", "--width", "u16"]).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8104").unwrap();
+
+    let response = client.send("OP + 65536");
+
+    server.stop();
+
+    assert_eq!(response, "ERROR \"parsing error: invalid integer: 65536\"");
+}
+
+#[test]
+fn test_overflow_checked_rejects_operation() {
+    let server = TestServer::start(&["127.0.0.1:8105", "--overflow", "checked"]).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8105").unwrap();
+
+    client.send("OP + 255");
+    let response = client.send("OP + 1");
+    let value = client.send("GET");
+
+    server.stop();
+
+    assert_eq!(response, "ERROR \"overflow\"");
+    assert_eq!(value, "VALUE 255");
+}
+
+#[test]
+fn test_overflow_saturating_clamps() {
+    let server = TestServer::start(&["127.0.0.1:8106", "--overflow", "saturating"]).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8106").unwrap();
+
+    client.send("OP + 200");
+    let response = client.send("OP * 2");
+    let value = client.send("GET");
+
+    server.stop();
+
+    assert_eq!(response, "OK");
+    assert_eq!(value, "VALUE 255");
+}
+
+#[test]
+fn test_extended_operators() {
+    let server = TestServer::start(&["127.0.0.1:8107"]).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8107").unwrap();
+
+    let responses: Vec<String> = ["OP + 7", "OP ^ 2", "OP % 10", "OP << 3", "OP | 1"]
+        .iter()
+        .map(|line| client.send(line))
+        .collect();
+    let division = client.send("OP % 0");
+    let value = client.send("GET");
+
+    server.stop();
+
+    assert!(responses.iter().all(|response| response == "OK"));
+    assert_eq!(division, "ERROR \"division by zero\"");
+    assert_eq!(value, "VALUE 73");
+}
+
+#[test]
+fn test_set_reset_and_unary_operations() {
+    let server = TestServer::start(&["127.0.0.1:8108", "--width", "i16"]).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8108").unwrap();
+
+    let set = client.send("OP = 300");
+    let neg = client.send("OP neg");
+    let after_neg = client.send("GET");
+    let abs = client.send("OP abs");
+    let after_abs = client.send("GET");
+    let not = client.send("OP not");
+    let after_not = client.send("GET");
+    let reset = client.send("RESET");
+    let after_reset = client.send("GET");
+
+    server.stop();
+
+    assert_eq!([set, neg, abs, not, reset], ["OK"; 5]);
+    assert_eq!(after_neg, "VALUE -300");
+    assert_eq!(after_abs, "VALUE 300");
+    assert_eq!(after_not, "VALUE -301");
+    assert_eq!(after_reset, "VALUE 0");
+}
+
+#[test]
+fn test_transaction_commit_and_rollback() {
+    let server = TestServer::start(&["127.0.0.1:8109"]).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8109").unwrap();
+    let mut other = TestClient::connect("127.0.0.1:8109").unwrap();
+
+    let begin = client.send("BEGIN");
+    client.send("OP + 10");
+    client.send("OP * 2");
+    other.send("OP + 1");
+    let before_commit = other.send("GET");
+    let commit = client.send("COMMIT");
+    let after_commit = other.send("GET");
+
+    client.send("BEGIN");
+    client.send("OP - 5");
+    let rollback = client.send("ROLLBACK");
+    let after_rollback = client.send("GET");
+
+    server.stop();
+
+    assert_eq!(begin, "OK");
+    assert_eq!(before_commit, "VALUE 1");
+    assert_eq!(commit, "OK");
+    assert_eq!(after_commit, "VALUE 22");
+    assert_eq!(rollback, "OK");
+    assert_eq!(after_rollback, "VALUE 22");
+}
+
+#[test]
+fn test_transaction_aborted_on_error() {
+    let server = TestServer::start(&["127.0.0.1:8110"]).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8110").unwrap();
+
+    client.send("OP + 3");
+    client.send("BEGIN");
+    client.send("OP * 5");
+    client.send("OP / 0");
+    client.send("OP + 1");
+    let commit = client.send("COMMIT");
+    let value = client.send("GET");
+
+    server.stop();
+
+    assert_eq!(
+        commit,
+        "ERROR \"transaction aborted at operation 2: division by zero\""
+    );
+    assert_eq!(value, "VALUE 3");
+}
+
+#[test]
+fn test_compare_and_set() {
+    let server = TestServer::start(&["127.0.0.1:8111"]).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8111").unwrap();
+    let mut other = TestClient::connect("127.0.0.1:8111").unwrap();
+
+    let first = client.send("CAS 0 10");
+    let stale = other.send("CAS 0 20");
+    let retried = other.send("CAS 10 20");
+    let conditional_stale = client.send("OP + 1 IF 10");
+    let conditional = client.send("OP + 1 IF 20");
+    let value = client.send("GET");
+
+    server.stop();
+
+    assert_eq!(first, "OK");
+    assert_eq!(stale, "ERROR \"compare failed: current value 10\"");
+    assert_eq!(retried, "OK");
+    assert_eq!(
+        conditional_stale,
+        "ERROR \"compare failed: current value 20\""
+    );
+    assert_eq!(conditional, "OK");
+    assert_eq!(value, "VALUE 21");
+}
+
+#[test]
+fn test_eval_expression() {
+    let server = TestServer::start(&["127.0.0.1:8112", "--width", "i32"]).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8112").unwrap();
+
+    client.send("OP = 4");
+    let eval = client.send("EVAL (x + 3) * 2 - x ^ 2");
+    let value = client.send("GET");
+    let invalid = client.send("EVAL (x + 3");
+    let unchanged = client.send("GET");
+
+    server.stop();
+
+    assert_eq!(eval, "OK");
+    assert_eq!(value, "VALUE -2");
+    assert_eq!(
+        invalid,
+        "ERROR \"parsing error: invalid expression: missing )\""
+    );
+    assert_eq!(unchanged, "VALUE -2");
+}
+
+#[test]
+fn test_history() {
+    let server = TestServer::start(&["127.0.0.1:8113"]).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8113").unwrap();
+
+    client.send("OP + 5");
+    client.send("OP / 0");
+    client.send("OP * 3");
+    client.send("EVAL x + 1");
+    let header = client.send("HISTORY 2");
+    let first = client.read_line();
+    let second = client.read_line();
+
+    server.stop();
+
+    assert_eq!(header, "HISTORY 2");
+    assert!(first.starts_with("ENTRY 2 "), "Got: '{}'", first);
+    assert!(first.ends_with(" 15 OP * 3"), "Got: '{}'", first);
+    assert!(second.starts_with("ENTRY 3 "), "Got: '{}'", second);
+    assert!(second.ends_with(" 16 EVAL (x + 1)"), "Got: '{}'", second);
+}
+
+#[test]
+fn test_undo_redo() {
+    let server = TestServer::start(&["127.0.0.1:8114", "--undo", "2"]).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8114").unwrap();
+    let mut other = TestClient::connect("127.0.0.1:8114").unwrap();
+
+    client.send("OP + 5");
+    client.send("OP + 5");
+    other.send("OP * 0");
+    let undo = client.send("UNDO");
+    let after_undo = client.send("GET");
+    let redo = other.send("REDO");
+    let after_redo = client.send("GET");
+    client.send("UNDO");
+    client.send("UNDO");
+    let too_deep = client.send("UNDO");
+    let value = client.send("GET");
+
+    server.stop();
+
+    assert_eq!(undo, "OK");
+    assert_eq!(after_undo, "VALUE 10");
+    assert_eq!(redo, "OK");
+    assert_eq!(after_redo, "VALUE 0");
+    assert_eq!(too_deep, "ERROR \"nothing to undo\"");
+    assert_eq!(value, "VALUE 5");
+}
+
+#[test]
+fn test_state_survives_restart() {
+    let dir = std::env::temp_dir().join(format!("tpi_protocol_data_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let dir = dir.to_string_lossy().into_owned();
+    let args = [
+        "127.0.0.1:8115",
+        "--data-dir",
+        &dir,
+        "--snapshot-every",
+        "3",
+    ];
+
+    let server = TestServer::start(&args).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8115").unwrap();
+    client.send("OP + 5");
+    client.send("USE team_a");
+    client.send("BEGIN");
+    client.send("OP + 7");
+    client.send("OP * 2");
+    client.send("COMMIT");
+    client.send("OP + 1");
+    client.send("UNDO");
+    client.send("USE default");
+    client.send("OP * 3");
+    server.stop();
+
+    let server = TestServer::start(&args).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8115").unwrap();
+    let value = client.send("GET");
+    client.send("USE team_a");
+    let value_a = client.send("GET");
+    server.stop();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(value, "VALUE 15");
+    assert_eq!(value_a, "VALUE 14");
+}
+
+fn eventually_get(client: &mut TestClient, expected: &str) -> String {
+    let mut value = client.send("GET");
+    for _ in 0..30 {
+        if value == expected {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+        value = client.send("GET");
+    }
+    value
+}
+
+#[test]
+fn test_replication_and_promotion() {
+    let token = ["--admin-token", "secret"];
+    let primary = TestServer::start(&["127.0.0.1:8116", token[0], token[1]]).unwrap();
+    let first_args = [
+        "127.0.0.1:8117",
+        "--replica-of",
+        "127.0.0.1:8116",
+        token[0],
+        token[1],
+    ];
+    let first = TestServer::start(&first_args).unwrap();
+    let primaries = "127.0.0.1:8116,127.0.0.1:8117";
+    let second_args = [
+        "127.0.0.1:8118",
+        "--replica-of",
+        primaries,
+        token[0],
+        token[1],
+    ];
+    let second = TestServer::start(&second_args).unwrap();
+
+    let mut client = TestClient::connect("127.0.0.1:8116").unwrap();
+    let mut first_client = TestClient::connect("127.0.0.1:8117").unwrap();
+    let mut second_client = TestClient::connect("127.0.0.1:8118").unwrap();
+    client.send("OP + 5");
+    client.send("BEGIN");
+    client.send("OP * 3");
+    client.send("COMMIT");
+    let replicated = eventually_get(&mut second_client, "VALUE 15");
+    let rejected = first_client.send("OP + 1");
+
+    primary.stop();
+    let unauthorized = first_client.send("PROMOTE wrong");
+    let promoted = first_client.send("PROMOTE secret");
+    first_client.send("OP + 1");
+    let failed_over = eventually_get(&mut second_client, "VALUE 16");
+
+    first.stop();
+    second.stop();
+
+    assert_eq!(replicated, "VALUE 15");
+    assert_eq!(rejected, "ERROR \"read only replica\"");
+    assert_eq!(unauthorized, "ERROR \"unauthorized\"");
+    assert_eq!(promoted, "OK");
+    assert_eq!(failed_over, "VALUE 16");
+}
+
+#[test]
+fn test_promotion_fences_old_primary() {
+    let token = ["--admin-token", "secret"];
+    let primary = TestServer::start(&["127.0.0.1:8133", token[0], token[1]]).unwrap();
+    let replica_args = [
+        "127.0.0.1:8134",
+        "--replica-of",
+        "127.0.0.1:8133",
+        token[0],
+        token[1],
+    ];
+    let replica = TestServer::start(&replica_args).unwrap();
+
+    let mut client = TestClient::connect("127.0.0.1:8133").unwrap();
+    let mut replica_client = TestClient::connect("127.0.0.1:8134").unwrap();
+    client.send("OP + 5");
+    let replicated = eventually_get(&mut replica_client, "VALUE 5");
+    let promoted = replica_client.send("PROMOTE secret");
+
+    let mut fenced = client.send("OP + 0");
+    for _ in 0..30 {
+        if fenced != "OK" {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+        fenced = client.send("OP + 0");
+    }
+
+    primary.stop();
+    replica.stop();
+
+    assert_eq!(replicated, "VALUE 5");
+    assert_eq!(promoted, "OK");
+    assert_eq!(fenced, "ERROR \"read only replica\"");
+}
+
+#[test]
+fn test_request_ids_across_connections() {
+    let server = TestServer::start(&["127.0.0.1:8119"]).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8119").unwrap();
+    client.send("SESSION abc");
+    let first = client.send("OP + 5 ID 1");
+
+    let mut retry = TestClient::connect("127.0.0.1:8119").unwrap();
+    retry.send("SESSION abc");
+    let repeated = retry.send("OP + 5 ID 1");
+    let next = retry.send("OP * 2 ID 2");
+    let value = retry.send("GET");
+
+    server.stop();
+
+    assert_eq!(first, "OK");
+    assert_eq!(repeated, "OK");
+    assert_eq!(next, "OK");
+    assert_eq!(value, "VALUE 10");
+}
+
+#[test]
+fn test_batch_applies_block_of_operations() {
+    let server = TestServer::start(&["127.0.0.1:8121"]).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8121").unwrap();
+
+    client
+        .stream
+        .write_all(b"BATCH 4\nOP + 5\nOP $ 3\nOP * 2\nOP - 1\n")
+        .unwrap();
+    let summary = client.read_line();
+    let error = client.read_line();
+    let value = client.send("GET");
+
+    server.stop();
+
+    assert_eq!(summary, "BATCH 3 1 VALUE 9");
+    assert!(error.starts_with("LINE 2 ERROR"), "Got: '{}'", error);
+    assert_eq!(value, "VALUE 9");
+}
+
+#[test]
+fn test_binary_and_text_clients_share_port() {
+    use tpi_calculadora_distribuida::{binary, operation::Operation};
+
+    let server = TestServer::start(&["127.0.0.1:8122"]).unwrap();
+    let mut stream = TcpStream::connect("127.0.0.1:8122").unwrap();
+    stream.write_all(&[binary::MAGIC]).unwrap();
+
+    let mut responses = vec![];
+    for op in [Operation::Add(5), Operation::Div(0), Operation::Get] {
+        let record = binary::encode_operation(&op).unwrap();
+        binary::write_frame(&stream, &record).unwrap();
+        let frame = binary::read_frame(&mut stream).unwrap().unwrap();
+        responses.push(binary::decode_response(&frame).unwrap());
+    }
+    let mut text = TestClient::connect("127.0.0.1:8122").unwrap();
+    let value = text.send("GET");
+
+    server.stop();
+
+    assert_eq!(responses[0], "OK");
+    assert_eq!(responses[1], "ERROR \"division_by_zero\"");
+    assert_eq!(responses[2], "VALUE 5");
+    assert_eq!(value, "VALUE 5");
+}
+
+#[test]
+fn test_json_lines_requests() {
+    let server = TestServer::start(&["127.0.0.1:8123"]).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8123").unwrap();
+
+    let applied = client.send(r#"{"op": "+", "operand": 3}"#);
+    let failed = client.send(r#"{"op": "/", "operand": 0}"#);
+    let value = client.send(r#"{"op": "GET"}"#);
+    let text = client.send("GET");
+
+    server.stop();
+
+    assert_eq!(applied, r#"{"status":"ok"}"#);
+    assert_eq!(
+        failed,
+        r#"{"status":"error","code":"division_by_zero","message":"division by zero"}"#
+    );
+    assert_eq!(value, r#"{"status":"ok","value":3}"#);
+    assert_eq!(text, "VALUE 3");
+}
+
+fn http_request(addr: &str, request: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn test_http_front_end() {
+    let server = TestServer::start(&["127.0.0.1:8124", "--http", "127.0.0.1:8125"]).unwrap();
+    let http = "127.0.0.1:8125";
+
+    let applied = http_request(
+        http,
+        "POST /op HTTP/1.1\r\nContent-Length: 24\r\nConnection: close\r\n\r\n{\"op\":\"+\",\"operand\":5}\r\n",
+    );
+    let failed = http_request(
+        http,
+        "POST /op HTTP/1.1\r\nContent-Length: 6\r\nConnection: close\r\n\r\nOP / 0",
+    );
+    let value = http_request(http, "GET /value HTTP/1.1\r\nConnection: close\r\n\r\n");
+    let history = http_request(http, "GET /history?count=1 HTTP/1.0\r\n\r\n");
+    let missing = http_request(http, "GET /missing HTTP/1.0\r\n\r\n");
+    let mut client = TestClient::connect("127.0.0.1:8124").unwrap();
+    let text = client.send("GET");
+
+    server.stop();
+
+    assert!(applied.starts_with("HTTP/1.1 200 OK"), "Got: '{}'", applied);
+    assert!(failed.starts_with("HTTP/1.1 400"), "Got: '{}'", failed);
+    assert!(failed.ends_with(r#""code":"division_by_zero","message":"division by zero"}"#));
+    assert!(
+        value.ends_with(r#"{"status":"ok","value":5}"#),
+        "Got: '{}'",
+        value
+    );
+    assert!(
+        history.contains(r#""operation":"OP + 5""#),
+        "Got: '{}'",
+        history
+    );
+    assert!(missing.starts_with("HTTP/1.1 404"), "Got: '{}'", missing);
+    assert_eq!(text, "VALUE 5");
+}
+
+#[test]
+fn test_log_file_records_events() {
+    let dir = std::env::temp_dir().join(format!("tpi_log_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("server.log").to_string_lossy().into_owned();
+    let args = [
+        "127.0.0.1:8126",
+        "--log-file",
+        &path,
+        "--log-level",
+        "debug",
+    ];
+    let server = TestServer::start(&args).unwrap();
+
+    let mut client = TestClient::connect("127.0.0.1:8126").unwrap();
+    client.send("OP + 5");
+    client.send("OP / 0");
+    client.send("GET");
+    thread::sleep(Duration::from_millis(200));
+
+    server.stop();
+    let log = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert!(log.contains(" INFO client "), "Got: '{}'", log);
+    assert!(log.contains(" DEBUG client 1: OP + 5"), "Got: '{}'", log);
+    assert!(
+        log.contains(" ERROR client 1: OP / 0 -> ERROR \"division by zero\""),
+        "Got: '{}'",
+        log
+    );
+}
+
+#[test]
+fn test_shutdown_drains_clients_and_keeps_state() {
+    let dir = std::env::temp_dir().join(format!("tpi_shutdown_data_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let dir = dir.to_string_lossy().into_owned();
+    let args = [
+        "127.0.0.1:8127",
+        "--data-dir",
+        &dir,
+        "--admin-token",
+        "secret",
+    ];
+
+    let mut server = TestServer::start(&args).unwrap();
+    let mut idle = TestClient::connect("127.0.0.1:8127").unwrap();
+    idle.send("OP + 7");
+    let mut admin = TestClient::connect("127.0.0.1:8127").unwrap();
+    let rejected = admin.send("SHUTDOWN wrong");
+    let accepted = admin.send("SHUTDOWN secret");
+    let exited = server.wait_exit();
+    let refused = TcpStream::connect("127.0.0.1:8127").is_err();
+    server.stop();
+
+    let server = TestServer::start(&args).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8127").unwrap();
+    let value = client.send("GET");
+    server.stop();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(rejected, "ERROR \"unauthorized\"");
+    assert_eq!(accepted, "OK");
+    assert!(
+        exited,
+        "The server should exit once its clients are drained"
+    );
+    assert!(refused, "The server should stop accepting connections");
+    assert_eq!(value, "VALUE 7");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_sigterm_shuts_down_and_writes_snapshot() {
+    let dir = std::env::temp_dir().join(format!("tpi_sigterm_data_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.to_string_lossy().into_owned();
+    let args = ["127.0.0.1:8132", "--data-dir", &path];
+
+    let mut server = TestServer::start(&args).unwrap();
+    let mut idle = TestClient::connect("127.0.0.1:8132").unwrap();
+    idle.send("OP + 7");
+    let pid = server.process.id().to_string();
+    let signaled = Command::new("kill").args(["-TERM", &pid]).status().unwrap();
+    let exited = server.wait_exit();
+    let status = server.process.try_wait().unwrap();
+    let snapshot = std::fs::read_to_string(dir.join("snapshot")).unwrap_or_default();
+    server.stop();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert!(signaled.success());
+    assert!(exited, "The server should exit on SIGTERM");
+    assert!(status.is_some_and(|status| status.success()));
+    assert!(snapshot.contains("default 7"), "Got: '{}'", snapshot);
+}
+
+#[test]
+fn test_busy_server_rejects_connections() {
+    let args = ["127.0.0.1:8128", "--workers", "1", "--accept-queue", "1"];
+    let server = TestServer::start(&args).unwrap();
+    let mut served = TestClient::connect("127.0.0.1:8128").unwrap();
+    let first = served.send("OP + 2");
+    let mut queued = TestClient::connect("127.0.0.1:8128").unwrap();
+    thread::sleep(Duration::from_millis(200));
+    let mut rejected = TestClient::connect("127.0.0.1:8128").unwrap();
+    let busy = rejected.read_line();
+
+    {
+        let _served = served;
+    }
+    let value = queued.send("GET");
+    server.stop();
+
+    assert_eq!(first, "OK");
+    assert_eq!(busy, "ERROR \"server busy\"");
+    assert_eq!(value, "VALUE 2");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_event_loop_serves_every_protocol() {
+    use tpi_calculadora_distribuida::{binary, operation::Operation};
+
+    let args = ["127.0.0.1:8129", "--mode", "event-loop"];
+    let server = TestServer::start(&args).unwrap();
+    let _idle: Vec<TcpStream> = (0..200)
+        .map(|_| TcpStream::connect("127.0.0.1:8129").unwrap())
+        .collect();
+    let mut client = TestClient::connect("127.0.0.1:8129").unwrap();
+    let applied = client.send("OP + 2");
+    let json = client.send(r#"{"op": "*", "operand": 3}"#);
+    client
+        .stream
+        .write_all(b"BATCH 2\nOP + 1\nOP / 0\n")
+        .unwrap();
+    let batch = [client.read_line(), client.read_line()];
+
+    let mut stream = TcpStream::connect("127.0.0.1:8129").unwrap();
+    stream.write_all(&[binary::MAGIC]).unwrap();
+    let record = binary::encode_operation(&Operation::Get).unwrap();
+    binary::write_frame(&stream, &record).unwrap();
+    let frame = binary::read_frame(&mut stream).unwrap().unwrap();
+    let value = binary::decode_response(&frame).unwrap();
+
+    server.stop();
+
+    assert_eq!(applied, "OK");
+    assert_eq!(json, r#"{"status":"ok"}"#);
+    assert_eq!(batch[0], "BATCH 1 1 VALUE 7");
+    assert_eq!(batch[1], "LINE 2 ERROR \"division by zero\"");
+    assert_eq!(value, "VALUE 7");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_event_loop_shutdown_bounds_slow_clients() {
+    let args = [
+        "127.0.0.1:8135",
+        "--mode",
+        "event-loop",
+        "--admin-token",
+        "secret",
+    ];
+    let mut server = TestServer::start(&args).unwrap();
+    let mut client = TestClient::connect("127.0.0.1:8135").unwrap();
+    let batch = format!("BATCH 1000\n{}", "OP + 1\n".repeat(1000));
+    client.stream.write_all(batch.as_bytes()).unwrap();
+    client.read_line();
+    let requests = "HISTORY 1000\n".repeat(1000);
+    let _slow: Vec<TcpStream> = (0..6)
+        .map(|_| {
+            let mut stream = TcpStream::connect("127.0.0.1:8135").unwrap();
+            stream.write_all(requests.as_bytes()).unwrap();
+            stream
+        })
+        .collect();
+    thread::sleep(Duration::from_millis(500));
+
+    let mut admin = TestClient::connect("127.0.0.1:8135").unwrap();
+    let accepted = admin.send("SHUTDOWN secret");
+    let exited = server.wait_exit();
+    server.stop();
+
+    assert_eq!(accepted, "OK");
+    assert!(
+        exited,
+        "The server should stop flushing slow clients at a single deadline"
+    );
+}
+
+#[test]
+fn test_atomic_backend_concurrent_clients() {
+    let args = ["127.0.0.1:8130", "--backend", "atomic", "--width", "u16"];
+    let server = TestServer::start(&args).unwrap();
+    let clients: Vec<_> = (0..4)
+        .map(|_| {
+            thread::spawn(|| {
+                let mut client = TestClient::connect("127.0.0.1:8130").unwrap();
+                for _ in 0..50 {
+                    client.send("OP + 1");
+                }
+            })
+        })
+        .collect();
+    for client in clients {
+        client.join().unwrap();
+    }
+    let mut client = TestClient::connect("127.0.0.1:8130").unwrap();
+    let value = client.send("GET");
+    let history = client.send("HISTORY");
+    server.stop();
+
+    assert_eq!(value, "VALUE 200");
+    assert_eq!(history, "ERROR \"unexpected message: HISTORY\"");
+}